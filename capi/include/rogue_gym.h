@@ -0,0 +1,95 @@
+/* C API of rogue-gym, generated from capi/src/lib.rs by cbindgen.
+ * Don't edit this file by hand, run `ci/check-capi-header.sh --update` instead. */
+
+#ifndef ROGUE_GYM_H
+#define ROGUE_GYM_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ * an environment with the latest observation and step info
+ */
+typedef struct RogueGym RogueGym;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ * The last error message in this thread, or NULL.
+ * The pointer is valid until the next error.
+ */
+const char *rogue_gym_last_error(void);
+
+/*
+ * Number of actions, which are indices of `KeyMap::AI_KEYS`(".hjklnbuy>sHJKLNBUY")
+ */
+size_t rogue_gym_num_actions(void);
+
+/*
+ * Length of status vectors
+ */
+size_t rogue_gym_status_len(void);
+
+/*
+ * Creates an environment from config json(NULL means the default config),
+ * or returns NULL on failure
+ */
+RogueGym *rogue_gym_new(const char *config_json, size_t max_steps);
+
+/*
+ * Frees an environment created by `rogue_gym_new`
+ */
+void rogue_gym_free(RogueGym *env);
+
+/*
+ * Starts a new episode with `*seed`, or the seed in the config if `seed` is NULL
+ */
+int rogue_gym_reset(RogueGym *env, const uint64_t *seed);
+
+/*
+ * Steps the environment by the action index, and writes the reward and whether
+ * the episode ended into `reward` and `done`, if they aren't NULL
+ */
+int rogue_gym_step(RogueGym *env, uint32_t action, float *reward, int *done);
+
+/*
+ * Writes the size of the screen
+ */
+int rogue_gym_screen_size(const RogueGym *env, size_t *height, size_t *width);
+
+/*
+ * Copies the screen as `height * width` ASCII bytes in row major order
+ */
+int rogue_gym_copy_screen(const RogueGym *env, uint8_t *buf, size_t len);
+
+/*
+ * Copies cells the player has visited as `height * width` bytes of 0 or 1
+ */
+int rogue_gym_copy_history(const RogueGym *env, uint8_t *buf, size_t len);
+
+/*
+ * Copies the status vector, whose length is `rogue_gym_status_len()`
+ */
+int rogue_gym_copy_status(const RogueGym *env, uint32_t *buf, size_t len);
+
+/*
+ * Copies legality of actions as `rogue_gym_num_actions()` bytes of 0 or 1
+ */
+int rogue_gym_copy_action_mask(const RogueGym *env, uint8_t *buf, size_t len);
+
+/*
+ * Reward terms, events and episode aggregates of the last step as json, or NULL
+ * on failure. The pointer is valid until the next call of `rogue_gym_step`,
+ * `rogue_gym_reset` or `rogue_gym_free`.
+ */
+const char *rogue_gym_events(RogueGym *env);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* ROGUE_GYM_H */
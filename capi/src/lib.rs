@@ -0,0 +1,307 @@
+//! C API of rogue-gym, built on `rogue_gym_core::env::RogueEnv`
+//!
+//! All functions returning `c_int` return 0 on success and -1 on failure, and
+//! the error message can be taken by `rogue_gym_last_error`.
+//! `include/rogue_gym.h` has to be updated when this API changes.
+// pointers from C are checked for NULL, and the rest is up to callers
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+use rogue_gym_core::character::player::Status;
+use rogue_gym_core::env::{Env, Observation, RogueEnv, StepInfo, AI_ACTIONS};
+use rogue_gym_core::error::*;
+use rogue_gym_core::GameConfig;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// runs `f` and records the error or panic in it
+fn guard<T>(default: T, f: impl FnOnce() -> GameResult<T>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(t)) => t,
+        Ok(Err(e)) => {
+            let msgs: Vec<_> = e.iter_chain().map(|e| e.to_string()).collect();
+            set_error(msgs.join(": "));
+            default
+        }
+        Err(_) => {
+            set_error("panicked in rogue-gym".to_owned());
+            default
+        }
+    }
+}
+
+fn status_code(res: GameResult<()>) -> GameResult<c_int> {
+    res.map(|_| 0)
+}
+
+fn invalid(msg: &'static str) -> failure::Error {
+    ErrorId::InvalidSetting.into_with(|| msg)
+}
+
+/// an environment with the latest observation and step info
+pub struct RogueGym {
+    env: RogueEnv,
+    obs: Observation,
+    info: StepInfo,
+    info_json: Option<CString>,
+}
+
+impl RogueGym {
+    fn from_ptr<'a>(ptr: *const RogueGym) -> GameResult<&'a RogueGym> {
+        unsafe { ptr.as_ref() }.ok_or_else(|| invalid("env is NULL"))
+    }
+    fn from_mut_ptr<'a>(ptr: *mut RogueGym) -> GameResult<&'a mut RogueGym> {
+        unsafe { ptr.as_mut() }.ok_or_else(|| invalid("env is NULL"))
+    }
+}
+
+/// copies `src` into the caller's buffer, which has to have `len >= src.len()`
+fn copy_to<T: Copy>(
+    src: impl ExactSizeIterator<Item = T>,
+    buf: *mut T,
+    len: usize,
+) -> GameResult<()> {
+    if buf.is_null() {
+        return Err(invalid("buffer is NULL"));
+    }
+    if len < src.len() {
+        return Err(ErrorId::InvalidSetting
+            .into_with(move || format!("buffer is too small: {} < {}", len, src.len())));
+    }
+    let buf = unsafe { slice::from_raw_parts_mut(buf, len) };
+    buf.iter_mut().zip(src).for_each(|(b, s)| *b = s);
+    Ok(())
+}
+
+/// The last error message in this thread, or NULL.
+/// The pointer is valid until the next error.
+#[no_mangle]
+pub extern "C" fn rogue_gym_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Number of actions, which are indices of `KeyMap::AI_KEYS`(".hjklnbuy>sHJKLNBUY")
+#[no_mangle]
+pub extern "C" fn rogue_gym_num_actions() -> usize {
+    AI_ACTIONS.len()
+}
+
+/// Length of status vectors
+#[no_mangle]
+pub extern "C" fn rogue_gym_status_len() -> usize {
+    Status::default().to_vec().len()
+}
+
+/// Creates an environment from config json(NULL means the default config),
+/// or returns NULL on failure
+#[no_mangle]
+pub extern "C" fn rogue_gym_new(config_json: *const c_char, max_steps: usize) -> *mut RogueGym {
+    guard(ptr::null_mut(), || {
+        let config = if config_json.is_null() {
+            GameConfig::default()
+        } else {
+            let json = unsafe { CStr::from_ptr(config_json) };
+            GameConfig::from_json(json.to_str().compat()?)?
+        };
+        let env = RogueEnv::new(config, max_steps, false)?;
+        let obs = env.observe()?;
+        let env = RogueGym {
+            env,
+            obs,
+            info: StepInfo::default(),
+            info_json: None,
+        };
+        Ok(Box::into_raw(Box::new(env)))
+    })
+}
+
+/// Frees an environment created by `rogue_gym_new`
+#[no_mangle]
+pub extern "C" fn rogue_gym_free(env: *mut RogueGym) {
+    if !env.is_null() {
+        unsafe { drop(Box::from_raw(env)) }
+    }
+}
+
+/// Starts a new episode with `*seed`, or the seed in the config if `seed` is NULL
+#[no_mangle]
+pub extern "C" fn rogue_gym_reset(env: *mut RogueGym, seed: *const u64) -> c_int {
+    guard(-1, || {
+        let env = RogueGym::from_mut_ptr(env)?;
+        let seed = unsafe { seed.as_ref() }.map(|&s| u128::from(s));
+        env.obs = env.env.reset(seed)?;
+        env.info = StepInfo::default();
+        env.info_json = None;
+        Ok(0)
+    })
+}
+
+/// Steps the environment by the action index, and writes the reward and whether
+/// the episode ended into `reward` and `done`, if they aren't NULL
+#[no_mangle]
+pub extern "C" fn rogue_gym_step(
+    env: *mut RogueGym,
+    action: u32,
+    reward: *mut f32,
+    done: *mut c_int,
+) -> c_int {
+    guard(-1, || {
+        let env = RogueGym::from_mut_ptr(env)?;
+        let action = *AI_ACTIONS
+            .get(action as usize)
+            .ok_or_else(|| invalid("action index is out of range"))?;
+        let (obs, r, d, info) = env.env.step(action)?;
+        env.obs = obs;
+        env.info = info;
+        env.info_json = None;
+        if let Some(reward) = unsafe { reward.as_mut() } {
+            *reward = r;
+        }
+        if let Some(done) = unsafe { done.as_mut() } {
+            *done = c_int::from(d);
+        }
+        Ok(0)
+    })
+}
+
+/// Writes the size of the screen
+#[no_mangle]
+pub extern "C" fn rogue_gym_screen_size(
+    env: *const RogueGym,
+    height: *mut usize,
+    width: *mut usize,
+) -> c_int {
+    guard(-1, || {
+        let (h, w) = RogueGym::from_ptr(env)?.obs.map.dim();
+        match unsafe { (height.as_mut(), width.as_mut()) } {
+            (Some(height), Some(width)) => {
+                *height = h;
+                *width = w;
+                Ok(0)
+            }
+            _ => Err(invalid("height or width is NULL")),
+        }
+    })
+}
+
+/// Copies the screen as `height * width` ASCII bytes in row major order
+#[no_mangle]
+pub extern "C" fn rogue_gym_copy_screen(env: *const RogueGym, buf: *mut u8, len: usize) -> c_int {
+    guard(-1, || {
+        let map = &RogueGym::from_ptr(env)?.obs.map;
+        status_code(copy_to(map.iter().cloned(), buf, len))
+    })
+}
+
+/// Copies cells the player has visited as `height * width` bytes of 0 or 1
+#[no_mangle]
+pub extern "C" fn rogue_gym_copy_history(env: *const RogueGym, buf: *mut u8, len: usize) -> c_int {
+    guard(-1, || {
+        let history = &RogueGym::from_ptr(env)?.obs.history;
+        status_code(copy_to(history.iter().map(|&b| u8::from(b)), buf, len))
+    })
+}
+
+/// Copies the status vector, whose length is `rogue_gym_status_len()`
+#[no_mangle]
+pub extern "C" fn rogue_gym_copy_status(env: *const RogueGym, buf: *mut u32, len: usize) -> c_int {
+    guard(-1, || {
+        let status = RogueGym::from_ptr(env)?.obs.status.to_vec();
+        status_code(copy_to(status.into_iter(), buf, len))
+    })
+}
+
+/// Copies legality of actions as `rogue_gym_num_actions()` bytes of 0 or 1
+#[no_mangle]
+pub extern "C" fn rogue_gym_copy_action_mask(
+    env: *const RogueGym,
+    buf: *mut u8,
+    len: usize,
+) -> c_int {
+    guard(-1, || {
+        let mask = &RogueGym::from_ptr(env)?.obs.action_mask;
+        status_code(copy_to(mask.iter().map(|&b| u8::from(b)), buf, len))
+    })
+}
+
+/// Reward terms, events and episode aggregates of the last step as json, or NULL
+/// on failure. The pointer is valid until the next call of `rogue_gym_step`,
+/// `rogue_gym_reset` or `rogue_gym_free`.
+#[no_mangle]
+pub extern "C" fn rogue_gym_events(env: *mut RogueGym) -> *const c_char {
+    guard(ptr::null(), || {
+        let env = RogueGym::from_mut_ptr(env)?;
+        if env.info_json.is_none() {
+            let json = serde_json::to_string(&env.info).compat()?;
+            env.info_json = Some(CString::new(json).compat()?);
+        }
+        Ok(env.info_json.as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    const CONFIG: &[u8] = b"{\"seed\": 1, \"enemies\": {\"enemies\": []}}\0";
+    #[test]
+    fn play() {
+        let env = rogue_gym_new(CONFIG.as_ptr() as *const c_char, 3);
+        assert!(!env.is_null());
+        let (mut h, mut w) = (0, 0);
+        assert_eq!(rogue_gym_screen_size(env, &mut h, &mut w), 0);
+        assert_eq!((h, w), (24, 80));
+        let mut screen = vec![0u8; h * w];
+        assert_eq!(rogue_gym_copy_screen(env, screen.as_mut_ptr(), h * w), 0);
+        assert!(screen.contains(&b'@'));
+        assert_eq!(rogue_gym_copy_screen(env, screen.as_mut_ptr(), 10), -1);
+        assert!(!rogue_gym_last_error().is_null());
+        let (mut reward, mut done) = (0.0, 0);
+        for i in 0..3 {
+            assert_eq!(rogue_gym_step(env, 10, &mut reward, &mut done), 0);
+            assert_eq!(done, c_int::from(i == 2));
+        }
+        let events = unsafe { CStr::from_ptr(rogue_gym_events(env)) };
+        let events: serde_json::Value = serde_json::from_slice(events.to_bytes()).unwrap();
+        assert_eq!(events["events"]["termination"], "MaxSteps");
+        assert_eq!(rogue_gym_step(env, 10, &mut reward, &mut done), -1);
+        assert_eq!(rogue_gym_step(env, 100, &mut reward, &mut done), -1);
+        assert_eq!(rogue_gym_reset(env, ptr::null()), 0);
+        let mut status = vec![0u32; rogue_gym_status_len()];
+        assert_eq!(
+            rogue_gym_copy_status(env, status.as_mut_ptr(), status.len()),
+            0
+        );
+        assert_eq!(status[0], 1);
+        rogue_gym_free(env);
+    }
+    #[test]
+    fn invalid_config() {
+        let env = rogue_gym_new(b"{\"width\": \0".as_ptr() as *const c_char, 3);
+        assert!(env.is_null());
+        let msg = unsafe { CStr::from_ptr(rogue_gym_last_error()) };
+        assert!(!msg.to_bytes().is_empty());
+    }
+    #[test]
+    fn header_declares_all() {
+        let header = include_str!("../include/rogue_gym.h");
+        let src = include_str!("lib.rs");
+        let exported = src
+            .split("pub extern \"C\" fn ")
+            .skip(1)
+            .map(|s| s.split('(').next().unwrap());
+        for name in exported {
+            assert!(header.contains(&format!("{}(", name)), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+/* plays an episode through the C API, built and run by ci/travis/test.sh */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include "rogue_gym.h"
+
+#define CHECK(cond)                                                   \
+    do {                                                              \
+        if (!(cond)) {                                                \
+            const char *err = rogue_gym_last_error();                 \
+            fprintf(stderr, "%s:%d: %s failed: %s\n", __FILE__,       \
+                    __LINE__, #cond, err ? err : "no error message"); \
+            return 1;                                                 \
+        }                                                             \
+    } while (0)
+
+static const char *CONFIG = "{\"seed\": 1, \"enemies\": {\"enemies\": []}}";
+
+int main(void) {
+    RogueGym *env = rogue_gym_new(CONFIG, 10);
+    CHECK(env != NULL);
+    size_t height = 0, width = 0;
+    CHECK(rogue_gym_screen_size(env, &height, &width) == 0);
+    CHECK(height == 24 && width == 80);
+    uint8_t *screen = malloc(height * width);
+    CHECK(rogue_gym_copy_screen(env, screen, height * width) == 0);
+    CHECK(memchr(screen, '@', height * width) != NULL);
+    CHECK(rogue_gym_copy_screen(env, screen, 1) == -1);
+    CHECK(rogue_gym_last_error() != NULL);
+
+    size_t num_actions = rogue_gym_num_actions();
+    uint8_t *mask = malloc(num_actions);
+    uint32_t *status = malloc(rogue_gym_status_len() * sizeof(uint32_t));
+    int done = 0, steps = 0;
+    float reward = 0.0f;
+    while (!done) {
+        CHECK(rogue_gym_copy_action_mask(env, mask, num_actions) == 0);
+        /* the first legal action except no-op */
+        uint32_t action = 0;
+        for (size_t i = 1; i < num_actions; i++) {
+            if (mask[i]) {
+                action = (uint32_t)i;
+                break;
+            }
+        }
+        CHECK(rogue_gym_step(env, action, &reward, &done) == 0);
+        CHECK(rogue_gym_copy_status(env, status, rogue_gym_status_len()) == 0);
+        steps++;
+    }
+    CHECK(steps == 10);
+    const char *events = rogue_gym_events(env);
+    CHECK(events != NULL && strstr(events, "MaxSteps") != NULL);
+    CHECK(rogue_gym_step(env, 0, NULL, NULL) == -1);
+
+    uint64_t seed = 2;
+    CHECK(rogue_gym_reset(env, &seed) == 0);
+    CHECK(rogue_gym_step(env, 10, NULL, NULL) == 0);
+
+    CHECK(rogue_gym_new("{\"width\": ", 10) == NULL);
+    free(screen);
+    free(mask);
+    free(status);
+    rogue_gym_free(env);
+    printf("capi test: ok\n");
+    return 0;
+}
@@ -0,0 +1,24 @@
+#!/bin/bash
+# Regenerates capi/include/rogue_gym.h by cbindgen, and fails if it differs from the
+# committed one. Run with `--update` to overwrite the header instead.
+
+set -e
+
+CBINDGEN_VERSION=0.26.0
+ROOT=$(cd "$(dirname "$0")/.." && pwd)
+HEADER=$ROOT/capi/include/rogue_gym.h
+
+if [ "$(cbindgen --version 2>/dev/null)" != "cbindgen $CBINDGEN_VERSION" ]; then
+    cargo install cbindgen --version $CBINDGEN_VERSION --force
+fi
+
+GENERATED=$(mktemp)
+trap 'rm -f "$GENERATED"' EXIT
+(cd "$ROOT/capi" && cbindgen --config cbindgen.toml --crate rogue-gym-capi --output "$GENERATED")
+
+if [ "$1" == "--update" ]; then
+    cp "$GENERATED" "$HEADER"
+elif ! diff -u "$HEADER" "$GENERATED"; then
+    echo "capi/include/rogue_gym.h is out of date, run ci/check-capi-header.sh --update" >&2
+    exit 1
+fi
@@ -4,6 +4,11 @@ set -ex
 
 cargo build --verbose --all
 cargo test --manifest-path=core/Cargo.toml
+cargo test --manifest-path=capi/Cargo.toml
+./ci/check-capi-header.sh
+cargo test --manifest-path=server/Cargo.toml
+cc capi/tests/test.c -Icapi/include -Ltarget/debug -lrogue_gym -o target/capi_test
+LD_LIBRARY_PATH=target/debug ./target/capi_test
 cd python
 cargo test --no-default-features
 tox -e py
@@ -7,6 +7,8 @@ use crate::error::*;
 use crate::item::{itembox::Entry as ItemEntry, ItemHandler, ItemToken};
 use crate::ui::UiState;
 use crate::{GameInfo, GameMsg, Reaction};
+use enum_iterator::IntoEnumIterator;
+use std::collections::BTreeSet;
 use std::iter;
 use std::rc::Rc;
 
@@ -52,6 +54,12 @@ pub(crate) fn process_action(
             }
             ui = after_turn(player, enemies, dungeon, &mut out)?;
         },
+        Action::Run(d) => {
+            ui = run_player(d, RunMode::Follow, dungeon, player, enemies, &mut out)?;
+        }
+        Action::RunCautiously(d) => {
+            ui = run_player(d, RunMode::Cautious, dungeon, player, enemies, &mut out)?;
+        }
         Action::Search => {
             out.append(&mut search(dungeon, player)?);
             ui = after_turn(player, enemies, dungeon, &mut out)?;
@@ -61,6 +69,21 @@ pub(crate) fn process_action(
     Ok((ui, out))
 }
 
+/// returns if the action can change the game state
+pub(crate) fn is_legal(action: Action, dungeon: &dyn Dungeon, player: &Player) -> bool {
+    match action {
+        Action::DownStair => dungeon.is_downstair(&player.pos),
+        Action::UpStair => false,
+        Action::Move(d) | Action::MoveUntil(d) | Action::Run(d) | Action::RunCautiously(d) => {
+            dungeon.can_move_player(&player.pos, d).is_some()
+        }
+        // search always passes a turn
+        Action::Search => true,
+        // no-op never changes the game state
+        Action::NoOp => false,
+    }
+}
+
 fn after_turn(
     player: &mut Player,
     enemies: &mut EnemyHandler,
@@ -91,7 +114,7 @@ fn move_active_enemies(
         match fight::enemy_attack(at.enemy(), player, enemies.rng()) {
             Some(hp) => {
                 let name = at.enemy().name();
-                res.push(Reaction::Notify(GameMsg::HitFrom(name.to_owned())));
+                res.push(Reaction::Notify(GameMsg::HitFrom(name.to_owned(), hp)));
                 did_hit = true;
                 match player.get_damage(hp) {
                     DamageReaction::Death => {
@@ -144,7 +167,10 @@ fn player_attack(
     player.buttle();
     enemies.activate(place.clone());
     if let Some(hp) = fight::player_attack(player, None, &*enemy, enemies.rng()) {
-        res.push(Reaction::Notify(GameMsg::HitTo(enemy.name().to_owned())));
+        res.push(Reaction::Notify(GameMsg::HitTo(
+            enemy.name().to_owned(),
+            hp,
+        )));
         match enemy.get_damage(hp) {
             DamageReaction::Death => {
                 enemies.remove(place);
@@ -191,6 +217,142 @@ fn move_player(
     Ok((res, done))
 }
 
+/// Variants of run actions
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RunMode {
+    /// follows passages and stops at forks, doors and stairs
+    Follow,
+    /// in addition to `Follow`, stops when something interesting comes into view
+    Cautious,
+}
+
+impl RunMode {
+    /// judge if a message notified while running stops the player
+    fn is_interrupted_by(self, msg: &GameMsg) -> bool {
+        match msg {
+            GameMsg::CantMove(_) | GameMsg::GotItem { .. } => true,
+            GameMsg::HitTo(..) | GameMsg::MissTo(_) | GameMsg::Killed(_) => true,
+            GameMsg::HitFrom(..) | GameMsg::MissFrom(_) => true,
+            // following run steps over items which the player can't pick up, as rogue does
+            GameMsg::CantGetItem(_) => self == RunMode::Cautious,
+            GameMsg::NoDownStair | GameMsg::SecretDoor | GameMsg::Quit => self == RunMode::Cautious,
+        }
+    }
+}
+
+/// runs longer than this stop, e.g. in a loop of passages which doesn't contain the start
+pub(crate) const MAX_RUN_STEPS: usize = 256;
+
+fn run_player(
+    mut direction: Direction,
+    mode: RunMode,
+    dungeon: &mut dyn Dungeon,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+    out: &mut Vec<Reaction>,
+) -> GameResult<Option<UiState>> {
+    let start = player.pos.clone();
+    let mut in_sight = match mode {
+        RunMode::Follow => BTreeSet::new(),
+        RunMode::Cautious => interesting_objects(dungeon, player, enemies),
+    };
+    for step in 0..MAX_RUN_STEPS {
+        if step > 0 {
+            direction = match next_direction(direction, dungeon, &player.pos) {
+                Some(d) => d,
+                None => break,
+            };
+        }
+        let start_len = out.len();
+        let (reactions, _) = move_player(direction, dungeon, player, enemies)?;
+        for r in reactions {
+            if r == Reaction::Redraw && out.contains(&r) {
+                continue;
+            }
+            out.push(r);
+        }
+        let ui = after_turn(player, enemies, dungeon, out)?;
+        if ui.is_some() {
+            return Ok(ui);
+        }
+        let interrupted = out[start_len..].iter().any(|r| match r {
+            Reaction::Notify(msg) => mode.is_interrupted_by(msg),
+            _ => false,
+        });
+        if interrupted || player.pos == start {
+            break;
+        }
+        match dungeon.tile(&player.pos).map(|t| t.to_byte()) {
+            Some(b'+') | Some(b'%') => break,
+            _ => {}
+        }
+        if mode == RunMode::Cautious {
+            let current = interesting_objects(dungeon, player, enemies);
+            if current.difference(&in_sight).next().is_some() {
+                break;
+            }
+            in_sight = current;
+        }
+    }
+    Ok(None)
+}
+
+/// decides the direction of the next running step, turning at corners of passages
+fn next_direction(
+    direction: Direction,
+    dungeon: &mut dyn Dungeon,
+    pos: &DungeonPath,
+) -> Option<Direction> {
+    let forward = dungeon.can_move_player(pos, direction).is_some();
+    let in_passage = dungeon.tile(pos).map(|t| t.to_byte()) == Some(b'#');
+    if !in_passage || direction.is_diag() {
+        return if forward { Some(direction) } else { None };
+    }
+    let branches: Vec<_> = Direction::into_enum_iter()
+        .take(4)
+        .filter(|&d| d != direction && d != direction.reverse())
+        .filter(|&d| {
+            let tile = dungeon
+                .can_move_player(pos, d)
+                .and_then(|next| dungeon.tile(&next).map(|t| t.to_byte()));
+            tile == Some(b'#') || tile == Some(b'+')
+        })
+        .collect();
+    match (forward, branches.len()) {
+        (true, 0) => Some(direction),
+        (false, 1) => Some(branches[0]),
+        // fork or dead end
+        _ => None,
+    }
+}
+
+/// collects positions of enemies, items, stairs and neighboring doors in the player's view
+fn interesting_objects(
+    dungeon: &mut dyn Dungeon,
+    player: &Player,
+    enemies: &EnemyHandler,
+) -> BTreeSet<DungeonPath> {
+    let player_cd = dungeon.path_to_cd(&player.pos);
+    dungeon
+        .draw_ranges()
+        .into_iter()
+        .filter(|path| {
+            if *path == player.pos {
+                return false;
+            }
+            if dungeon.get_item(path).is_some() || dungeon.is_downstair(path) {
+                return true;
+            }
+            if enemies.get_enemy(path).is_some() && dungeon.draw_enemy(&player.pos, path) {
+                return true;
+            }
+            let cd = dungeon.path_to_cd(path);
+            let is_side = cd.x == player_cd.x || cd.y == player_cd.y;
+            is_side && cd.is_adjacent(player_cd) && dungeon.tile(path) == Some(b'+'.into())
+        })
+        .collect()
+}
+
 fn search(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Vec<Reaction>> {
     dungeon.search(&player.pos).map(|v| {
         v.into_iter()
@@ -226,3 +388,160 @@ fn get_item(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Option
         num: got_item.how_many.0,
     }))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dungeon::Coord;
+    use crate::input::InputCode;
+    use crate::{GameConfig, RunTime};
+    const CONFIG: &str = r#"
+{
+    "hide_dungeon": false,
+    "enemies": {
+        "enemies": []
+    }
+}
+"#;
+    fn setup_runtime(seed: u128) -> RunTime {
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        config.seed = Some(seed);
+        config.build().unwrap()
+    }
+    fn path(cd: Coord) -> DungeonPath {
+        [1, cd.x.0, cd.y.0].into()
+    }
+    fn is_passage(runtime: &mut RunTime, cd: Coord) -> bool {
+        runtime.dungeon.tile(&path(cd)).map(|t| t.to_byte()) == Some(b'#')
+    }
+    fn passage_neighbors(runtime: &mut RunTime, cd: Coord) -> Vec<Direction> {
+        Direction::into_enum_iter()
+            .take(4)
+            .filter(|d| is_passage(runtime, cd + d.to_cd()))
+            .collect()
+    }
+    /// finds a straight passage cell next to a corner, and the direction to the corner
+    fn find_corner(runtime: &mut RunTime) -> Option<(Coord, Direction)> {
+        let (w, h) = runtime.screen_size();
+        for y in 1..h.0 - 1 {
+            for x in 0..w.0 {
+                let corner = Coord::new(x, y);
+                if !is_passage(runtime, corner) {
+                    continue;
+                }
+                let arms = passage_neighbors(runtime, corner);
+                if arms.len() != 2 || arms[0] == arms[1].reverse() {
+                    continue;
+                }
+                let start = corner + arms[0].to_cd();
+                let straight = passage_neighbors(runtime, start);
+                if straight.len() == 2 && straight[0] == straight[1].reverse() {
+                    return Some((start, arms[0].reverse()));
+                }
+            }
+        }
+        None
+    }
+    #[test]
+    fn run_turns_at_corners() {
+        let mut tested = 0;
+        for seed in 0..20 {
+            let mut runtime = setup_runtime(seed);
+            let (start, direction) = match find_corner(&mut runtime) {
+                Some(t) => t,
+                None => continue,
+            };
+            let corner = start + direction.to_cd();
+            runtime.player.pos = path(start);
+            runtime
+                .react_to_input(InputCode::Act(Action::MoveUntil(direction)))
+                .unwrap();
+            assert_eq!(runtime.player.pos, path(corner));
+            runtime.player.pos = path(start);
+            runtime
+                .react_to_input(InputCode::Act(Action::Run(direction)))
+                .unwrap();
+            assert_ne!(runtime.player.pos, path(start));
+            assert_ne!(runtime.player.pos, path(corner));
+            tested += 1;
+        }
+        assert!(tested > 0);
+    }
+    #[test]
+    fn legal_moves() {
+        for seed in 0..10 {
+            let mut runtime = setup_runtime(seed);
+            for d in Direction::into_enum_iter().take(8) {
+                let input = InputCode::Act(Action::Move(d));
+                let (legal, before) = (runtime.is_legal(input), runtime.player.pos.clone());
+                runtime.react_to_input(input).unwrap();
+                assert_eq!(legal, runtime.player.pos != before);
+            }
+            let stair = runtime.dungeon.is_downstair(&runtime.player.pos);
+            assert_eq!(runtime.is_legal(InputCode::Act(Action::DownStair)), stair);
+            assert!(runtime.is_legal(InputCode::Act(Action::Search)));
+            assert!(!runtime.is_legal(InputCode::Act(Action::NoOp)));
+        }
+    }
+    /// whether running has to stop at `cd`: doors, stairs, forks and dead ends of passages
+    fn is_stop(runtime: &mut RunTime, cd: Coord) -> bool {
+        match runtime.dungeon.tile(&path(cd)).map(|t| t.to_byte()) {
+            Some(b'+') | Some(b'%') => true,
+            Some(b'#') => {
+                let ways = Direction::into_enum_iter()
+                    .take(4)
+                    .filter(|d| {
+                        let tile = runtime.dungeon.tile(&path(cd + d.to_cd()));
+                        tile.map(|t| t.to_byte()) == Some(b'#')
+                            || tile.map(|t| t.to_byte()) == Some(b'+')
+                    })
+                    .count();
+                ways != 2
+            }
+            _ => false,
+        }
+    }
+    #[test]
+    fn run_terminates() {
+        for seed in 0..10 {
+            let mut runtime = setup_runtime(seed);
+            let start = runtime.player.pos.clone();
+            // from the first room, and from a passage next to a corner
+            let mut starts = vec![(start, None)];
+            if let Some((cd, d)) = find_corner(&mut runtime) {
+                starts.push((path(cd), Some(d)));
+            }
+            for (start, dir) in starts {
+                let dirs: Vec<_> = match dir {
+                    Some(d) => vec![d],
+                    None => Direction::into_enum_iter().take(8).collect(),
+                };
+                for d in dirs {
+                    runtime.player.pos = start.clone();
+                    let res = runtime
+                        .react_to_input(InputCode::Act(Action::Run(d)))
+                        .unwrap();
+                    let pos = runtime.player.pos.clone();
+                    let cd = Coord::new(pos[1], pos[2]);
+                    let got_item = res.iter().any(|r| match r {
+                        Reaction::Notify(GameMsg::GotItem { .. }) => true,
+                        _ => false,
+                    });
+                    // in rooms, runs go straight until walls
+                    let blocked = runtime.dungeon.can_move_player(&pos, d).is_none();
+                    assert!(
+                        got_item || is_stop(&mut runtime, cd) || blocked,
+                        "seed: {}, stopped at {:?} running {:?}",
+                        seed,
+                        cd,
+                        d
+                    );
+                    runtime.player.pos = start.clone();
+                    runtime
+                        .react_to_input(InputCode::Act(Action::RunCautiously(d)))
+                        .unwrap();
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use super::{DamageReaction, Defense, Dice, Exp, HitPoint, Level, Strength};
 use crate::{Drawable, SmallStr};
 use dungeon::{Dungeon, DungeonPath, MoveResult};
+use error::GameResult;
+use hash::Fnv64;
 use item::ItemNum;
 use rng::{Parcent, RngHandle};
 use smallvec::SmallVec;
@@ -9,6 +11,7 @@ use std::collections::BTreeMap;
 use std::ops::Range;
 use std::rc::{Rc, Weak};
 use tile::Tile;
+use validation::Validator;
 
 pub type DiceVec<T> = SmallVec<[Dice<T>; 4]>;
 
@@ -25,8 +28,30 @@ pub struct Config {
 }
 
 impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.field("enemies", |v| {
+            for (i, preset) in self.enemies.iter().enumerate() {
+                match *preset {
+                    Preset::Builtin(id) if id >= BUILTIN_ENEMIES.len() => v.index(i, |v| {
+                        v.error(format!(
+                            "no builtin enemy {}, there are {}",
+                            id,
+                            BUILTIN_ENEMIES.len()
+                        ))
+                    }),
+                    _ => {}
+                }
+            }
+        });
+        v.parcent("appear_rate_gold", self.appear_rate_gold.0);
+        v.parcent("appear_rate_nogold", self.appear_rate_nogold.0);
+    }
     pub fn tile_max(&self) -> Option<u8> {
-        self.enemies.iter().map(|p| p.tile().to_byte()).max()
+        self.enemies
+            .iter()
+            .filter_map(Preset::tile)
+            .map(Tile::to_byte)
+            .max()
     }
     pub fn build(self, seed: u128) -> EnemyHandler {
         let rng = RngHandle::from_seed(seed);
@@ -96,10 +121,11 @@ impl Preset {
             Preset::Custom(s) => s,
         }
     }
-    fn tile(&self) -> Tile {
+    /// `None` for an invalid builtin index, which `Config::validate` reports
+    fn tile(&self) -> Option<Tile> {
         match self {
-            Preset::Builtin(i) => BUILTIN_ENEMIES[*i].tile.into(),
-            Preset::Custom(s) => s.tile,
+            Preset::Builtin(i) => BUILTIN_ENEMIES.get(*i).map(|e| e.tile.into()),
+            Preset::Custom(s) => Some(s.tile),
         }
     }
 }
@@ -160,6 +186,9 @@ pub struct Enemy {
     defense: Defense,
     exp: Exp,
     hp: Cell<HitPoint>,
+    /// the player knows the hit point of this enemy by damaging it
+    #[serde(default)]
+    hp_known: Cell<bool>,
     id: EnemyId,
     level: Level,
     max_hp: HitPoint,
@@ -200,7 +229,18 @@ impl Enemy {
     pub fn name(&self) -> &SmallStr {
         &self.name
     }
+    /// current hit point / max hit point, if the player knows it
+    pub fn hp_rate(&self) -> Option<f32> {
+        if !self.hp_known.get() {
+            return None;
+        }
+        if self.max_hp.0 <= 0 {
+            return Some(0.0);
+        }
+        Some(self.hp.get().0 as f32 / self.max_hp.0 as f32)
+    }
     pub fn get_damage(&self, damage: HitPoint) -> DamageReaction {
+        self.hp_known.set(true);
         let cur = self.hp.get();
         if cur <= damage {
             DamageReaction::Death
@@ -209,6 +249,11 @@ impl Enemy {
             DamageReaction::None
         }
     }
+    /// makes the enemy sleep until it's attacked
+    pub(crate) fn fall_asleep(&self) {
+        let attr = self.attr.get();
+        self.attr.set(EnemyAttr(attr.0 & !EnemyAttr::MEAN.0));
+    }
     fn run(&self) {
         self.running.replace(true);
     }
@@ -239,6 +284,17 @@ pub struct EnemyHandler {
 }
 
 impl EnemyHandler {
+    /// writes placed and active enemies, including their hit points, and the rng to `hasher`
+    pub(crate) fn hash_state(&self, hasher: &mut Fnv64) -> GameResult<()> {
+        for enemies in &[&self.placed_enemies, &self.active_enemies] {
+            hasher.write_u32(enemies.len() as u32);
+            enemies.iter().try_for_each(|(path, enemy)| {
+                hasher.write_json(path)?;
+                hasher.write_json(&**enemy)
+            })?;
+        }
+        hasher.write_json(&self.rng)
+    }
     fn new(mut stats: Vec<Status>, rng: RngHandle, config: ConfigInner) -> Self {
         stats.sort_by_key(|stat| stat.rarelity);
         EnemyHandler {
@@ -289,6 +345,10 @@ impl EnemyHandler {
         if !self.rng.parcent(appear_parcent) {
             return None;
         }
+        self.gen_enemy_surely(range, lev_add)
+    }
+    /// generate an enemy regardless of appear rates
+    pub fn gen_enemy_surely(&mut self, range: Range<u32>, lev_add: i64) -> Option<Rc<Enemy>> {
         let idx = self.select(range);
         let stat = self.enemy_stats.get(idx)?;
         let level = stat.level + lev_add.into();
@@ -299,6 +359,7 @@ impl EnemyHandler {
             defense: stat.defense - (lev_add as i32).into(),
             exp: stat.exp + Exp::from((lev_add * 10) as u32) + self.exp_add(level, hp),
             hp: Cell::new(hp),
+            hp_known: Cell::default(),
             id: self.next_id.increment(),
             level,
             name: stat.name.clone(),
@@ -331,6 +392,16 @@ impl EnemyHandler {
             .or_else(|| self.active_enemies.get(&path))
             .map(Rc::clone)
     }
+    /// all enemies in the dungeon with their positions
+    pub fn iter<'a>(&'a self) -> impl 'a + Iterator<Item = (&'a DungeonPath, &'a Enemy)> {
+        self.placed_enemies
+            .iter()
+            .chain(self.active_enemies.iter())
+            .map(|(p, e)| (p, e.as_ref()))
+    }
+    pub fn is_active(&self, path: &DungeonPath) -> bool {
+        self.active_enemies.contains_key(path)
+    }
     pub fn activate_area<'a, F>(&mut self, is_in_activation_area: F)
     where
         F: Fn(&DungeonPath) -> bool,
@@ -751,3 +822,22 @@ pub const BUILTIN_ENEMIES: [StaticStatus; 26] = [
         tile: b'Z',
     },
 ];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn hp_rate() {
+        let mut handler = Config::default().build(1);
+        let enemy = handler.gen_enemy_surely(0..10, 5).unwrap();
+        let hp = enemy.hp.get();
+        assert!(hp.0 > 2);
+        assert_eq!(enemy.hp_rate(), None);
+        assert!(matches!(
+            enemy.get_damage(HitPoint(2)),
+            DamageReaction::None
+        ));
+        let rate = enemy.hp.get().0 as f32 / hp.0 as f32;
+        assert_eq!(enemy.hp_rate(), Some(rate));
+    }
+}
@@ -1,11 +1,13 @@
 use super::{clamp, DamageReaction, Defense, Dice, Exp, HitPoint, Level, Maxed, Strength};
 use crate::dungeon::{Direction, DungeonPath};
 use crate::error::GameResult;
+use crate::hash::Fnv64;
 use crate::item::{
     armor, food::Food, itembox::ItemBox, weapon, InitItem, Item, ItemHandler, ItemKind, ItemToken,
 };
 use crate::rng::RngHandle;
 use crate::tile::{Drawable, Tile};
+use crate::validation::Validator;
 use smallstr::SmallStr;
 use std::{cmp, fmt};
 use tuple_map::TupleMap2;
@@ -73,6 +75,14 @@ fn default_init_items() -> Vec<InitItem> {
 }
 
 impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.check("init_hp", self.init_hp.0 > 0, || {
+            "must be positive".to_owned()
+        });
+        v.check("max_items", self.max_items > 0, || {
+            "must be positive".to_owned()
+        });
+    }
     pub fn build(self) -> Player {
         let status = StatusInner::from_config(&self);
         Player {
@@ -155,11 +165,16 @@ impl Player {
     pub fn level(&self) -> Level {
         self.status.level
     }
+    /// game turns passed since the game started
+    pub fn turns(&self) -> u32 {
+        self.status.turns
+    }
     pub(crate) fn buttle(&mut self) {
         self.status.quiet = 0
     }
     pub(crate) fn turn_passed(&mut self, rng: &mut RngHandle) -> Vec<PlayerEvent> {
         let mut res = vec![];
+        self.status.turns += 1;
         self.status.food_left -= 1;
         if self.status.food_left == 0 {
             return vec![PlayerEvent::Dead];
@@ -209,6 +224,14 @@ impl Player {
             None
         })
     }
+    /// writes the whole state of the player to `hasher`
+    pub(crate) fn hash_state(&self, hasher: &mut Fnv64) -> GameResult<()> {
+        hasher.write_json(&self.pos)?;
+        hasher.write_json(&self.status)?;
+        hasher.write_json(&self.armor.as_ref().map(|t| t.get()))?;
+        hasher.write_json(&self.weapon.as_ref().map(|t| t.get()))?;
+        self.itembox.hash_state(hasher)
+    }
     fn equip_from_box(&self, query: impl FnMut(&Item) -> bool) -> Option<ItemToken> {
         self.itembox.find_by(query).map(|item| {
             let mut item = item.clone();
@@ -269,6 +292,9 @@ struct StatusInner {
     food_left: u32,
     running: bool,
     quiet: u32,
+    /// game turns passed
+    #[serde(default)]
+    turns: u32,
 }
 
 impl StatusInner {
@@ -281,6 +307,7 @@ impl StatusInner {
             food_left: config.hunger_time,
             running: false,
             quiet: 0,
+            turns: 0,
         }
     }
 }
@@ -290,6 +317,12 @@ impl StatusInner {
 pub enum Action {
     Move(Direction),
     MoveUntil(Direction),
+    /// run along passages, turning at corners and stopping at forks
+    /// (Alt + direction key in the default keymap)
+    Run(Direction),
+    /// run until something interesting comes into view
+    /// (Ctrl + direction key in the default keymap, where Ctrl + j is received as '\n')
+    RunCautiously(Direction),
     UpStair,
     DownStair,
     Search,
@@ -299,7 +332,7 @@ pub enum Action {
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Leveling {
     /// necesarry exp for level up
-    exps: Vec<Exp>,
+    pub exps: Vec<Exp>,
 }
 
 impl Default for Leveling {
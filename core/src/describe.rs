@@ -0,0 +1,453 @@
+//! English descriptions of what the player sees, for text-based agents
+use character::player::{Hunger, Status};
+use character::Action;
+use dungeon::{Coord, Direction, Positioned};
+use error::*;
+use input::InputCode;
+use item::ItemKind;
+use observation::Entities;
+use std::fmt::Write;
+use std::str::FromStr;
+use {GameMsg, RunTime};
+
+/// how much a description tells
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verbosity {
+    /// short fragments: the place, exits, enemies, HP and messages
+    Brief,
+    /// sentences about everything the player can see, and the main status
+    #[default]
+    Normal,
+    /// `Normal` with positions, sizes, enemy health and the whole status
+    Verbose,
+}
+
+impl FromStr for Verbosity {
+    type Err = ::failure::Error;
+    fn from_str(s: &str) -> GameResult<Self> {
+        match s {
+            "brief" => Ok(Verbosity::Brief),
+            "normal" => Ok(Verbosity::Normal),
+            "verbose" => Ok(Verbosity::Verbose),
+            _ => Err(ErrorId::InvalidConversion.into_with(|| {
+                format!(
+                    "Unknown verbosity {}, expected 'brief', 'normal' or 'verbose'",
+                    s
+                )
+            })),
+        }
+    }
+}
+
+/// what the player can see, which can be described with any verbosity
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub player: Coord,
+    pub entities: Entities,
+    /// doors of the room, or directions the passage leads, in dungeon coordinates
+    pub exits: Vec<Coord>,
+    pub status: Status,
+    /// messages of the last turn
+    pub messages: Vec<GameMsg>,
+}
+
+const MOVES: [Direction; 8] = [
+    Direction::Up,
+    Direction::RightUp,
+    Direction::Right,
+    Direction::RightDown,
+    Direction::Down,
+    Direction::LeftDown,
+    Direction::Left,
+    Direction::LeftUp,
+];
+
+impl Scene {
+    pub(crate) fn new(runtime: &RunTime, messages: Vec<GameMsg>) -> GameResult<Self> {
+        let player = runtime.dungeon.path_to_cd(&runtime.player.pos);
+        let entities = runtime.entities();
+        let exits = match entities.room {
+            Some([xs, ys, xe, ye]) => {
+                let mut doors = vec![];
+                runtime.dungeon.draw(&mut |Positioned(cd, tile)| {
+                    let (x, y) = (cd.x.0, cd.y.0);
+                    let on_edge = x == xs || x == xe - 1 || y == ys || y == ye - 1;
+                    let in_room = xs <= x && x < xe && ys <= y && y < ye;
+                    if tile.to_byte() == b'+' && on_edge && in_room && cd != player {
+                        doors.push(cd);
+                    }
+                    Ok(())
+                })?;
+                doors.sort_by_key(|&cd| (cd.move_dist(player), cd.y, cd.x));
+                doors
+            }
+            None => MOVES
+                .iter()
+                .filter(|&&d| runtime.is_legal(InputCode::Act(Action::Move(d))))
+                .map(|&d| player + d.to_cd())
+                .collect(),
+        };
+        Ok(Scene {
+            player,
+            entities,
+            exits,
+            status: runtime.player_status(),
+            messages,
+        })
+    }
+    /// the description in English
+    pub fn describe(&self, verbosity: Verbosity) -> String {
+        let mut res = String::new();
+        self.describe_place(verbosity, &mut res);
+        self.describe_enemies(verbosity, &mut res);
+        if verbosity != Verbosity::Brief {
+            self.describe_items(verbosity, &mut res);
+        }
+        describe_status(&self.status, verbosity, &mut res);
+        let messages: Vec<_> = self.messages.iter().filter_map(message).collect();
+        if !messages.is_empty() {
+            match verbosity {
+                Verbosity::Brief => push_line(&mut res, messages.join(" ")),
+                _ => push_line(&mut res, format!("Last turn: {}", messages.join(" "))),
+            }
+        }
+        res
+    }
+    fn where_(&self, cd: Coord, verbosity: Verbosity) -> String {
+        let offset = cd - self.player;
+        let (dx, dy) = (offset.x.0, offset.y.0);
+        let dist = cd.move_dist(self.player);
+        match verbosity {
+            Verbosity::Brief => format!("{} {}", compass(dx, dy, true), dist),
+            _ => {
+                let steps = if dist == 1 { "step" } else { "steps" };
+                let mut s = format!("{} {} {}", dist, steps, compass(dx, dy, false));
+                if verbosity == Verbosity::Verbose {
+                    write!(s, " at ({}, {})", cd.x.0, cd.y.0).unwrap();
+                }
+                s
+            }
+        }
+    }
+    fn describe_place(&self, verbosity: Verbosity, res: &mut String) {
+        let level = self.status.dungeon_level;
+        let exits: Vec<_> = self
+            .exits
+            .iter()
+            .map(|&cd| self.where_(cd, verbosity))
+            .collect();
+        let stair = self.entities.stair.map(|cd| self.where_(cd, verbosity));
+        if verbosity == Verbosity::Brief {
+            let place = if self.entities.room.is_some() {
+                "room"
+            } else {
+                "passage"
+            };
+            let mut line = format!("Level {}, {}.", level, place);
+            if !exits.is_empty() {
+                let name = if self.entities.room.is_some() {
+                    "Doors"
+                } else {
+                    "Open"
+                };
+                write!(line, " {}: {}.", name, exits.join(", ")).unwrap();
+            }
+            if let Some(stair) = stair {
+                write!(line, " Stairs: {}.", stair).unwrap();
+            }
+            push_line(res, line);
+            return;
+        }
+        let mut line = match (self.entities.room, verbosity) {
+            (Some([xs, ys, xe, ye]), Verbosity::Verbose) => format!(
+                "You are on level {}, in a {}x{} room.",
+                level,
+                xe - xs,
+                ye - ys
+            ),
+            (Some(_), _) => format!("You are on level {}, in a room.", level),
+            (None, _) => format!("You are on level {}, in a passage.", level),
+        };
+        if verbosity == Verbosity::Verbose {
+            let (x, y) = (self.player.x.0, self.player.y.0);
+            write!(line, " You stand at ({}, {}).", x, y).unwrap();
+        }
+        match (self.entities.room.is_some(), exits.len()) {
+            (true, 0) => line.push_str(" You see no doors."),
+            (true, 1) => write!(line, " There is a door {}.", exits[0]).unwrap(),
+            (true, _) => write!(line, " There are doors {}.", join_and(&exits)).unwrap(),
+            (false, 0) => line.push_str(" The passage is a dead end."),
+            (false, _) => write!(line, " The passage leads {}.", join_and(&exits)).unwrap(),
+        }
+        if let Some(stair) = stair {
+            write!(line, " The stairs down are {}.", stair).unwrap();
+        }
+        push_line(res, line);
+    }
+    fn describe_enemies(&self, verbosity: Verbosity, res: &mut String) {
+        let enemies: Vec<_> = self
+            .entities
+            .enemies
+            .iter()
+            .map(|enemy| {
+                let place = self.where_(enemy.cd, verbosity);
+                match verbosity {
+                    Verbosity::Brief => format!("{} {}", enemy.name, place),
+                    Verbosity::Normal => format!("{} {}", with_article(&enemy.name), place),
+                    Verbosity::Verbose => match enemy.hp_rate {
+                        Some(hp_rate) => format!(
+                            "{} {} ({})",
+                            with_article(&enemy.name),
+                            place,
+                            health(hp_rate)
+                        ),
+                        None => format!("{} {}", with_article(&enemy.name), place),
+                    },
+                }
+            })
+            .collect();
+        match (verbosity, enemies.is_empty()) {
+            (Verbosity::Verbose, true) => push_line(res, "You see no monsters.".to_owned()),
+            (_, true) => {}
+            (Verbosity::Brief, false) => {
+                push_line(res, format!("Monsters: {}.", enemies.join(", ")))
+            }
+            (_, false) => push_line(res, format!("You see {}.", join_and(&enemies))),
+        }
+    }
+    fn describe_items(&self, verbosity: Verbosity, res: &mut String) {
+        let items: Vec<_> = self
+            .entities
+            .items
+            .iter()
+            .map(|item| {
+                let place = self.where_(item.cd, verbosity);
+                format!("{} {}", item_name(&item.kind, item.num), place)
+            })
+            .collect();
+        match (verbosity, items.is_empty()) {
+            (Verbosity::Verbose, true) => push_line(res, "There are no items in view.".to_owned()),
+            (_, true) => {}
+            (_, false) => push_line(res, format!("On the floor: {}.", join_and(&items))),
+        }
+    }
+}
+
+fn describe_status(status: &Status, verbosity: Verbosity, res: &mut String) {
+    let hp = format!("HP {}/{}", status.hp.current.0, status.hp.max.0);
+    if verbosity == Verbosity::Brief {
+        push_line(res, format!("{}.", hp));
+        return;
+    }
+    let mut line = format!(
+        "{}, Str {}/{}, Gold {}",
+        hp, status.strength.current.0, status.strength.max.0, status.gold
+    );
+    if verbosity == Verbosity::Verbose {
+        write!(
+            line,
+            ", Armor {}, Level {}, Exp {}",
+            status.defense.0, status.player_level, status.exp.0
+        )
+        .unwrap();
+    }
+    line.push('.');
+    match status.hunger_level {
+        Hunger::Normal => {}
+        Hunger::Hungry => line.push_str(" You are hungry."),
+        Hunger::Weak => line.push_str(" You are weak from hunger."),
+    }
+    push_line(res, line);
+}
+
+fn push_line(res: &mut String, line: String) {
+    if !res.is_empty() {
+        res.push('\n');
+    }
+    res.push_str(&line);
+}
+
+/// e.g. "a, b and c"
+fn join_and(words: &[String]) -> String {
+    match words.split_last() {
+        None => String::new(),
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+fn with_article(name: &str) -> String {
+    let vowel = name
+        .chars()
+        .next()
+        .is_some_and(|c| "aeiouAEIOU".contains(c));
+    format!("{} {}", if vowel { "an" } else { "a" }, name)
+}
+
+/// direction of the offset, where up is north
+fn compass(dx: i32, dy: i32, short: bool) -> &'static str {
+    // an offset mostly along one axis is described by the axis
+    let (dx, dy) = if dx.abs() > dy.abs() * 2 {
+        (dx, 0)
+    } else if dy.abs() > dx.abs() * 2 {
+        (0, dy)
+    } else {
+        (dx, dy)
+    };
+    let (long, brief) = match (dx.signum(), dy.signum()) {
+        (0, -1) => ("north", "N"),
+        (1, -1) => ("north-east", "NE"),
+        (1, 0) => ("east", "E"),
+        (1, 1) => ("south-east", "SE"),
+        (0, 1) => ("south", "S"),
+        (-1, 1) => ("south-west", "SW"),
+        (-1, 0) => ("west", "W"),
+        (-1, -1) => ("north-west", "NW"),
+        _ => ("here", "here"),
+    };
+    if short {
+        brief
+    } else {
+        long
+    }
+}
+
+fn health(hp_rate: f32) -> &'static str {
+    if hp_rate >= 1.0 {
+        "unhurt"
+    } else if hp_rate >= 0.5 {
+        "wounded"
+    } else {
+        "badly wounded"
+    }
+}
+
+fn item_name(kind: &ItemKind, num: u32) -> String {
+    let name = kind.category();
+    match kind {
+        ItemKind::Gold => format!("{} gold", num),
+        _ if num == 1 => with_article(name),
+        ItemKind::Armor(_) | ItemKind::Food(_) => format!("{} pieces of {}", num, name),
+        _ => format!("{} {}s", num, name),
+    }
+}
+
+fn message(msg: &GameMsg) -> Option<String> {
+    let res = match msg {
+        GameMsg::CantMove(d) => {
+            let d = d.to_cd();
+            format!("You can't move {}.", compass(d.x.0, d.y.0, false))
+        }
+        GameMsg::CantGetItem(kind) => format!("You can't pick up the {}.", kind.category()),
+        GameMsg::GotItem { kind, num } => format!("You picked up {}.", item_name(kind, *num)),
+        GameMsg::HitTo(name, hp) => format!("You hit the {} for {} damage.", name, hp.0),
+        GameMsg::HitFrom(name, hp) => format!("The {} hits you for {} damage.", name, hp.0),
+        GameMsg::MissTo(name) => format!("You miss the {}.", name),
+        GameMsg::MissFrom(name) => format!("The {} misses you.", name),
+        GameMsg::Killed(name) => format!("You defeated the {}.", name),
+        GameMsg::NoDownStair => "There are no stairs here.".to_owned(),
+        GameMsg::SecretDoor => "You found a secret door.".to_owned(),
+        GameMsg::Quit => return None,
+    };
+    Some(res)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use character::HitPoint;
+    use input::KeyMap;
+    use observation::EnemyInfo;
+    use SmallStr;
+    #[test]
+    fn describe_runtime() {
+        let mut config = ::GameConfig::default();
+        config.seed = Some(1);
+        config.enemies.enemies.clear();
+        let mut runtime = config.build().unwrap();
+        runtime.keymap = KeyMap::ai();
+        let scene = runtime.scene(vec![]).unwrap();
+        let text = scene.describe(Verbosity::Normal);
+        assert!(text.starts_with("You are on level 1, in a "), "{}", text);
+        assert!(text.contains("HP 12/12"), "{}", text);
+        let brief = scene.describe(Verbosity::Brief);
+        let verbose = scene.describe(Verbosity::Verbose);
+        assert!(brief.len() < text.len() && text.len() < verbose.len());
+        // walk through the nearest door into the passage
+        let door = scene.exits[0];
+        let room = scene.entities.room.unwrap();
+        let in_room = |cd: Coord| {
+            let (x, y) = (cd.x.0, cd.y.0);
+            room[0] <= x && x < room[2] && room[1] <= y && y < room[3]
+        };
+        let mut player = scene.player;
+        for _ in 0..100 {
+            let next = MOVES
+                .iter()
+                .filter(|&&d| runtime.is_legal(InputCode::Act(Action::Move(d))))
+                .min_by_key(|&&d| {
+                    let cd = player + d.to_cd();
+                    if player == door {
+                        in_room(cd) as i32
+                    } else {
+                        cd.euc_dist_squared(door)
+                    }
+                })
+                .cloned()
+                .unwrap();
+            runtime
+                .react_to_input(InputCode::Act(Action::Move(next)))
+                .unwrap();
+            player = runtime.scene(vec![]).unwrap().player;
+            if !in_room(player) {
+                break;
+            }
+        }
+        let scene = runtime.scene(vec![]).unwrap();
+        assert_eq!(scene.entities.room, None);
+        assert!(!scene.exits.is_empty());
+        let text = scene.describe(Verbosity::Normal);
+        assert!(text.contains("in a passage. The passage leads"), "{}", text);
+    }
+    #[test]
+    fn describe_scene() {
+        let mut scene = Scene::default();
+        scene.player = Coord::new(10, 10);
+        scene.entities.room = Some([5, 5, 15, 12]);
+        scene.exits = vec![Coord::new(10, 5), Coord::new(14, 8)];
+        scene.entities.stair = Some(Coord::new(7, 11));
+        scene.entities.enemies.push(EnemyInfo {
+            cd: Coord::new(11, 9),
+            tile: b'E'.into(),
+            name: "emu".to_owned(),
+            hp_rate: Some(0.4),
+        });
+        scene.status.dungeon_level = 2;
+        scene.messages = vec![
+            GameMsg::HitTo(SmallStr::from_str("emu"), HitPoint(3)),
+            GameMsg::MissFrom(SmallStr::from_str("emu")),
+        ];
+        assert_eq!(
+            scene.describe(Verbosity::Normal),
+            "You are on level 2, in a room. There are doors 5 steps north and 4 steps north-east. \
+             The stairs down are 3 steps west.\n\
+             You see an emu 1 step north-east.\n\
+             HP 0/0, Str 0/0, Gold 0.\n\
+             Last turn: You hit the emu for 3 damage. The emu misses you."
+        );
+        assert_eq!(
+            scene.describe(Verbosity::Brief),
+            "Level 2, room. Doors: N 5, NE 4. Stairs: W 3.\n\
+             Monsters: emu NE 1.\n\
+             HP 0/0.\n\
+             You hit the emu for 3 damage. The emu misses you."
+        );
+        let verbose = scene.describe(Verbosity::Verbose);
+        assert!(verbose.contains("in a 10x7 room. You stand at (10, 10)."));
+        assert!(verbose.contains("(badly wounded)"));
+        assert!(verbose.contains("There are no items in view."));
+        assert_eq!("brief".parse::<Verbosity>().unwrap(), Verbosity::Brief);
+        assert!("loud".parse::<Verbosity>().is_err());
+    }
+}
@@ -1,16 +1,19 @@
 //! module for making and managing dangeon
 mod coord;
 mod field;
-mod rogue;
+pub mod rogue;
 pub use self::coord::{Coord, Direction, Positioned, X, Y};
 pub use self::field::{Cell, CellAttr, Field};
+pub use self::rogue::export::FloorDump;
 use character::player::Status as PlayerStatus;
 use character::EnemyHandler;
 use error::*;
 use item::{ItemHandler, ItemToken};
 use ndarray::Array2;
+use rect_iter::RectRange;
 use smallvec::SmallVec;
 use tile::Tile;
+use validation::Validator;
 use {GameInfo, GameMsg, GlobalConfig};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -34,6 +37,12 @@ impl Default for DungeonStyle {
 }
 
 impl DungeonStyle {
+    pub(crate) fn validate(&self, v: &mut Validator, width: X, height: Y) {
+        match self {
+            DungeonStyle::Rogue(config) => config.validate(v, width, height),
+            _ => v.check("style", false, || "only rogue is implemented".to_owned()),
+        }
+    }
     pub fn build(
         self,
         config_global: &GlobalConfig,
@@ -94,6 +103,11 @@ pub trait Dungeon {
     fn remove_item(&mut self, path: &DungeonPath) -> Option<ItemToken>;
     fn tile(&mut self, path: &DungeonPath) -> Option<Tile>;
     fn get_history(&self, state: &PlayerStatus) -> Option<Array2<bool>>;
+    fn export_floor(&self, enemies: &EnemyHandler, player: &DungeonPath) -> FloorDump;
+    /// stairs the player has seen in the current level
+    fn seen_stairs(&self) -> Vec<DungeonPath>;
+    /// the range of the room where the player is
+    fn room_range(&self, player: &DungeonPath) -> Option<RectRange<i32>>;
     fn move_enemy(
         &mut self,
         path: &DungeonPath,
@@ -106,6 +120,10 @@ pub trait Dungeon {
         player_pos: &DungeonPath,
         skip: &dyn Fn(&DungeonPath) -> bool,
     ) -> MoveResult;
+    /// the rogue dungeon, if this is
+    fn as_rogue(&self) -> Option<&rogue::Dungeon> {
+        None
+    }
 }
 
 type PathVec = SmallVec<[i32; 4]>;
@@ -0,0 +1,383 @@
+//! export floors as structured data for offline analysis
+use super::{floor::Floor, Address, RoomKind, SpecialRoom, Surface};
+use character::EnemyHandler;
+use dungeon::{Coord, Direction};
+use enum_iterator::IntoEnumIterator;
+use error::*;
+use ndarray::Array2;
+use rect_iter::{Get2D, RectRange};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use tile::Drawable;
+
+/// a room in `FloorDump`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RoomDump {
+    pub id: usize,
+    /// "normal", "maze" or "empty"
+    pub kind: String,
+    /// [x_start, y_start, x_end, y_end) of the room, or the passage cell of an empty room
+    pub range: [i32; 4],
+    pub is_dark: bool,
+    pub special: Option<SpecialRoom>,
+}
+
+/// a door in `FloorDump`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DoorDump {
+    pub cd: Coord,
+    pub is_locked: bool,
+}
+
+/// an item in `FloorDump`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ItemDump {
+    pub cd: Coord,
+    pub tile: char,
+    pub name: String,
+    pub num: u32,
+}
+
+/// an enemy in `FloorDump`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EnemyDump {
+    pub cd: Coord,
+    pub tile: char,
+    pub name: String,
+    pub is_active: bool,
+}
+
+/// full information of a floor, including hidden cells
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FloorDump {
+    pub level: u32,
+    pub width: i32,
+    pub height: i32,
+    /// true surfaces of the floor, where hidden passages and locked doors are revealed
+    pub map: Vec<String>,
+    pub rooms: Vec<RoomDump>,
+    /// pairs of room ids connected by passages
+    pub room_graph: Vec<[usize; 2]>,
+    /// passages out of rooms, as groups of connected cells
+    pub passages: Vec<Vec<Coord>>,
+    pub doors: Vec<DoorDump>,
+    pub hidden: Vec<Coord>,
+    pub locked: Vec<Coord>,
+    pub items: Vec<ItemDump>,
+    pub enemies: Vec<EnemyDump>,
+    pub stairs: Vec<Coord>,
+    pub player: Option<Coord>,
+}
+
+fn sorted(mut v: Vec<Coord>) -> Vec<Coord> {
+    v.sort_by_key(|cd| (cd.y, cd.x));
+    v
+}
+
+impl EnemyDump {
+    /// enemies in the given level
+    pub(super) fn collect(level: u32, enemies: &EnemyHandler) -> Vec<EnemyDump> {
+        enemies
+            .iter()
+            .filter_map(|(path, enemy)| {
+                let address = Address::from_path(path);
+                if address.level != level {
+                    return None;
+                }
+                Some(EnemyDump {
+                    cd: address.cd,
+                    tile: enemy.tile().to_char(),
+                    name: enemy.name().to_string(),
+                    is_active: enemies.is_active(path),
+                })
+            })
+            .collect()
+    }
+}
+
+impl FloorDump {
+    pub(super) fn new(
+        level: u32,
+        floor: &Floor,
+        enemies: Vec<EnemyDump>,
+        player: Option<Coord>,
+    ) -> Self {
+        let field = &floor.field;
+        let cells: Vec<_> = field.size().into_iter().map(Coord::from).collect();
+        let true_surface = |cd: Coord| {
+            let cell = field.get_p(cd);
+            if cell.is_hidden() {
+                Surface::Passage
+            } else if cell.is_locked() {
+                Surface::Door
+            } else {
+                cell.surface
+            }
+        };
+        let map = (0..field.height().0)
+            .map(|y| {
+                (0..field.width().0)
+                    .map(|x| true_surface(Coord::new(x, y)).tile().to_char())
+                    .collect()
+            })
+            .collect();
+        let rooms = floor
+            .rooms
+            .iter()
+            .map(|room| {
+                let to_array = |r: &RectRange<i32>| {
+                    [
+                        r.get_x().start,
+                        r.get_y().start,
+                        r.get_x().end,
+                        r.get_y().end,
+                    ]
+                };
+                let (kind, range) = match room.kind {
+                    RoomKind::Normal { ref range } => ("normal", to_array(range)),
+                    RoomKind::Maze(ref maze) => ("maze", to_array(&maze.range)),
+                    RoomKind::Empty { up_left } => {
+                        let (x, y) = (up_left.x.0, up_left.y.0);
+                        ("empty", [x, y, x + 1, y + 1])
+                    }
+                };
+                RoomDump {
+                    id: room.id,
+                    kind: kind.to_owned(),
+                    range,
+                    is_dark: room.is_dark,
+                    special: room.special,
+                }
+            })
+            .collect();
+        let passages = floor.passages();
+        let doors = sorted(floor.doors.iter().cloned().collect())
+            .into_iter()
+            .map(|cd| DoorDump {
+                cd,
+                is_locked: field.get_p(cd).is_locked(),
+            })
+            .collect();
+        let items = {
+            let mut items: Vec<_> = floor
+                .items
+                .iter()
+                .map(|(&cd, token)| {
+                    let item = token.get();
+                    ItemDump {
+                        cd,
+                        tile: item.tile().to_char(),
+                        name: format!("{}", item),
+                        num: item.how_many.0,
+                    }
+                })
+                .collect();
+            items.sort_by_key(|item| (item.cd.y, item.cd.x));
+            items
+        };
+        FloorDump {
+            level,
+            width: field.width().0,
+            height: field.height().0,
+            map,
+            rooms,
+            room_graph: room_graph(floor, &passages),
+            passages,
+            doors,
+            hidden: cells
+                .iter()
+                .cloned()
+                .filter(|&cd| field.get_p(cd).is_hidden())
+                .collect(),
+            locked: cells
+                .iter()
+                .cloned()
+                .filter(|&cd| field.get_p(cd).is_locked())
+                .collect(),
+            items,
+            enemies,
+            stairs: cells
+                .iter()
+                .cloned()
+                .filter(|&cd| field.get_p(cd).surface == Surface::Stair)
+                .collect(),
+            player,
+        }
+    }
+    pub fn to_json(&self) -> GameResult<String> {
+        ::serde_json::to_string_pretty(self).into_chained(|| "FloorDump::to_json")
+    }
+    /// converts the dump into named 2D arrays of shape (height, width)
+    /// - surface: ASCII code of the true surface
+    /// - room: room id, or -1 for cells out of rooms
+    /// - passage: passage id, or -1 for cells out of passages
+    /// - hidden/locked/door/stair: 1 if the cell is so, otherwise 0
+    /// - item/enemy: ASCII code of the object, or 0
+    pub fn to_arrays(&self) -> Vec<(&'static str, Array2<i32>)> {
+        let shape = [self.height as usize, self.width as usize];
+        let zeros = || Array2::<i32>::zeros(shape);
+        let idx = |cd: &Coord| [cd.y.0 as usize, cd.x.0 as usize];
+        let mut surface = zeros();
+        for (y, row) in self.map.iter().enumerate() {
+            for (x, c) in row.bytes().enumerate() {
+                surface[[y, x]] = i32::from(c);
+            }
+        }
+        let mut room = Array2::from_elem(shape, -1);
+        for r in &self.rooms {
+            let [xs, ys, xe, ye] = r.range;
+            for y in ys..ye {
+                for x in xs..xe {
+                    room[[y as usize, x as usize]] = r.id as i32;
+                }
+            }
+        }
+        let mut passage = Array2::from_elem(shape, -1);
+        for (i, cells) in self.passages.iter().enumerate() {
+            for cd in cells {
+                passage[idx(cd)] = i as i32;
+            }
+        }
+        let mark = |cds: &mut dyn Iterator<Item = (&Coord, i32)>| {
+            let mut array = zeros();
+            cds.for_each(|(cd, v)| array[idx(cd)] = v);
+            array
+        };
+        vec![
+            ("surface", surface),
+            ("room", room),
+            ("passage", passage),
+            ("hidden", mark(&mut self.hidden.iter().map(|cd| (cd, 1)))),
+            ("locked", mark(&mut self.locked.iter().map(|cd| (cd, 1)))),
+            ("door", mark(&mut self.doors.iter().map(|d| (&d.cd, 1)))),
+            ("stair", mark(&mut self.stairs.iter().map(|cd| (cd, 1)))),
+            (
+                "item",
+                mark(&mut self.items.iter().map(|i| (&i.cd, i.tile as i32))),
+            ),
+            (
+                "enemy",
+                mark(&mut self.enemies.iter().map(|e| (&e.cd, e.tile as i32))),
+            ),
+        ]
+    }
+    /// writes the dump into `dir` as `floor.json` and `<name>.npy` for each array
+    pub fn write_bundle(&self, dir: impl AsRef<Path>) -> GameResult<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).into_chained(|| "FloorDump::write_bundle")?;
+        let mut json =
+            File::create(dir.join("floor.json")).into_chained(|| "FloorDump::write_bundle")?;
+        json.write_all(self.to_json()?.as_bytes())
+            .into_chained(|| "FloorDump::write_bundle")?;
+        for (name, array) in self.to_arrays() {
+            let file = File::create(dir.join(name).with_extension("npy"))
+                .into_chained(|| "FloorDump::write_bundle")?;
+            write_npy(&array, file).into_chained(|| "FloorDump::write_bundle")?;
+        }
+        Ok(())
+    }
+}
+
+/// writes an array in `.npy` format, which can be loaded by `numpy.load`
+pub fn write_npy(array: &Array2<i32>, mut writer: impl Write) -> io::Result<()> {
+    let (h, w) = array.dim();
+    let mut header = format!(
+        "{{'descr': '<i4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        h, w
+    );
+    // magic(6) + version(2) + header length(2) + header has to be aligned to 64 bytes
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for v in array.iter() {
+        writer.write_all(&v.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// pairs of rooms connected by passages
+fn room_graph(floor: &Floor, passages: &[Vec<Coord>]) -> Vec<[usize; 2]> {
+    let mut room_of = HashMap::new();
+    for room in &floor.rooms {
+        match room.kind {
+            RoomKind::Normal { ref range } => range.iter().for_each(|cd| {
+                room_of.insert(Coord::from(cd), room.id);
+            }),
+            RoomKind::Maze(ref maze) => maze.passages().for_each(|cd| {
+                room_of.insert(cd, room.id);
+            }),
+            RoomKind::Empty { up_left } => {
+                room_of.insert(up_left, room.id);
+            }
+        }
+    }
+    let mut edges = BTreeSet::new();
+    for cells in passages {
+        let touching: BTreeSet<usize> = cells
+            .iter()
+            .flat_map(|&cd| {
+                Direction::into_enum_iter()
+                    .take(4)
+                    .map(move |d| cd + d.to_cd())
+                    .chain(Some(cd))
+            })
+            .filter_map(|cd| room_of.get(&cd).cloned())
+            .collect();
+        for &a in &touching {
+            for &b in touching.range(a + 1..) {
+                edges.insert([a, b]);
+            }
+        }
+    }
+    edges.into_iter().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use {GameConfig, RunTime};
+    fn runtime() -> RunTime {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.build().unwrap()
+    }
+    #[test]
+    fn dump_current_floor() {
+        let runtime = runtime();
+        let dump = runtime.export_floor();
+        assert_eq!((dump.width, dump.height), (80, 24));
+        assert_eq!(dump.map.len(), 24);
+        assert_eq!(dump.stairs.len(), 1);
+        assert!(dump.player.is_some());
+        assert!(!dump.room_graph.is_empty());
+        let json = dump.to_json().unwrap();
+        let dump2: FloorDump = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(dump, dump2);
+        let arrays = dump.to_arrays();
+        let surface = &arrays[0].1;
+        let stair = dump.stairs[0];
+        assert_eq!(
+            surface[[stair.y.0 as usize, stair.x.0 as usize]],
+            i32::from(b'%')
+        );
+        for (_, array) in arrays {
+            assert_eq!(array.dim(), (24, 80));
+        }
+    }
+    #[test]
+    fn npy_header() {
+        let array = Array2::from_shape_vec((2, 3), vec![1, 2, 3, 4, 5, 6]).unwrap();
+        let mut buf = Vec::new();
+        write_npy(&array, &mut buf).unwrap();
+        let header_len = u16::from_le_bytes([buf[8], buf[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(buf.len(), 10 + header_len + 6 * 4);
+        assert_eq!(&buf[10 + header_len..10 + header_len + 4], &[1, 0, 0, 0]);
+    }
+}
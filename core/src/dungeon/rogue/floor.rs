@@ -1,15 +1,17 @@
 //! rogue floor
-use super::{passages, rooms, Address, Config, Room, Surface};
+use super::{passages, rooms, Address, Config, Room, SpecialRoom, Surface};
 use dungeon::{Cell, CellAttr, Coord, Direction, Field, Positioned, X, Y};
 use enemies::EnemyHandler;
 use enum_iterator::IntoEnumIterator;
 use error::*;
 use fenwick::FenwickSet;
+use hash::MapHash;
 use item::{ItemHandler, ItemToken};
 use ndarray::Array2;
 use rect_iter::{Get2D, GetMut2D};
 use rng::RngHandle;
 use std::collections::{HashMap, HashSet, VecDeque};
+use tile::Drawable;
 use GameMsg;
 
 /// representation of 'floor'
@@ -25,10 +27,19 @@ pub struct Floor {
     pub non_empty_rooms: FenwickSet,
     /// items
     pub items: HashMap<Coord, ItemToken>,
+    /// lengths of passages dug between rooms
+    pub passage_lengths: Vec<usize>,
+    /// hash of tiles drawn from `field`, updated when cells get visible or invisible
+    known: MapHash,
 }
 
 impl Floor {
-    fn new(rooms: Vec<Room>, doors: HashSet<Coord>, field: Field<Surface>) -> Self {
+    fn new(
+        rooms: Vec<Room>,
+        doors: HashSet<Coord>,
+        field: Field<Surface>,
+        passage_lengths: Vec<usize>,
+    ) -> Self {
         let non_empty_rooms =
             rooms
                 .iter()
@@ -38,13 +49,56 @@ impl Floor {
                     }
                     s
                 });
-        Floor {
+        let mut floor = Floor {
             rooms,
             doors,
             field,
             non_empty_rooms,
             items: Default::default(),
+            passage_lengths,
+            known: MapHash::default(),
+        };
+        floor.rehash_known();
+        floor
+    }
+
+    /// hash of the map the player knows, same as `MapHash::new` of the drawn field
+    pub fn known_hash(&self) -> MapHash {
+        self.known
+    }
+
+    /// computes `known_hash` again, which is needed after modifying `field` directly
+    pub fn rehash_known(&mut self) {
+        let (w, h) = (
+            self.field.width().0 as usize,
+            self.field.height().0 as usize,
+        );
+        let mut map = Array2::from_elem([h, w], b' ');
+        if let Some(range) = self.field.size_ytrimed() {
+            for cd in range {
+                let tile = self.field.get_p(cd).tile().to_byte();
+                map[[cd.1 as usize, cd.0 as usize]] = tile;
+            }
         }
+        self.known = MapHash::new(&map);
+    }
+
+    /// modifies the cell by `f`, keeping `known` up to date
+    fn modify_cell<R>(
+        field: &mut Field<Surface>,
+        known: &mut MapHash,
+        cd: Coord,
+        f: impl FnOnce(&mut Cell<Surface>) -> R,
+    ) -> GameResult<R> {
+        let cell = field.try_get_mut_p(cd)?;
+        let old = cell.tile().to_byte();
+        let res = f(cell);
+        // the first and the last rows aren't drawn
+        if 0 < cd.y.0 && cd.y.0 < field.height().0 - 1 {
+            let new = field.get_p(cd).tile().to_byte();
+            known.update(cd.x.0 as usize, cd.y.0 as usize, old, new);
+        }
+        Ok(res)
     }
 
     /// generate a new floor without items
@@ -73,7 +127,7 @@ impl Floor {
         })?;
         // sometimes door is hidden randomly so first we store positions to avoid borrow restriction
         let mut passages = Vec::new();
-        passages::dig_passges(
+        let passage_lengths = passages::dig_passges(
             &rooms,
             config.room_num_x,
             config.room_num_y,
@@ -102,7 +156,7 @@ impl Floor {
                     })
                     .into_chained(|| "Floor::new dig_passges returned invalid index")
             })?;
-        Ok(Floor::new(rooms, doors, field))
+        Ok(Floor::new(rooms, doors, field, passage_lengths))
     }
     /// place enemies
     pub fn place_enemies(
@@ -121,6 +175,7 @@ impl Floor {
         for (cd, room) in self
             .rooms
             .iter_mut()
+            .filter(|room| room.special.is_none())
             .filter_map(|room| Some((room.select_cell(rng, true)?, room)))
         {
             if let Some(enemy) = enemies.gen_enemy(min..max, i64::from(lev_add), room.has_gold) {
@@ -129,6 +184,29 @@ impl Floor {
                 room.fill_cell(cd, true);
             }
         }
+        // special rooms
+        for room in &mut self.rooms {
+            let (num, sleeps) = match room.special {
+                Some(SpecialRoom::Treasure { guards, .. }) => (guards, true),
+                Some(SpecialRoom::Zoo { enemies }) => (enemies, false),
+                None => continue,
+            };
+            for _ in 0..num {
+                let cd = match room.select_cell(rng, true) {
+                    Some(cd) => cd,
+                    None => break,
+                };
+                let enemy = match enemies.gen_enemy_surely(min..max, i64::from(lev_add)) {
+                    Some(enemy) => enemy,
+                    None => break,
+                };
+                if sleeps {
+                    enemy.fall_asleep();
+                }
+                enemies.place(Address::new(level, cd).into(), enemy);
+                room.fill_cell(cd, true);
+            }
+        }
     }
     /// setup items for a floor
     pub fn setup_items(
@@ -143,6 +221,7 @@ impl Floor {
             for (cd, room) in self
                 .rooms
                 .iter_mut()
+                .filter(|room| !room.is_treasure())
                 .filter_map(|room| Some((room.select_cell(rng, false)?, room)))
             {
                 if let Some(gold) = item_handle.setup_gold(level) {
@@ -151,6 +230,22 @@ impl Floor {
                     self.items.insert(cd, gold);
                 }
             }
+            // setup treasures
+            for room in &mut self.rooms {
+                let num = match room.special {
+                    Some(SpecialRoom::Treasure { items, .. }) => items,
+                    _ => continue,
+                };
+                for _ in 0..num {
+                    let cd = match room.select_cell(rng, false) {
+                        Some(cd) => cd,
+                        None => break,
+                    };
+                    room.fill_cell(cd, false);
+                    room.has_gold = true;
+                    self.items.insert(cd, item_handle.setup_treasure(level));
+                }
+            }
         }
     }
 
@@ -159,13 +254,10 @@ impl Floor {
         let cd = self
             .select_cell(rng, false)
             .ok_or_else(|| ErrorId::MaybeBug.into_with(|| "[setup stair] no empty cell!"))?;
-        {
-            let cell = self
-                .field
-                .try_get_mut_p(cd)
-                .into_chained(|| "[setup stair] select_cell returned invalid coord")?;
+        Self::modify_cell(&mut self.field, &mut self.known, cd, |cell| {
             cell.surface = Surface::Stair;
-        }
+        })
+        .chain_err(|| "[setup stair] select_cell returned invalid coord")?;
         self.set_obj(cd, false);
         Ok(())
     }
@@ -221,12 +313,11 @@ impl Floor {
             .range()
             .unwrap_or(&self.rooms[room_id].assigned_area)
             .to_owned();
+        let (field, known) = (&mut self.field, &mut self.known);
         range.iter().try_for_each(|cd| {
             let is_edge = range.is_edge(cd);
-            self.field
-                .try_get_mut_p(cd)
-                .map(|mut_cell| mark(mut_cell, is_edge))
-                .into_chained(|| "in Floor::with_current_room")
+            Self::modify_cell(field, known, cd.into(), |cell| mark(cell, is_edge))
+                .chain_err(|| "in Floor::with_current_room")
         })
     }
 
@@ -286,13 +377,13 @@ impl Floor {
             .into_chained(|| "Floor::player_in Cannot move")?
             .visit();
         self.set_obj(cd, true);
+        let (field, known) = (&mut self.field, &mut self.known);
         Direction::into_enum_iter().take(9).for_each(|d| {
-            let cd = cd + d.to_cd();
-            if let Ok(cell) = self.field.try_get_mut_p(cd) {
+            let _ = Self::modify_cell(field, known, cd + d.to_cd(), |cell| {
                 if !d.is_diag() || cell.surface != Surface::Passage {
                     cell.approached();
                 }
-            }
+            });
         });
         Ok(())
     }
@@ -303,13 +394,13 @@ impl Floor {
             self.leaves_room(cd).chain_err(|| "Floor::player_out")?;
         }
         self.remove_obj(cd, true);
+        let (field, known) = (&mut self.field, &mut self.known);
         Direction::into_enum_iter().take(9).for_each(|d| {
-            let cd = cd + d.to_cd();
-            if let Ok(cell) = self.field.try_get_mut_p(cd) {
+            let _ = Self::modify_cell(field, known, cd + d.to_cd(), |cell| {
                 if cell.surface == Surface::Floor {
                     cell.left();
                 }
-            }
+            });
         });
         Ok(())
     }
@@ -335,6 +426,19 @@ impl Floor {
     /// select an empty cell from rooms randomly
     pub(super) fn select_cell(&self, rng: &mut RngHandle, is_character: bool) -> Option<Coord> {
         let mut candidates = self.non_empty_rooms.clone();
+        // characters are placed in special rooms only when there's no other choice
+        if is_character {
+            let mut normal = candidates.clone();
+            self.rooms
+                .iter()
+                .filter(|room| room.special.is_some())
+                .for_each(|room| {
+                    normal.remove(room.id);
+                });
+            if normal.len() > 0 {
+                candidates = normal;
+            }
+        }
         while candidates.len() > 0 {
             let room_idx = candidates
                 .select(rng)
@@ -357,18 +461,20 @@ impl Floor {
     ) -> impl 'a + Iterator<Item = GameMsg> {
         let probinc = 0; // TODO: it should be changed by player status
         Direction::into_enum_iter().take(8).filter_map(move |d| {
-            let cd = cd + d.to_cd();
-            let cell = self.field.try_get_mut_p(cd).ok()?;
-            if cell.is_hidden() && rng.does_happen(probinc + config.passage_unlock_rate_inv) {
-                cell.unlock();
-                cell.surface = Surface::Passage;
-            }
-            if cell.is_locked() && rng.does_happen(probinc + config.door_unlock_rate_inv) {
-                cell.unlock();
-                cell.surface = Surface::Door;
-                return Some(GameMsg::SecretDoor);
-            }
-            None
+            let (field, known) = (&mut self.field, &mut self.known);
+            Self::modify_cell(field, known, cd + d.to_cd(), |cell| {
+                if cell.is_hidden() && rng.does_happen(probinc + config.passage_unlock_rate_inv) {
+                    cell.unlock();
+                    cell.surface = Surface::Passage;
+                }
+                if cell.is_locked() && rng.does_happen(probinc + config.door_unlock_rate_inv) {
+                    cell.unlock();
+                    cell.surface = Surface::Door;
+                    return Some(GameMsg::SecretDoor);
+                }
+                None
+            })
+            .ok()?
         })
     }
 
@@ -384,6 +490,44 @@ impl Floor {
         array
     }
 
+    /// passages out of rooms, as groups of connected cells(including hidden ones)
+    pub fn passages(&self) -> Vec<Vec<Coord>> {
+        let is_passage = |cd: Coord| {
+            let in_room = self
+                .rooms
+                .iter()
+                .filter_map(|room| room.range())
+                .any(|r| r.contains(cd));
+            match self.field.try_get_p(cd) {
+                Ok(cell) => !in_room && (cell.surface == Surface::Passage || cell.is_hidden()),
+                Err(_) => false,
+            }
+        };
+        let mut visited = HashSet::new();
+        let mut res = Vec::new();
+        for start in self.field.size().into_iter().map(Coord::from) {
+            if visited.contains(&start) || !is_passage(start) {
+                continue;
+            }
+            let mut cells = Vec::new();
+            let mut queue = VecDeque::new();
+            visited.insert(start);
+            queue.push_back(start);
+            while let Some(cd) = queue.pop_front() {
+                cells.push(cd);
+                for d in Direction::into_enum_iter().take(4) {
+                    let next = cd + d.to_cd();
+                    if !visited.contains(&next) && is_passage(next) {
+                        visited.insert(next);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            res.push(cells);
+        }
+        res
+    }
+
     pub(super) fn in_same_room(&self, a: Coord, b: Coord) -> bool {
         let id = match self.cd_to_room_id(a) {
             Some(i) => i,
@@ -526,4 +670,41 @@ mod test {
         }
         assert!(cnt > 15);
     }
+    #[test]
+    fn special_rooms() {
+        use character::enemies::Config as EnemyConfig;
+        use item::Config as ItemConfig;
+        let mut config = Config::default();
+        config.treasure_room.rate_inv = 1;
+        config.monster_zoo.rate_inv = 1;
+        let mut rng = RngHandle::from_seed(1);
+        for level in 2..10 {
+            let mut items = ItemHandler::new(ItemConfig::default(), 1);
+            let mut enemies = EnemyConfig::default().build(1);
+            let mut floor = Floor::gen_floor(level, &config, X(80), Y(24), &mut rng).unwrap();
+            floor.setup_items(level, &mut items, true, &mut rng);
+            floor.place_enemies(level, 0, &mut enemies, &mut rng);
+            let specials: Vec<_> = floor.rooms.iter().filter_map(|r| r.special).collect();
+            assert_eq!(specials.len(), 2);
+            for room in floor.rooms.iter().filter(|r| r.special.is_some()) {
+                let cells: Vec<Coord> = room.range().unwrap().iter().map(Coord::from).collect();
+                let enemies_in_room: Vec<_> = cells
+                    .iter()
+                    .filter_map(|&cd| enemies.get_enemy(&Address::new(level, cd).into()))
+                    .collect();
+                let items_in_room = cells
+                    .iter()
+                    .filter(|cd| floor.items.contains_key(cd))
+                    .count();
+                match room.special.unwrap() {
+                    SpecialRoom::Treasure { items, guards } => {
+                        assert_eq!(items_in_room, items);
+                        assert_eq!(enemies_in_room.len(), guards);
+                        assert!(enemies_in_room.iter().all(|e| !e.is_mean()));
+                    }
+                    SpecialRoom::Zoo { enemies } => assert_eq!(enemies_in_room.len(), enemies),
+                }
+            }
+        }
+    }
 }
@@ -1,10 +1,13 @@
+pub mod export;
 pub mod floor;
 pub mod maze;
 pub mod passages;
 pub mod rooms;
+pub mod validate;
 
+use self::export::{EnemyDump, FloorDump};
 use self::floor::Floor;
-pub use self::rooms::{Room, RoomKind};
+pub use self::rooms::{Room, RoomKind, SpecialRoom, SpecialRoomConfig};
 use super::{Coord, Direction, Dungeon as DungeonTrait, DungeonPath, MoveResult, Positioned, X, Y};
 use character::{player::Status as PlayerStatus, EnemyHandler};
 use enum_iterator::IntoEnumIterator;
@@ -16,6 +19,7 @@ use rng::RngHandle;
 use std::collections::VecDeque;
 use tile::{Drawable, Tile};
 use tuple_map::TupleMap2;
+use validation::Validator;
 use {GameInfo, GameMsg, GlobalConfig};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -57,6 +61,12 @@ pub struct Config {
     pub door_unlock_rate_inv: u32,
     #[serde(default = "default_passage_unlock_rate_inv")]
     pub passage_unlock_rate_inv: u32,
+    /// treasure room, which is filled with gold and items and guarded by sleeping monsters
+    #[serde(default)]
+    pub treasure_room: SpecialRoomConfig,
+    /// monster zoo, which is packed with enemies
+    #[serde(default)]
+    pub monster_zoo: SpecialRoomConfig,
 }
 
 const fn default_room_num_x() -> X {
@@ -128,10 +138,57 @@ impl Default for Config {
             max_extra_edges: default_max_extra_edges(),
             door_unlock_rate_inv: default_door_unlock_rate_inv(),
             passage_unlock_rate_inv: default_passage_unlock_rate_inv(),
+            treasure_room: SpecialRoomConfig::default(),
+            monster_zoo: SpecialRoomConfig::default(),
         }
     }
 }
 
+impl Config {
+    /// checks the config for a dungeon of `width` x `height`
+    pub(crate) fn validate(&self, v: &mut Validator, width: X, height: Y) {
+        let (rn_x, rn_y) = (self.room_num_x.0, self.room_num_y.0);
+        v.check("room_num_x", rn_x > 0, || "must be positive".to_owned());
+        v.check("room_num_y", rn_y > 0, || "must be positive".to_owned());
+        let min = self.min_room_size;
+        v.field("min_room_size", |v| {
+            v.check("x", min.x.0 >= 3, || {
+                format!("{} is smaller than 3", min.x.0)
+            });
+            v.check("y", min.y.0 >= 3, || {
+                format!("{} is smaller than 3", min.y.0)
+            });
+            // rooms on the top and bottom rows are shrunk by 1 not to hit the message area
+            if rn_x > 0 {
+                let cell_x = width.0 / rn_x;
+                v.check("x", min.x.0 < cell_x, || {
+                    format!(
+                        "{} is larger than room cells, which are {} wide",
+                        min.x.0, cell_x
+                    )
+                });
+            }
+            if rn_y > 0 {
+                let cell_y = height.0 / rn_y - if rn_y == 1 { 2 } else { 1 };
+                v.check("y", min.y.0 < cell_y, || {
+                    format!(
+                        "{} is larger than room cells, which are {} high",
+                        min.y.0, cell_y
+                    )
+                });
+            }
+        });
+        v.positive("amulet_level", self.amulet_level);
+        v.positive("maze_rate_inv", self.maze_rate_inv);
+        v.positive("dark_level", self.dark_level);
+        v.positive("hidden_passage_rate_inv", self.hidden_passage_rate_inv);
+        v.positive("locked_door_rate_inv", self.locked_door_rate_inv);
+        v.positive("max_extra_edges", self.max_extra_edges);
+        v.positive("door_unlock_rate_inv", self.door_unlock_rate_inv);
+        v.positive("passage_unlock_rate_inv", self.passage_unlock_rate_inv);
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Surface {
     Passage,
@@ -331,6 +388,44 @@ impl DungeonTrait for Dungeon {
             None
         }
     }
+    fn export_floor(&self, enemies: &EnemyHandler, player: &DungeonPath) -> FloorDump {
+        let player = Address::from_path(player);
+        let player = if player.level == self.level {
+            Some(player.cd)
+        } else {
+            None
+        };
+        let enemies = EnemyDump::collect(self.level, enemies);
+        FloorDump::new(self.level, &self.current_floor, enemies, player)
+    }
+    fn as_rogue(&self) -> Option<&Dungeon> {
+        Some(self)
+    }
+    fn seen_stairs(&self) -> Vec<DungeonPath> {
+        let field = &self.current_floor.field;
+        field
+            .size()
+            .into_iter()
+            .map(Coord::from)
+            .filter(|&cd| {
+                let cell = field.get_p(cd);
+                cell.surface == Surface::Stair && cell.is_obj_visible()
+            })
+            .map(|cd| Address::new(self.level, cd).into())
+            .collect()
+    }
+    fn room_range(&self, player: &DungeonPath) -> Option<RectRange<i32>> {
+        let address = Address::from_path(player);
+        if address.level != self.level {
+            return None;
+        }
+        self.current_floor
+            .rooms
+            .iter()
+            .filter_map(|room| room.range())
+            .find(|range| range.contains(address.cd))
+            .cloned()
+    }
     fn move_enemy(
         &mut self,
         current: &DungeonPath,
@@ -471,6 +566,7 @@ impl Dungeon {
                     let cell = floor.field.get_mut_p(cd);
                     cell.visible(true);
                 });
+            floor.rehash_known();
         }
         ::std::mem::swap(&mut self.current_floor, &mut floor);
         if !is_initial {
@@ -540,7 +636,9 @@ impl Address {
 
 #[cfg(test)]
 mod test {
-    use super::{Address, Coord, Direction, DungeonPath, MoveResult, TupleMap2};
+    use super::{Address, Coord, Direction, DungeonPath, DungeonTrait, MoveResult, TupleMap2};
+    use super::{Get2D, GetMut2D, Surface};
+    use crate::dungeon::CellAttr;
     use crate::{GameConfig, RunTime};
     // tiny dungeon setting
     const CONFIG: &str = r#"
@@ -563,6 +661,25 @@ mod test {
         GameConfig::from_json(CONFIG).unwrap().build().unwrap()
     }
     #[test]
+    fn stairs_seen_before() {
+        let runtime = setup_runtime();
+        let mut dungeon = runtime.dungeon.as_rogue().unwrap().clone();
+        let field = &mut dungeon.current_floor.field;
+        let stair = field
+            .size()
+            .into_iter()
+            .map(Coord::from)
+            .find(|&cd| field.get_p(cd).surface == Surface::Stair)
+            .unwrap();
+        let cell = field.get_mut_p(stair);
+        cell.attr.remove(CellAttr::IS_VISIBLE);
+        cell.attr.insert(CellAttr::HAS_DRAWN);
+        assert_eq!(dungeon.seen_stairs(), vec![Address::new(1, stair).into()]);
+        let cell = dungeon.current_floor.field.get_mut_p(stair);
+        cell.attr.remove(CellAttr::HAS_DRAWN);
+        assert_eq!(dungeon.seen_stairs(), vec![]);
+    }
+    #[test]
     fn test_move_enemy() {
         let mut runtime = setup_runtime();
         let mut check_move = |from, to, direc: Direction| {
@@ -9,7 +9,7 @@ use rng::{RngHandle, SliceRandom};
 use std::collections::HashMap;
 use tuple_map::TupleMap2;
 
-/// make passages between rooms
+/// make passages between rooms and returns their lengths
 pub(super) fn dig_passges<F>(
     rooms: &[Room],
     xrooms: X,
@@ -17,7 +17,7 @@ pub(super) fn dig_passges<F>(
     rng: &mut RngHandle,
     max_extra_edges: u32,
     mut register: F,
-) -> GameResult<()>
+) -> GameResult<Vec<usize>>
 where
     F: FnMut(Positioned<Surface>) -> GameResult<()>,
 {
@@ -26,6 +26,7 @@ where
     let mut selected = FenwickSet::with_capacity(num_rooms);
     let mut cur_room = rng.range(0..num_rooms);
     selected.insert(cur_room);
+    let mut lengths = Vec::new();
     // Connect all rooms
     while selected.len() < num_rooms {
         // select the room which isn't in graph
@@ -35,13 +36,14 @@ where
         if let Some((nxt_room, direction)) = nxt {
             selected.insert(nxt_room);
             graph.coonect(cur_room, nxt_room);
-            connect_2rooms(
+            let len = connect_2rooms(
                 &rooms[cur_room],
                 &rooms[nxt_room],
                 direction,
                 rng,
                 &mut register,
             )?;
+            lengths.push(len);
         } else {
             cur_room = selected.select(rng).unwrap();
         }
@@ -57,10 +59,11 @@ where
         });
         if let Some((room2, direction)) = selected {
             graph.coonect(room1, room2);
-            connect_2rooms(&rooms[room1], &rooms[room2], direction, rng, &mut register)?;
+            let len = connect_2rooms(&rooms[room1], &rooms[room2], direction, rng, &mut register)?;
+            lengths.push(len);
         }
     }
-    Ok(())
+    Ok(lengths)
 }
 
 fn select_candidate(
@@ -78,13 +81,14 @@ fn select_candidate(
         .map(|t| t.1)
 }
 
+/// returns the number of passage cells dug between the doors
 fn connect_2rooms<F>(
     room1: &Room,
     room2: &Room,
     direction: Direction,
     rng: &mut RngHandle,
     register: &mut F,
-) -> GameResult<()>
+) -> GameResult<usize>
 where
     F: FnMut(Positioned<Surface>) -> GameResult<()>,
 {
@@ -125,7 +129,9 @@ where
         .skip(1)
         .chain(turn_start.direc_iter(turn_dir, |cd| cd != turn_end))
         .chain(turn_end.direc_iter(direction, |cd| cd != end))
-        .try_for_each(|cd| register(Positioned(cd, Surface::Passage)))
+        .try_fold(0, |len, cd| {
+            register(Positioned(cd, Surface::Passage)).map(|_| len + 1)
+        })
         .chain_err(|| "passages::connect_2rooms")
 }
 
@@ -18,6 +18,74 @@ pub enum RoomKind {
     Empty { up_left: Coord },
 }
 
+/// configuration of a special room
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SpecialRoomConfig {
+    /// a level has the room with a probability of 1 / rate_inv(0 means never)
+    #[serde(default)]
+    pub rate_inv: u32,
+    /// the room appears only at this level or deeper
+    #[serde(default = "default_special_min_level")]
+    pub min_level: u32,
+    /// number of objects(items or enemies) per 10 cells
+    #[serde(default = "default_special_density")]
+    pub density: u32,
+    /// `rate_inv` of each level from level 1, where the last one is used for deeper levels
+    /// (e.g. `[0, 0, 8, 4]`), which overrides `rate_inv` and `min_level`
+    #[serde(default)]
+    pub rate_inv_per_level: Vec<u32>,
+}
+
+const fn default_special_min_level() -> u32 {
+    2
+}
+
+const fn default_special_density() -> u32 {
+    3
+}
+
+impl Default for SpecialRoomConfig {
+    fn default() -> Self {
+        SpecialRoomConfig {
+            rate_inv: 0,
+            min_level: default_special_min_level(),
+            density: default_special_density(),
+            rate_inv_per_level: vec![],
+        }
+    }
+}
+
+impl SpecialRoomConfig {
+    /// 1 / probability that a level has the room(0 means never)
+    pub fn rate_inv_at(&self, level: u32) -> u32 {
+        if let Some(last) = self.rate_inv_per_level.last() {
+            let i = level.saturating_sub(1) as usize;
+            return *self.rate_inv_per_level.get(i).unwrap_or(last);
+        }
+        if level >= self.min_level {
+            self.rate_inv
+        } else {
+            0
+        }
+    }
+    fn does_appear(&self, level: u32, rng: &mut RngHandle) -> bool {
+        let rate_inv = self.rate_inv_at(level);
+        rate_inv > 0 && rng.does_happen(rate_inv)
+    }
+    fn num_objects(&self, cells: usize) -> usize {
+        ::std::cmp::max(cells * self.density as usize / 10, 1)
+    }
+}
+
+/// special contents of a room
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SpecialRoom {
+    /// treasure room filled with gold and items, guarded by sleeping monsters
+    Treasure { items: usize, guards: usize },
+    /// monster zoo packed with enemies
+    Zoo { enemies: usize },
+}
+
 /// A data structure representing a room in the dungeon
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Room {
@@ -34,6 +102,8 @@ pub struct Room {
     pub is_visited: bool,
     /// if this room has gold or not
     pub has_gold: bool,
+    /// special contents of the room, if any
+    pub special: Option<SpecialRoom>,
     /// cells which has no object
     empty_cells: FenwickSet,
     /// cells which has no enemy
@@ -52,6 +122,7 @@ impl Room {
             empty_cells,
             has_gold: false,
             is_visited: false,
+            special: None,
         }
     }
     /// takes a closure `register` and draw room by it
@@ -126,6 +197,9 @@ impl Room {
             _ => false,
         }
     }
+    pub fn is_treasure(&self) -> bool {
+        matches!(self.special, Some(SpecialRoom::Treasure { .. }))
+    }
     pub fn contains(&self, cd: Coord) -> bool {
         self.assigned_area.contains(cd)
     }
@@ -207,7 +281,38 @@ pub(super) fn gen_rooms(
             let is_empty = empty_rooms.contains(i);
             make_room(is_empty, room_size, lower_left, i, &config, level, rng)
         })
-        .collect()
+        .collect::<GameResult<Vec<_>>>()
+        .map(|mut rooms| {
+            assign_special_rooms(&mut rooms, level, config, rng);
+            rooms
+        })
+}
+
+/// select special rooms from normal rooms
+fn assign_special_rooms(rooms: &mut [Room], level: u32, config: &Config, rng: &mut RngHandle) {
+    let mut select_room = |rooms: &mut [Room], special: &SpecialRoomConfig| {
+        if !special.does_appear(level, rng) {
+            return None;
+        }
+        let candidates: Vec<_> = rooms
+            .iter()
+            .filter(|room| room.is_normal() && room.special.is_none())
+            .map(|room| room.id)
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let id = candidates[rng.range(0..candidates.len())];
+        let cells = rooms[id].empty_cells.len();
+        Some((id, special.num_objects(cells)))
+    };
+    if let Some((id, items)) = select_room(rooms, &config.treasure_room) {
+        let guards = ::std::cmp::max(items / 2, 1);
+        rooms[id].special = Some(SpecialRoom::Treasure { items, guards });
+    }
+    if let Some((id, enemies)) = select_room(rooms, &config.monster_zoo) {
+        rooms[id].special = Some(SpecialRoom::Zoo { enemies });
+    }
 }
 
 /// generata a room
@@ -305,6 +410,18 @@ pub(super) mod test {
         }
     }
     #[test]
+    fn special_room_rates() {
+        let mut config = SpecialRoomConfig {
+            rate_inv: 3,
+            ..SpecialRoomConfig::default()
+        };
+        assert_eq!(config.rate_inv_at(1), 0);
+        assert_eq!(config.rate_inv_at(2), 3);
+        config.rate_inv_per_level = vec![0, 8, 4];
+        let rates: Vec<_> = (1..6).map(|l| config.rate_inv_at(l)).collect();
+        assert_eq!(rates, vec![0, 8, 4, 4, 4]);
+    }
+    #[test]
     fn pos_check() {
         let (xrooms, yrooms) = (3, 3);
         use enum_iterator::IntoEnumIterator;
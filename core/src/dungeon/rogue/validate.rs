@@ -0,0 +1,311 @@
+//! invariant checker and statistics for generated floors
+use super::export::{EnemyDump, FloorDump};
+use super::{floor::Floor, RoomKind, Surface};
+use dungeon::{Coord, Direction, DungeonStyle, X, Y};
+use enum_iterator::IntoEnumIterator;
+use error::*;
+use item::{ItemHandler, ItemKind};
+use rect_iter::Get2D;
+use rng::RngHandle;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use GameConfig;
+
+/// a floor generated in the same way as `Dungeon::new_level`, with the player's spawn point
+/// and enemies
+#[derive(Clone, Debug)]
+pub struct FloorSample {
+    pub level: u32,
+    pub floor: Floor,
+    pub spawn: Coord,
+    pub enemies: Vec<EnemyDump>,
+}
+
+impl FloorSample {
+    /// generates floors from level 1 to `levels` by `Floor::gen_floor` with the given seed
+    pub fn generate(config: &GameConfig, seed: u128, levels: u32) -> GameResult<Vec<FloorSample>> {
+        const ERR_STR: &str = "FloorSample::generate";
+        let rogue = match config.dungeon {
+            DungeonStyle::Rogue(ref rogue) => rogue,
+            _ => {
+                return Err(ErrorId::InvalidSetting.into_with(|| "only rogue dungeon is supported"))
+            }
+        };
+        let (width, height) = config.dungeon_size();
+        let (width, height) = (X(width), Y(height));
+        let mut rng = RngHandle::from_seed(seed);
+        let mut item_handle = ItemHandler::new(config.item.clone(), seed);
+        let mut enemies = config.enemies.clone().build(seed);
+        (1..=levels)
+            .map(|level| {
+                let mut floor = Floor::gen_floor(level, rogue, width, height, &mut rng)?;
+                floor.setup_items(level, &mut item_handle, true, &mut rng);
+                floor.setup_stair(&mut rng)?;
+                let lev_add = level.saturating_sub(rogue.amulet_level);
+                floor.place_enemies(level, lev_add, &mut enemies, &mut rng);
+                let spawn = floor.select_cell(&mut rng, true).ok_or_else(|| {
+                    ErrorId::MaybeBug.into_with(|| "FloorSample::generate No space for player!")
+                })?;
+                Ok(FloorSample {
+                    level,
+                    floor,
+                    spawn,
+                    enemies: EnemyDump::collect(level, &enemies),
+                })
+            })
+            .collect::<GameResult<Vec<_>>>()
+            .chain_err(|| ERR_STR)
+    }
+    /// checks the invariants of the floor
+    pub fn validate(&self) -> Vec<Violation> {
+        let enemies: Vec<_> = self.enemies.iter().map(|e| e.cd).collect();
+        self.floor.validate(self.spawn, &enemies)
+    }
+    /// dumps the floor, where the player is at the spawn point
+    pub fn dump(&self) -> FloorDump {
+        FloorDump::new(
+            self.level,
+            &self.floor,
+            self.enemies.clone(),
+            Some(self.spawn),
+        )
+    }
+}
+
+/// a broken invariant of a floor
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Violation {
+    /// a room can't be reached from the spawn point
+    DisconnectedRoom { id: usize },
+    /// there's no stair in the floor
+    NoStair,
+    /// the stair can't be reached from the spawn point
+    UnreachableStair { cd: Coord },
+    /// an item can't be reached from the spawn point
+    UnreachableItem { cd: Coord },
+    /// an object is placed on a wall
+    ObjectOnWall { cd: Coord },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::DisconnectedRoom { id } => write!(f, "room {} is disconnected", id),
+            Violation::NoStair => write!(f, "no stair"),
+            Violation::UnreachableStair { cd } => write!(f, "stair at {:?} is unreachable", cd),
+            Violation::UnreachableItem { cd } => write!(f, "item at {:?} is unreachable", cd),
+            Violation::ObjectOnWall { cd } => write!(f, "object at {:?} is on a wall", cd),
+        }
+    }
+}
+
+impl Floor {
+    /// checks the invariants of the floor, assuming the player starts from `spawn` and
+    /// enemies are at `enemies`
+    /// hidden passages and locked doors are regarded as walkable, since they can be found by
+    /// searching
+    pub fn validate(&self, spawn: Coord, enemies: &[Coord]) -> Vec<Violation> {
+        let mut res = Vec::new();
+        let reachable = self.reachable_cells(spawn);
+        for room in &self.rooms {
+            let is_reached = match room.kind {
+                RoomKind::Normal { ref range } => range
+                    .iter()
+                    .any(|cd| !range.is_edge(cd) && reachable.contains(&cd.into())),
+                RoomKind::Maze(ref maze) => maze.passages().any(|cd| reachable.contains(&cd)),
+                RoomKind::Empty { up_left } => reachable.contains(&up_left),
+            };
+            if !is_reached {
+                res.push(Violation::DisconnectedRoom { id: room.id });
+            }
+        }
+        let stairs: Vec<_> = self
+            .field
+            .size()
+            .into_iter()
+            .map(Coord::from)
+            .filter(|&cd| self.field.get_p(cd).surface == Surface::Stair)
+            .collect();
+        if stairs.is_empty() {
+            res.push(Violation::NoStair);
+        }
+        for &cd in &stairs {
+            if !reachable.contains(&cd) {
+                res.push(Violation::UnreachableStair { cd });
+            }
+            if self.is_on_room_edge(cd) {
+                res.push(Violation::ObjectOnWall { cd });
+            }
+        }
+        let mut items: Vec<_> = self.items.keys().cloned().collect();
+        items.sort_by_key(|cd| (cd.y, cd.x));
+        for cd in items {
+            if !reachable.contains(&cd) {
+                res.push(Violation::UnreachableItem { cd });
+            }
+            let surface = self.field.get_p(cd).surface;
+            if (surface != Surface::Floor && surface != Surface::Passage)
+                || self.is_on_room_edge(cd)
+            {
+                res.push(Violation::ObjectOnWall { cd });
+            }
+        }
+        // enemies can also stand on stairs or doors
+        for &cd in enemies {
+            if !self.field.get_p(cd).surface.can_walk() {
+                res.push(Violation::ObjectOnWall { cd });
+            }
+        }
+        res
+    }
+    fn is_on_room_edge(&self, cd: Coord) -> bool {
+        self.rooms.iter().any(|room| match room.kind {
+            RoomKind::Normal { ref range } => range.is_edge(cd),
+            _ => false,
+        })
+    }
+    fn can_walk_eventually(&self, cd: Coord) -> bool {
+        match self.field.try_get_p(cd) {
+            Ok(cell) => cell.surface.can_walk() || cell.is_hidden() || cell.is_locked(),
+            Err(_) => false,
+        }
+    }
+    fn reachable_cells(&self, start: Coord) -> HashSet<Coord> {
+        let mut reached = HashSet::new();
+        let mut queue = VecDeque::new();
+        reached.insert(start);
+        queue.push_back(start);
+        while let Some(cd) = queue.pop_front() {
+            for d in Direction::into_enum_iter().take(8) {
+                let next = cd + d.to_cd();
+                if reached.contains(&next) || !self.can_walk_eventually(next) {
+                    continue;
+                }
+                if d.is_diag()
+                    && !(self.can_walk_eventually(cd + d.x())
+                        && self.can_walk_eventually(cd + d.y()))
+                {
+                    continue;
+                }
+                reached.insert(next);
+                queue.push_back(next);
+            }
+        }
+        reached
+    }
+}
+
+/// statistics of a floor
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FloorStats {
+    /// number of rooms which are not empty
+    pub rooms: usize,
+    /// number of maze rooms
+    pub mazes: usize,
+    /// number of dark rooms
+    pub dark_rooms: usize,
+    /// lengths of passages between rooms
+    pub passage_lengths: Vec<usize>,
+    /// total amount of gold
+    pub gold: u32,
+}
+
+impl FloorStats {
+    pub fn from_floor(floor: &Floor) -> Self {
+        let rooms = floor.rooms.iter().filter(|room| !room.is_empty());
+        FloorStats {
+            rooms: rooms.clone().count(),
+            mazes: rooms
+                .clone()
+                .filter(|room| matches!(room.kind, RoomKind::Maze(_)))
+                .count(),
+            dark_rooms: rooms.filter(|room| room.is_dark).count(),
+            passage_lengths: floor.passage_lengths.clone(),
+            gold: floor
+                .items
+                .values()
+                .filter(|token| token.get().kind == ItemKind::Gold)
+                .map(|token| token.get().how_many.0)
+                .sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn default_floors_are_valid() {
+        let config = GameConfig::default();
+        for seed in 0..20 {
+            let floors = FloorSample::generate(&config, seed, 10).unwrap();
+            for sample in floors {
+                assert_eq!(
+                    sample.validate(),
+                    vec![],
+                    "seed: {} level: {}",
+                    seed,
+                    sample.level
+                );
+                let stats = FloorStats::from_floor(&sample.floor);
+                assert!(stats.rooms > 0);
+                assert!(stats.mazes <= stats.rooms);
+                assert!(stats.dark_rooms <= stats.rooms);
+                assert!(stats.passage_lengths.len() + 1 >= sample.floor.rooms.len());
+            }
+        }
+    }
+    #[test]
+    fn detect_object_on_wall() {
+        let config = GameConfig::default();
+        let mut tested = false;
+        for seed in 0..20 {
+            let mut sample = FloorSample::generate(&config, seed, 1)
+                .unwrap()
+                .pop()
+                .unwrap();
+            let (cd, token) = match sample.floor.items.iter().next() {
+                Some((&cd, token)) => (cd, token.clone()),
+                None => continue,
+            };
+            let range = match sample.floor.rooms.iter().find(|room| room.contains(cd)) {
+                Some(room) if room.is_normal() => room.range().unwrap().clone(),
+                _ => continue,
+            };
+            let wall = Coord::from(range.lower_left());
+            sample.floor.items.remove(&cd);
+            sample.floor.items.insert(wall, token);
+            assert!(sample
+                .validate()
+                .contains(&Violation::ObjectOnWall { cd: wall }));
+            tested = true;
+        }
+        assert!(tested);
+    }
+    #[test]
+    fn detect_enemy_on_wall() {
+        let config = GameConfig::default();
+        let mut tested = false;
+        for seed in 0..20 {
+            for mut sample in FloorSample::generate(&config, seed, 5).unwrap() {
+                let enemy = match sample.enemies.first_mut() {
+                    Some(enemy) => enemy,
+                    None => continue,
+                };
+                let room = sample
+                    .floor
+                    .rooms
+                    .iter()
+                    .find(|room| room.is_normal())
+                    .unwrap();
+                let wall = Coord::from(room.range().unwrap().lower_left());
+                enemy.cd = wall;
+                assert!(sample
+                    .validate()
+                    .contains(&Violation::ObjectOnWall { cd: wall }));
+                tested = true;
+            }
+        }
+        assert!(tested);
+    }
+}
@@ -0,0 +1,354 @@
+//! gym-like environments to train agents in Rust, without key inputs or screens
+use character::player::Status;
+use character::Action;
+use dungeon::{Direction, Positioned};
+use error::*;
+use events::{EpisodeStats, StepEvents, Termination};
+use input::{InputCode, Key, KeyMap};
+use ndarray::{s, Array2, Array3, ArrayViewMut3};
+use observation::ImageSetting;
+use reward::{Reward, RewardTracker};
+use rng::gen_seed;
+use symbol::SymbolTable;
+use tile::Tile;
+use {GameConfig, RunTime};
+
+/// actions of `KeyMap::ai`, in the order of `KeyMap::AI_KEYS` and action masks
+pub const AI_ACTIONS: [Action; 19] = [
+    Action::NoOp,
+    Action::Move(Direction::Left),
+    Action::Move(Direction::Down),
+    Action::Move(Direction::Up),
+    Action::Move(Direction::Right),
+    Action::Move(Direction::RightDown),
+    Action::Move(Direction::LeftDown),
+    Action::Move(Direction::RightUp),
+    Action::Move(Direction::LeftUp),
+    Action::DownStair,
+    Action::Search,
+    Action::MoveUntil(Direction::Left),
+    Action::MoveUntil(Direction::Down),
+    Action::MoveUntil(Direction::Up),
+    Action::MoveUntil(Direction::Right),
+    Action::MoveUntil(Direction::RightDown),
+    Action::MoveUntil(Direction::LeftDown),
+    Action::MoveUntil(Direction::RightUp),
+    Action::MoveUntil(Direction::LeftUp),
+];
+
+/// an environment for reinforcement learning
+pub trait Env {
+    type Action;
+    type Observation;
+    type Info;
+    /// starts a new episode with `seed`, or the seed in the config if None
+    fn reset(&mut self, seed: Option<u128>) -> GameResult<Self::Observation>;
+    /// returns (observation, reward, done, info)
+    fn step(
+        &mut self,
+        action: Self::Action,
+    ) -> GameResult<(Self::Observation, f32, bool, Self::Info)>;
+}
+
+/// what the player observes, same as `PlayerState` in python
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    /// tiles as `[height, width]`
+    pub map: Array2<u8>,
+    /// cells the player has visited as `[height, width]`
+    pub history: Array2<bool>,
+    pub status: Status,
+    /// legality of `AI_ACTIONS`
+    pub action_mask: Vec<bool>,
+    /// the symbol table used in images
+    pub symbols: SymbolTable,
+}
+
+impl Observation {
+    fn new(runtime: &RunTime, viewport: bool, symbols: SymbolTable) -> GameResult<Self> {
+        let status = runtime.player_status();
+        let history = runtime.history(&status).ok_or_else(|| {
+            ErrorId::Unimplemented.into_with(|| "Observation::new: the dungeon has no history")
+        })?;
+        let (w, h) = if viewport {
+            runtime.screen_size()
+        } else {
+            runtime.dungeon_size()
+        };
+        let mut map = Array2::from_elem([h.0 as usize, w.0 as usize], b' ');
+        let draw = |Positioned(cd, tile): Positioned<Tile>| {
+            let idx = [cd.y.0 as usize, cd.x.0 as usize];
+            *map.get_mut(idx).ok_or_else(|| {
+                ErrorId::MaybeBug.into_with(|| "Observation::new: out of the screen")
+            })? = tile.to_byte();
+            Ok(())
+        };
+        let history = if viewport {
+            runtime.draw_viewport(draw)?;
+            let viewport = runtime.viewport();
+            let (x, y) = (viewport.offset.x.0 as usize, viewport.offset.y.0 as usize);
+            let (w, h) = (viewport.width.0 as usize, viewport.height.0 as usize);
+            let mut history = history.slice(s![y..y + h, x..x + w]).to_owned();
+            history.row_mut(0).fill(false);
+            history.row_mut(h - 1).fill(false);
+            history
+        } else {
+            runtime.draw_screen(draw)?;
+            history
+        };
+        let keys = KeyMap::AI_KEYS.iter().map(|&c| Key::Char(c));
+        Ok(Observation {
+            map,
+            history,
+            status,
+            action_mask: runtime.action_mask(keys),
+            symbols,
+        })
+    }
+    pub fn image_shape(&self, setting: &ImageSetting) -> [usize; 3] {
+        setting.shape(&self.symbols, self.map.dim())
+    }
+    /// writes the image into `out`, which has to be `image_shape` sized
+    pub fn encode(&self, setting: &ImageSetting, out: ArrayViewMut3<f32>) -> GameResult<()> {
+        let map: Vec<Vec<u8>> = self.map.outer_iter().map(|row| row.to_vec()).collect();
+        setting
+            .encode(&map, &self.history, &self.status, &self.symbols, out)
+            .map_err(Into::into)
+    }
+    pub fn image(&self, setting: &ImageSetting) -> GameResult<Array3<f32>> {
+        let mut res = Array3::zeros(self.image_shape(setting));
+        self.encode(setting, res.view_mut())?;
+        Ok(res)
+    }
+}
+
+/// what happened in a step
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepInfo {
+    pub reward: Reward,
+    pub events: StepEvents,
+    /// aggregates of the episode, only for terminal steps
+    pub episode: Option<EpisodeStats>,
+}
+
+/// an environment playing a game built from `GameConfig`
+pub struct RogueEnv {
+    config: GameConfig,
+    runtime: RunTime,
+    reward: RewardTracker,
+    episode: EpisodeStats,
+    steps: usize,
+    max_steps: usize,
+    /// observe only the viewport instead of the whole dungeon
+    viewport: bool,
+    symbols: SymbolTable,
+    done: bool,
+}
+
+impl RogueEnv {
+    pub fn new(config: GameConfig, max_steps: usize, viewport: bool) -> GameResult<Self> {
+        let symbols = config.symbol_table()?;
+        let mut runtime = config.clone().build()?;
+        // action masks are computed by the keymap
+        runtime.keymap = KeyMap::ai();
+        let reward = RewardTracker::new(config.reward.clone(), &runtime);
+        Ok(RogueEnv {
+            config,
+            runtime,
+            reward,
+            episode: EpisodeStats::default(),
+            steps: 0,
+            max_steps,
+            viewport,
+            symbols,
+            done: false,
+        })
+    }
+    pub fn runtime(&self) -> &RunTime {
+        &self.runtime
+    }
+    pub fn observe(&self) -> GameResult<Observation> {
+        Observation::new(&self.runtime, self.viewport, self.symbols)
+    }
+}
+
+impl Env for RogueEnv {
+    type Action = Action;
+    type Observation = Observation;
+    type Info = StepInfo;
+    fn reset(&mut self, seed: Option<u128>) -> GameResult<Observation> {
+        let mut config = self.config.clone();
+        if seed.is_some() {
+            config.seed = seed;
+        }
+        self.runtime = config.build()?;
+        self.runtime.keymap = KeyMap::ai();
+        self.reward = RewardTracker::new(self.config.reward.clone(), &self.runtime);
+        self.episode = EpisodeStats::default();
+        self.steps = 0;
+        self.done = false;
+        self.observe()
+    }
+    fn step(&mut self, action: Action) -> GameResult<(Observation, f32, bool, StepInfo)> {
+        if self.done {
+            return Err(ErrorId::InvalidSetting
+                .into_with(|| "RogueEnv::step: the episode is done, call reset"));
+        }
+        let prev = self.runtime.player_status();
+        let reactions = self.runtime.react_to_input(InputCode::Act(action))?;
+        let reward = self.reward.step(&self.runtime, &reactions);
+        let status = self.runtime.player_status();
+        let mut events = StepEvents::new(&reactions, &prev, &status);
+        self.steps += 1;
+        if events.termination.is_none() && self.steps >= self.max_steps {
+            events.termination = Some(Termination::MaxSteps);
+        }
+        self.episode.push(&events, &reward, &status);
+        self.done = events.termination.is_some();
+        let info = StepInfo {
+            episode: if self.done {
+                Some(self.episode.clone())
+            } else {
+                None
+            },
+            reward,
+            events,
+        };
+        Ok((self.observe()?, info.reward.total(), self.done, info))
+    }
+}
+
+/// multiple environments stepped together, which reset themselves when done
+pub struct VecEnv<E> {
+    envs: Vec<E>,
+    /// base of seeds used when environments reset themselves
+    seed: u128,
+    /// how many times each environment has reset itself since `reset`
+    resets: Vec<u128>,
+}
+
+impl<E: Env> VecEnv<E> {
+    pub fn new(envs: Vec<E>) -> Self {
+        let resets = vec![0; envs.len()];
+        VecEnv {
+            envs,
+            seed: gen_seed(),
+            resets,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+    pub fn envs(&self) -> &[E] {
+        &self.envs
+    }
+    /// resets all environments, where the i-th one uses `seed + i`.
+    /// After that, the k-th episode the i-th environment starts by itself uses `seed + i + k * n`,
+    /// or a random seed stream when `seed` is `None`.
+    pub fn reset(&mut self, seed: Option<u128>) -> GameResult<Vec<E::Observation>> {
+        self.seed = seed.unwrap_or_else(gen_seed);
+        self.resets.iter_mut().for_each(|k| *k = 0);
+        self.envs
+            .iter_mut()
+            .enumerate()
+            .map(|(i, env)| env.reset(seed.map(|s| s + i as u128)))
+            .collect()
+    }
+    /// steps all environments by `actions`.
+    /// Observations of terminal steps are replaced by the first ones of next episodes.
+    #[allow(clippy::type_complexity)]
+    pub fn step(
+        &mut self,
+        actions: impl IntoIterator<Item = E::Action>,
+    ) -> GameResult<(Vec<E::Observation>, Vec<f32>, Vec<bool>, Vec<E::Info>)> {
+        let n = self.envs.len();
+        let (mut obs, mut rewards) = (Vec::with_capacity(n), Vec::with_capacity(n));
+        let (mut dones, mut infos) = (Vec::with_capacity(n), Vec::with_capacity(n));
+        let mut actions = actions.into_iter();
+        for (i, env) in self.envs.iter_mut().enumerate() {
+            let action = actions.next().ok_or_else(|| {
+                ErrorId::InvalidSetting.into_with(|| "VecEnv::step: too few actions")
+            })?;
+            let (o, r, done, info) = env.step(action)?;
+            obs.push(if done {
+                let k = &mut self.resets[i];
+                *k += 1;
+                let seed = (i as u128)
+                    .wrapping_add(k.wrapping_mul(n as u128))
+                    .wrapping_add(self.seed);
+                env.reset(Some(seed))?
+            } else {
+                o
+            });
+            rewards.push(r);
+            dones.push(done);
+            infos.push(info);
+        }
+        Ok((obs, rewards, dones, infos))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use observation::StatusFlag;
+    fn config() -> GameConfig {
+        let mut config = GameConfig::default();
+        config.seed = Some(1);
+        config.enemies.enemies.clear();
+        config
+    }
+    #[test]
+    fn ai_actions() {
+        let keymap = KeyMap::ai();
+        for (&c, &action) in KeyMap::AI_KEYS.iter().zip(AI_ACTIONS.iter()) {
+            assert_eq!(keymap.get(Key::Char(c)), Some(InputCode::Act(action)));
+        }
+    }
+    #[test]
+    fn step_env() {
+        let mut env = RogueEnv::new(config(), 5, false).unwrap();
+        let obs = env.reset(None).unwrap();
+        assert_eq!(obs.map.dim(), (24, 80));
+        let setting = ImageSetting::new(false, StatusFlag::default(), true);
+        let image = obs.image(&setting).unwrap();
+        assert_eq!(image.dim(), (obs.symbols.len() + 1, 24, 80));
+        for i in 0..5 {
+            let (_, reward, done, info) = env.step(Action::Search).unwrap();
+            assert_eq!(reward, info.reward.total());
+            assert_eq!(done, i == 4);
+            assert_eq!(info.episode.is_some(), done);
+        }
+        assert!(env.step(Action::Search).is_err());
+        assert_eq!(env.reset(None).unwrap(), obs);
+    }
+    #[test]
+    fn vec_env() {
+        let envs = (0..3)
+            .map(|_| RogueEnv::new(config(), 2, true).unwrap())
+            .collect();
+        let mut envs = VecEnv::new(envs);
+        let first = envs.reset(Some(10)).unwrap();
+        assert_ne!(first[0], first[1]);
+        assert_eq!(first[0].map.dim(), (24, 80));
+        let (_, _, dones, _) = envs.step(vec![Action::NoOp; 3]).unwrap();
+        assert_eq!(dones, vec![false; 3]);
+        let (obs, _, dones, infos) = envs.step(vec![Action::NoOp; 3]).unwrap();
+        assert_eq!(dones, vec![true; 3]);
+        assert!(infos.iter().all(|info| info.episode.is_some()));
+        // next episodes use new seeds, not the seed in the config
+        let mut env = RogueEnv::new(config(), 2, true).unwrap();
+        assert_ne!(obs[0], env.reset(None).unwrap());
+        assert_ne!(obs[0], first[0]);
+        assert_ne!(obs[0], obs[1]);
+        assert_eq!(obs[1], env.reset(Some(10 + 1 + 3)).unwrap());
+        envs.step(vec![Action::NoOp; 3]).unwrap();
+        let (again, _, _, _) = envs.step(vec![Action::NoOp; 3]).unwrap();
+        assert_ne!(again[1], obs[1]);
+        assert_eq!(again[1], env.reset(Some(10 + 1 + 2 * 3)).unwrap());
+        assert!(envs.step(vec![Action::NoOp; 2]).is_err());
+    }
+}
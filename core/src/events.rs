@@ -0,0 +1,137 @@
+//! structured records of what happened in steps and episodes
+use character::player::Status;
+use item::ItemKind;
+use reward::Reward;
+use ui::{MordalKind, UiState};
+use {GameMsg, Reaction, SmallStr};
+
+/// why an episode ended
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Termination {
+    /// the player died, with the message on the grave
+    Death(String),
+    /// the episode reached the step limit
+    MaxSteps,
+}
+
+/// what happened in a step
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepEvents {
+    /// all messages in the order they were notified
+    pub messages: Vec<GameMsg>,
+    /// items the player picked up
+    pub items: Vec<(ItemKind, u32)>,
+    /// names of enemies the player killed
+    pub kills: Vec<SmallStr>,
+    pub damage_dealt: i64,
+    pub damage_taken: i64,
+    /// (previous level, current level) if the player moved to another level
+    pub level_change: Option<(u32, u32)>,
+    pub termination: Option<Termination>,
+}
+
+impl StepEvents {
+    /// collects events from reactions to an input, where `prev` and `cur` are
+    /// the player status before and after the input
+    pub fn new(reactions: &[Reaction], prev: &Status, cur: &Status) -> Self {
+        let mut res = StepEvents::default();
+        for reaction in reactions {
+            match reaction {
+                Reaction::Notify(msg) => {
+                    match msg {
+                        GameMsg::GotItem { kind, num } => res.items.push((kind.clone(), *num)),
+                        GameMsg::Killed(name) => res.kills.push(name.clone()),
+                        GameMsg::HitTo(_, hp) => res.damage_dealt += hp.0,
+                        GameMsg::HitFrom(_, hp) => res.damage_taken += hp.0,
+                        _ => {}
+                    }
+                    res.messages.push(msg.clone());
+                }
+                Reaction::UiTransition(UiState::Mordal(MordalKind::Grave(msg))) => {
+                    res.termination = Some(Termination::Death(msg.to_string()));
+                }
+                _ => {}
+            }
+        }
+        if prev.dungeon_level != cur.dungeon_level {
+            res.level_change = Some((prev.dungeon_level, cur.dungeon_level));
+        }
+        res
+    }
+}
+
+/// aggregates of events in an episode
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EpisodeStats {
+    pub steps: usize,
+    /// sum of rewards
+    pub reward: f32,
+    pub kills: usize,
+    /// number of times the player picked up items
+    pub items: usize,
+    pub damage_dealt: i64,
+    pub damage_taken: i64,
+    /// the deepest level the player reached
+    pub max_level: u32,
+    pub gold: u32,
+    pub termination: Option<Termination>,
+}
+
+impl EpisodeStats {
+    /// adds events of a step, where `status` is the player status after the step
+    pub fn push(&mut self, events: &StepEvents, reward: &Reward, status: &Status) {
+        self.steps += 1;
+        self.reward += reward.total();
+        self.kills += events.kills.len();
+        self.items += events.items.len();
+        self.damage_dealt += events.damage_dealt;
+        self.damage_taken += events.damage_taken;
+        self.max_level = self.max_level.max(status.dungeon_level);
+        self.gold = status.gold;
+        if events.termination.is_some() {
+            self.termination = events.termination.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use character::HitPoint;
+    use dungeon::Direction;
+    #[test]
+    fn collect_events() {
+        let prev = Status::default();
+        let cur = Status {
+            dungeon_level: prev.dungeon_level + 1,
+            ..prev.clone()
+        };
+        let reactions = vec![
+            Reaction::Notify(GameMsg::CantMove(Direction::Left)),
+            Reaction::Notify(GameMsg::HitTo(SmallStr::from_str("bat"), HitPoint(3))),
+            Reaction::Notify(GameMsg::Killed(SmallStr::from_str("bat"))),
+            Reaction::Notify(GameMsg::HitFrom(SmallStr::from_str("kestrel"), HitPoint(2))),
+            Reaction::StatusUpdated,
+            Reaction::UiTransition(UiState::die("Killed by kestrel".to_owned())),
+        ];
+        let events = StepEvents::new(&reactions, &prev, &cur);
+        assert_eq!(events.messages.len(), 4);
+        assert_eq!(events.kills, vec![SmallStr::from_str("bat")]);
+        assert_eq!((events.damage_dealt, events.damage_taken), (3, 2));
+        assert_eq!(events.level_change, Some((0, 1)));
+        let death = Some(Termination::Death("Killed by kestrel".to_owned()));
+        assert_eq!(events.termination, death);
+        let mut episode = EpisodeStats::default();
+        let reward = Reward {
+            kill: 1.0,
+            ..Reward::default()
+        };
+        episode.push(&events, &reward, &cur);
+        episode.push(&StepEvents::default(), &reward, &cur);
+        assert_eq!(episode.steps, 2);
+        assert_eq!(episode.reward, 2.0);
+        assert_eq!(episode.kills, 1);
+        assert_eq!(episode.max_level, 1);
+        assert_eq!(episode.termination, death);
+    }
+}
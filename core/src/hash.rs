@@ -0,0 +1,287 @@
+//! stable hashing of game states
+//!
+//! Hashes don't depend on platforms, versions of std or runs, so that they can be
+//! used to count visited states or dedup replays.
+use character::player::Status;
+use dungeon::Positioned;
+use error::*;
+use ndarray::Array2;
+use serde::Serialize;
+use serde_json;
+use std::str::FromStr;
+use trajectory::Frame;
+use RunTime;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64bit FNV-1a, which doesn't depend on platforms or versions of std
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Fnv64(u64);
+
+impl Fnv64 {
+    pub(crate) fn new() -> Self {
+        Fnv64(FNV_OFFSET)
+    }
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+    pub(crate) fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+    /// writes `value` as JSON, followed by a separator
+    pub(crate) fn write_json<T: Serialize + ?Sized>(&mut self, value: &T) -> GameResult<()> {
+        let json = serde_json::to_vec(value).into_chained(|| "Fnv64::write_json")?;
+        self.write(&json);
+        self.write(b"\n");
+        Ok(())
+    }
+    pub(crate) fn finish(self) -> u64 {
+        self.0
+    }
+}
+
+/// how much of the game state a hash covers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashGranularity {
+    /// the map the player knows except the player, and the dungeon level
+    Known,
+    /// the map with the player, and the status
+    MapAndStatus,
+    /// the ground truth, including hidden cells, items, enemies and random number generators
+    Full,
+}
+
+impl FromStr for HashGranularity {
+    type Err = ::failure::Error;
+    fn from_str(s: &str) -> GameResult<Self> {
+        match s {
+            "known" => Ok(HashGranularity::Known),
+            "map_and_status" => Ok(HashGranularity::MapAndStatus),
+            "full" => Ok(HashGranularity::Full),
+            _ => Err(ErrorId::InvalidConversion.into_with(|| {
+                format!(
+                    "Unknown granularity {}, expected 'known', 'map_and_status' or 'full'",
+                    s
+                )
+            })),
+        }
+    }
+}
+
+/// splitmix64, to spread hashes of cells
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn cell_hash(x: usize, y: usize, tile: u8) -> u64 {
+    mix((x as u64) | (y as u64) << 16 | u64::from(tile) << 32)
+}
+
+/// XOR of hashes of all cells in a map(a.k.a. Zobrist hashing), which can be
+/// updated incrementally when cells are redrawn
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MapHash(u64);
+
+impl MapHash {
+    pub fn new(map: &Array2<u8>) -> Self {
+        let mut res = MapHash::default();
+        for ((y, x), &tile) in map.indexed_iter() {
+            res.0 ^= cell_hash(x, y, tile);
+        }
+        res
+    }
+    /// updates the hash by a cell changed from `old` to `new`
+    pub fn update(&mut self, x: usize, y: usize, old: u8, new: u8) {
+        if old != new {
+            self.0 ^= cell_hash(x, y, old) ^ cell_hash(x, y, new);
+        }
+    }
+    /// hash of the map and `status`, same as `HashGranularity::MapAndStatus`
+    pub fn with_status(self, status: &Status) -> u64 {
+        let mut hasher = Fnv64::new();
+        hasher.write(&self.0.to_le_bytes());
+        status
+            .to_vec()
+            .into_iter()
+            .for_each(|s| hasher.write_u32(s));
+        hasher.finish()
+    }
+}
+
+/// hash of surfaces the player has seen and the dungeon level
+fn known_hash(runtime: &RunTime) -> GameResult<u64> {
+    let map_hash = match runtime.dungeon.as_rogue() {
+        Some(dungeon) => dungeon.current_floor.known_hash(),
+        None => drawn_map_hash(runtime)?,
+    };
+    let mut hasher = Fnv64::new();
+    hasher.write(&map_hash.0.to_le_bytes());
+    hasher.write_u32(runtime.dungeon.level());
+    Ok(hasher.finish())
+}
+
+/// `MapHash` of the dungeon drawn without the player
+fn drawn_map_hash(runtime: &RunTime) -> GameResult<MapHash> {
+    let (w, h) = runtime.dungeon_size();
+    let mut map = Array2::from_elem([h.0 as usize, w.0 as usize], b' ');
+    runtime.dungeon.draw(&mut |Positioned(cd, tile)| {
+        let idx = [cd.y.0 as usize, cd.x.0 as usize];
+        *map.get_mut(idx)
+            .ok_or_else(|| ErrorId::MaybeBug.into_with(|| "known_hash: out of the dungeon"))? =
+            tile.to_byte();
+        Ok(())
+    })?;
+    Ok(MapHash::new(&map))
+}
+
+pub(crate) fn state_hash(runtime: &RunTime, granularity: HashGranularity) -> GameResult<u64> {
+    match granularity {
+        HashGranularity::Known => known_hash(runtime),
+        HashGranularity::MapAndStatus => {
+            let frame = Frame::from_runtime(runtime)?;
+            Ok(MapHash::new(&frame.map).with_status(&runtime.player_status()))
+        }
+        HashGranularity::Full => full_hash(runtime),
+    }
+}
+
+/// hash of the ground truth of the game, which covers
+/// - the screen, the history map and the status
+/// - the current floor dump(all cells with attributes, items and enemies on the floor)
+/// - the player's position, internal status(hunger, regeneration...), equipments and inventory
+/// - all placed and active enemies with their hit points and states
+/// - states of the random number generators of the dungeon, items and enemies
+///
+/// Past floors and messages are not covered.
+pub(crate) fn full_hash(runtime: &RunTime) -> GameResult<u64> {
+    let mut hasher = Fnv64::new();
+    let frame = Frame::from_runtime(runtime)?;
+    let (h, w) = frame.map.dim();
+    hasher.write_u32(h as u32);
+    hasher.write_u32(w as u32);
+    hasher.write(&frame.map.iter().cloned().collect::<Vec<_>>());
+    let history: Vec<_> = frame.history.iter().map(|&b| b as u8).collect();
+    hasher.write(&history);
+    frame.status.iter().for_each(|&s| hasher.write_u32(s));
+    hasher.write_json(&runtime.export_floor())?;
+    runtime.player.hash_state(&mut hasher)?;
+    runtime.enemies.hash_state(&mut hasher)?;
+    runtime.item.hash_state(&mut hasher)?;
+    if let Some(dungeon) = runtime.dungeon.as_rogue() {
+        hasher.write_json(&dungeon.rng)?;
+    }
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn fnv_vectors() {
+        let hash = |s: &str| {
+            let mut hasher = Fnv64::new();
+            hasher.write(s.as_bytes());
+            hasher.finish()
+        };
+        assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash("foobar"), 0x8594_4171_f739_67e8);
+    }
+    #[test]
+    fn incremental_map_hash() {
+        let mut map = Array2::from_elem([3, 4], b'.');
+        let mut hash = MapHash::new(&map);
+        map[[1, 2]] = b'@';
+        hash.update(2, 1, b'.', b'@');
+        assert_eq!(hash, MapHash::new(&map));
+        let status = Status::default();
+        let before = hash.with_status(&status);
+        hash.update(2, 1, b'@', b'.');
+        hash.update(3, 1, b'.', b'@');
+        assert_ne!(hash.with_status(&status), before);
+    }
+    #[test]
+    fn granularity() {
+        let config = ::GameConfig::default();
+        let runtime = config.build().unwrap();
+        let hashes: Vec<_> = [
+            HashGranularity::Known,
+            HashGranularity::MapAndStatus,
+            HashGranularity::Full,
+        ]
+        .iter()
+        .map(|&g| runtime.state_hash(g).unwrap())
+        .collect();
+        assert_ne!(hashes[0], hashes[1]);
+        assert_ne!(hashes[1], hashes[2]);
+        assert_eq!(hashes[2], full_hash(&runtime).unwrap());
+        // the known map doesn't change when the player moves in a lit room
+        let mut config = ::GameConfig::default();
+        config.seed = Some(1);
+        config.enemies.enemies.clear();
+        let mut runtime = config.build().unwrap();
+        runtime.keymap = ::input::KeyMap::ai();
+        let hash = |runtime: &RunTime| {
+            let known = runtime.state_hash(HashGranularity::Known).unwrap();
+            (
+                known,
+                runtime.state_hash(HashGranularity::MapAndStatus).unwrap(),
+            )
+        };
+        let moved_in_room = "hjklhjkl".chars().any(|c| {
+            let before = hash(&runtime);
+            runtime.react_to_key(::input::Key::Char(c)).unwrap();
+            let after = hash(&runtime);
+            before.0 == after.0 && before.1 != after.1
+        });
+        assert!(moved_in_room);
+        assert_eq!(
+            "known".parse::<HashGranularity>().unwrap(),
+            HashGranularity::Known
+        );
+        assert!("all".parse::<HashGranularity>().is_err());
+    }
+    #[test]
+    fn incremental_known_hash() {
+        let keys = "hjklyubnhhjjkkllss>";
+        for seed in 0..10 {
+            let mut config = ::GameConfig::default();
+            config.seed = Some(seed);
+            config.enemies.enemies.clear();
+            let mut runtime = config.build().unwrap();
+            runtime.keymap = ::input::KeyMap::ai();
+            let mut rng = ::rng::RngHandle::from_seed(seed);
+            for _ in 0..200 {
+                let c = keys.as_bytes()[rng.range(0..keys.len())] as char;
+                runtime.react_to_key(::input::Key::Char(c)).unwrap();
+                let floor = &runtime.dungeon.as_rogue().unwrap().current_floor;
+                assert_eq!(floor.known_hash(), drawn_map_hash(&runtime).unwrap());
+            }
+        }
+    }
+    #[test]
+    fn full_hash_covers_enemies() {
+        let mut config = ::GameConfig::default();
+        config.seed = Some(1);
+        let mut runtime = config.build().unwrap();
+        let mut hash = full_hash(&runtime).unwrap();
+        let mut assert_changed = |runtime: &RunTime| {
+            let new = full_hash(runtime).unwrap();
+            assert_ne!(hash, new);
+            hash = new;
+        };
+        let (_, enemy) = runtime.enemies.iter().next().unwrap();
+        enemy.get_damage(::character::HitPoint(1));
+        assert_changed(&runtime);
+        runtime.enemies.rng().range(0..10);
+        assert_changed(&runtime);
+    }
+}
@@ -53,6 +53,35 @@ impl Default for KeyMap {
             (Key::Char('Y'), InputCode::Act(Action::MoveUntil(LeftUp))),
             (Key::Char('N'), InputCode::Act(Action::MoveUntil(RightDown))),
             (Key::Char('B'), InputCode::Act(Action::MoveUntil(LeftDown))),
+            (Key::Alt('l'), InputCode::Act(Action::Run(Right))),
+            (Key::Alt('k'), InputCode::Act(Action::Run(Up))),
+            (Key::Alt('j'), InputCode::Act(Action::Run(Down))),
+            (Key::Alt('h'), InputCode::Act(Action::Run(Left))),
+            (Key::Alt('u'), InputCode::Act(Action::Run(RightUp))),
+            (Key::Alt('y'), InputCode::Act(Action::Run(LeftUp))),
+            (Key::Alt('n'), InputCode::Act(Action::Run(RightDown))),
+            (Key::Alt('b'), InputCode::Act(Action::Run(LeftDown))),
+            (Key::Ctrl('l'), InputCode::Act(Action::RunCautiously(Right))),
+            (Key::Ctrl('k'), InputCode::Act(Action::RunCautiously(Up))),
+            // terminals send Ctrl + j as '\n'
+            (Key::Char('\n'), InputCode::Act(Action::RunCautiously(Down))),
+            (Key::Ctrl('h'), InputCode::Act(Action::RunCautiously(Left))),
+            (
+                Key::Ctrl('u'),
+                InputCode::Act(Action::RunCautiously(RightUp)),
+            ),
+            (
+                Key::Ctrl('y'),
+                InputCode::Act(Action::RunCautiously(LeftUp)),
+            ),
+            (
+                Key::Ctrl('n'),
+                InputCode::Act(Action::RunCautiously(RightDown)),
+            ),
+            (
+                Key::Ctrl('b'),
+                InputCode::Act(Action::RunCautiously(LeftDown)),
+            ),
             (Key::Char('s'), InputCode::Act(Action::Search)),
             (Key::Char('.'), InputCode::Act(Action::NoOp)),
             (Key::Char('>'), InputCode::Act(Action::DownStair)),
@@ -72,6 +101,11 @@ impl Default for KeyMap {
 }
 
 impl KeyMap {
+    /// keys of `KeyMap::ai`, in the order of action masks
+    pub const AI_KEYS: [char; 19] = [
+        '.', 'h', 'j', 'k', 'l', 'n', 'b', 'u', 'y', '>', 's', 'H', 'J', 'K', 'L', 'N', 'B', 'U',
+        'Y',
+    ];
     pub fn ai() -> Self {
         use self::Direction::*;
         let map = vec![
@@ -135,8 +169,9 @@ impl<'de> Visitor<'de> for KeyMapVisitor {
         M: MapAccess<'de>,
     {
         let mut inner = HashMap::new();
-        while let Some((k, v)) = access.next_entry()? {
-            let key = match Key::from_str(k) {
+        // keys like "\n" are escaped and can't be borrowed
+        while let Some((k, v)) = access.next_entry::<String, InputCode>()? {
+            let key = match Key::from_str(&k) {
                 Some(k) => k,
                 None => panic!("invalid key name {} in KeyMap", k),
             };
@@ -279,6 +314,27 @@ mod keymap_test {
         assert_eq!(j, Key::Char('j'));
     }
     #[test]
+    fn ai_keys() {
+        let keymap = KeyMap::ai();
+        assert_eq!(KeyMap::AI_KEYS.len(), keymap.inner.len());
+        for &c in KeyMap::AI_KEYS.iter() {
+            assert!(keymap.get(Key::Char(c)).is_some(), "{}", c);
+        }
+    }
+    #[test]
+    fn run_keys() {
+        use self::Direction::*;
+        let keymap = KeyMap::default();
+        for &(c, d) in &[('h', Left), ('j', Down), ('k', Up), ('l', Right)] {
+            let run = InputCode::Act(Action::Run(d));
+            assert_eq!(keymap.get(Key::Alt(c)), Some(run));
+        }
+        let cautious = |d| Some(InputCode::Act(Action::RunCautiously(d)));
+        assert_eq!(keymap.get(Key::Ctrl('h')), cautious(Left));
+        assert_eq!(keymap.get(Key::Char('\n')), cautious(Down));
+        assert_eq!(keymap.get(Key::Ctrl('j')), None);
+    }
+    #[test]
     fn serde() {
         let keymap = KeyMap::default();
         let ser = json::to_string(&keymap).unwrap();
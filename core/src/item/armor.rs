@@ -4,6 +4,7 @@ use character::Defense;
 use rng::{Parcent, RngHandle};
 use smallstr::SmallStr;
 use std::fmt;
+use validation::Validator;
 
 /// Armor configuration
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -49,6 +50,27 @@ fn is_default_powerup_rate(u: &Parcent) -> bool {
 }
 
 impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.check("armors", !self.armors.is_empty(), || {
+            "needs at least one armor".to_owned()
+        });
+        v.field("armors", |v| {
+            for (i, preset) in self.armors.iter().enumerate() {
+                match *preset {
+                    Preset::Builtin(id) if id >= BUILTIN_ARMORS.len() => v.index(i, |v| {
+                        v.error(format!(
+                            "no builtin armor {}, there are {}",
+                            id,
+                            BUILTIN_ARMORS.len()
+                        ))
+                    }),
+                    _ => {}
+                }
+            }
+        });
+        v.parcent("cursed_rate", self.cursed_rate.0);
+        v.parcent("powerup_rate", self.powerup_rate.0);
+    }
     pub(super) fn build(self) -> Handler<ArmorStatus> {
         let Config {
             cursed_rate,
@@ -1,5 +1,6 @@
 use super::ItemNum;
 use rng::RngHandle;
+use validation::Validator;
 
 /// Gold configuration
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -15,12 +16,21 @@ pub struct Config {
 }
 
 impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.positive("rate_inv", self.rate_inv);
+        v.check("base", self.base + self.per_level > 0, || {
+            "base and per_level can't be both zero".to_owned()
+        });
+    }
     pub(super) fn gen(&self, rng: &mut RngHandle, level: u32) -> Option<ItemNum> {
         if !rng.does_happen(self.rate_inv) {
             return None;
         }
+        Some(self.gen_surely(rng, level))
+    }
+    pub(super) fn gen_surely(&self, rng: &mut RngHandle, level: u32) -> ItemNum {
         let num = rng.range(0..self.base + self.per_level * level) + self.minimum;
-        Some(ItemNum(num))
+        ItemNum(num)
     }
 }
 
@@ -1,6 +1,8 @@
 //! utility for managing character's items
 use super::{Item, ItemToken};
+use error::GameResult;
 use fenwick::FenwickSet;
+use hash::Fnv64;
 use std::collections::BTreeMap;
 
 #[derive(Clone, Debug)]
@@ -42,6 +44,13 @@ impl ItemBox {
     pub fn items(&self) -> impl Iterator<Item = &Item> {
         self.tokens().map(|t| t.get())
     }
+    pub(crate) fn hash_state(&self, hasher: &mut Fnv64) -> GameResult<()> {
+        hasher.write_u32(self.items.len() as u32);
+        self.items.iter().try_for_each(|(&i, token)| {
+            hasher.write_u32(i as u32);
+            hasher.write_json(token.get())
+        })
+    }
     pub fn find_by(&self, mut query: impl FnMut(&Item) -> bool) -> Option<&ItemToken> {
         self.items
             .iter()
@@ -1,7 +1,7 @@
 //! module for item
 pub mod armor;
 pub mod food;
-mod gold;
+pub mod gold;
 mod handler;
 pub mod itembox;
 pub mod weapon;
@@ -14,6 +14,7 @@ pub use self::itembox::ItemBox;
 use self::weapon::{Weapon, WeaponStatus};
 use character::{Dice, HitPoint, Level};
 use error::*;
+use hash::Fnv64;
 use rng::RngHandle;
 use smallstr::SmallStr;
 use std::cell::UnsafeCell;
@@ -22,13 +23,22 @@ use std::fmt;
 use std::ops::{Deref, DerefMut};
 use std::rc::{Rc, Weak};
 use tile::{Drawable, Tile};
+use validation::Validator;
 
 /// Item configuration
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Config {
-    armor: armor::Config,
-    gold: gold::Config,
-    weapon: weapon::Config,
+    pub armor: armor::Config,
+    pub gold: gold::Config,
+    pub weapon: weapon::Config,
+}
+
+impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.field("armor", |v| self.armor.validate(v));
+        v.field("gold", |v| self.gold.validate(v));
+        v.field("weapon", |v| self.weapon.validate(v));
+    }
 }
 
 /// item tag
@@ -45,6 +55,19 @@ pub enum ItemKind {
 }
 
 impl ItemKind {
+    /// name of the kind, ignoring the details(e.g. "weapon" for any weapon)
+    pub fn category(&self) -> &'static str {
+        match self {
+            ItemKind::Armor(_) => "armor",
+            ItemKind::Food(_) => "food",
+            ItemKind::Gold => "gold",
+            ItemKind::Potion => "potion",
+            ItemKind::Ring => "ring",
+            ItemKind::Scroll => "scroll",
+            ItemKind::Wand => "wand",
+            ItemKind::Weapon(_) => "weapon",
+        }
+    }
     /// construct item from ItemNum & default attribute setting
     pub fn numbered(self, num: ItemNum) -> Item {
         let attr = match self {
@@ -365,6 +388,10 @@ pub struct ItemHandler {
 }
 
 impl ItemHandler {
+    /// writes the state of the rng to `hasher`
+    pub(crate) fn hash_state(&self, hasher: &mut Fnv64) -> GameResult<()> {
+        hasher.write_json(&self.rng)
+    }
     /// generate new ItemHandler
     pub fn new(config_: Config, seed: u128) -> Self {
         let config = config_.clone();
@@ -397,6 +424,18 @@ impl ItemHandler {
         let num = self.config.gold.gen(&mut self.rng, level)?;
         Some(self.gen_item(ItemKind::Gold.numbered(num).many()))
     }
+    /// Sets up an item for treasure rooms
+    pub fn setup_treasure(&mut self, level: u32) -> ItemToken {
+        let item = match self.rng.range(0..3) {
+            0 if !self.weapon_handle.stats.is_empty() => self.weapon_handle.gen_item(&mut self.rng),
+            1 if !self.armor_handle.stats.is_empty() => self.armor_handle.gen_item(&mut self.rng),
+            _ => {
+                let num = self.config.gold.gen_surely(&mut self.rng, level);
+                ItemKind::Gold.numbered(num).many()
+            }
+        };
+        self.gen_item(item)
+    }
     /// Sets up player items
     pub fn init_player_items(&mut self, pack: &mut ItemBox, items: &[InitItem]) -> GameResult<()> {
         items.iter().try_for_each(|item| {
@@ -2,6 +2,7 @@ use super::handler::{Handler, ItemInner, ItemStat};
 use super::{InitItem, Item, ItemAttr, ItemKind, ItemNum};
 use crate::character::{Dice, HitPoint, Level};
 use crate::rng::{Parcent, RngHandle};
+use crate::validation::Validator;
 use crate::SmallStr;
 use std::fmt;
 use std::ops::Range;
@@ -31,6 +32,27 @@ impl Default for Config {
 }
 
 impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        v.check("weapons", !self.weapons.is_empty(), || {
+            "needs at least one weapon".to_owned()
+        });
+        v.field("weapons", |v| {
+            for (i, preset) in self.weapons.iter().enumerate() {
+                match *preset {
+                    Preset::Builtin(id) if id >= BUILTIN_WEAPONS.len() => v.index(i, |v| {
+                        v.error(format!(
+                            "no builtin weapon {}, there are {}",
+                            id,
+                            BUILTIN_WEAPONS.len()
+                        ))
+                    }),
+                    _ => {}
+                }
+            }
+        });
+        v.parcent("cursed_rate", self.cursed_rate.0);
+        v.parcent("powerup_rate", self.powerup_rate.0);
+    }
     pub(super) fn build(self) -> Handler<WeaponStatus> {
         let Config {
             weapons,
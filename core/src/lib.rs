@@ -18,6 +18,7 @@ extern crate rect_iter;
 extern crate regex;
 #[macro_use]
 extern crate serde;
+extern crate serde_ignored;
 extern crate serde_json;
 extern crate smallvec;
 #[cfg(unix)]
@@ -30,26 +31,37 @@ mod actions;
 use std::fs::File;
 use std::io::{self, Read};
 pub mod character;
+pub mod describe;
 pub mod dungeon;
+pub mod env;
 pub mod error;
+pub mod events;
 mod fenwick;
+pub mod hash;
 pub mod input;
 pub mod item;
+pub mod observation;
+pub mod replay;
+pub mod reward;
 mod rng;
 mod smallstr;
 pub mod symbol;
 pub mod tile;
+pub mod trajectory;
 pub mod ui;
+pub mod validation;
 
-use character::{enemies, player, EnemyHandler, Player};
-use dungeon::{Direction, Dungeon, DungeonStyle, Positioned, X, Y};
+use character::{enemies, player, EnemyHandler, HitPoint, Player};
+use dungeon::{Direction, Dungeon, DungeonStyle, FloorDump, Positioned, X, Y};
 use error::*;
 use input::{InputCode, Key, KeyMap};
 use item::{ItemHandler, ItemKind};
 use ndarray::Array2;
 pub use smallstr::SmallStr;
+use symbol::SymbolTable;
 use tile::{Drawable, Tile};
-use ui::{MordalKind, MordalMsg, UiState};
+use ui::{MordalKind, MordalMsg, UiState, Viewport};
+use validation::{ConfigError, Validator};
 
 /// Game configuration
 /// it's inteded to construct from json
@@ -63,6 +75,17 @@ pub struct GameConfig {
     #[serde(default = "default_screen_height")]
     #[serde(skip_serializing_if = "is_default_height")]
     pub height: i32,
+    /// dungeon width
+    /// if None, the dungeon has the same width as the screen
+    /// otherwise the screen shows a part of the dungeon around the player
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub dungeon_width: Option<i32>,
+    /// dungeon height
+    /// if None, the dungeon has the same height as the screen
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub dungeon_height: Option<i32>,
     /// seed of random number generator
     /// if None, we use random value chosen by `thread_rng().gen()`
     #[serde(default)]
@@ -93,6 +116,14 @@ pub struct GameConfig {
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
     pub enemies: enemies::Config,
+    /// reward configuration
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub reward: reward::Config,
+    /// symbol table configuration, which groups tiles into channels of images
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub symbols: symbol::Config,
     /// hide dungeon or not
     /// this setting is only for debugging and don't use it when you play game
     #[serde(default = "default_hide_dungeon")]
@@ -133,6 +164,8 @@ impl Default for GameConfig {
         GameConfig {
             width: DEFAULT_WIDTH,
             height: DEFAULT_HEIGHT,
+            dungeon_width: None,
+            dungeon_height: None,
             seed: Default::default(),
             seed_range: Default::default(),
             dungeon: DungeonStyle::default(),
@@ -140,6 +173,8 @@ impl Default for GameConfig {
             keymap: KeyMap::default(),
             player: player::Config::default(),
             enemies: enemies::Config::default(),
+            reward: reward::Config::default(),
+            symbols: symbol::Config::default(),
             hide_dungeon: default_hide_dungeon(),
         }
     }
@@ -153,21 +188,107 @@ pub const MAX_HEIGHT: i32 = DEFAULT_HEIGHT * 2;
 pub const MIN_WIDTH: i32 = 32;
 pub const MIN_HEIGHT: i32 = 16;
 
+pub const MAX_DUNGEON_WIDTH: i32 = DEFAULT_WIDTH * 4;
+pub const MAX_DUNGEON_HEIGHT: i32 = DEFAULT_HEIGHT * 4;
+
 impl GameConfig {
     /// construct Game configuration from json string
     pub fn from_json(json: &str) -> GameResult<Self> {
         serde_json::from_str(json).into_chained(|| "GameConfig::from_json")
     }
+    /// same as `from_json`, but unknown fields and problems found by `validate` are errors
+    pub fn from_json_strict(json: &str) -> GameResult<Self> {
+        const ERR_STR: &str = "GameConfig::from_json_strict";
+        let mut errors = vec![];
+        let config: GameConfig =
+            serde_ignored::deserialize(&mut serde_json::Deserializer::from_str(json), |path| {
+                errors.push(ConfigError {
+                    path: validation::ignored_path(&path),
+                    message: "unknown field".to_owned(),
+                })
+            })
+            .into_chained(|| ERR_STR)?;
+        // internally tagged or flattened structs ignore unknown fields without telling
+        // serde_ignored, so we compare them with the parsed ones
+        let value: serde_json::Value = serde_json::from_str(json).into_chained(|| ERR_STR)?;
+        if let (Some(dungeon), DungeonStyle::Rogue(rogue)) = (value.get("dungeon"), &config.dungeon)
+        {
+            let mut known = serde_json::to_value(rogue).into_chained(|| ERR_STR)?;
+            known["style"] = serde_json::Value::Null;
+            validation::unknown_keys(dungeon, &known, "dungeon", &mut errors);
+        }
+        if let Some(player) = value.get("player") {
+            let known = serde_json::to_value(&config.player).into_chained(|| ERR_STR)?;
+            validation::unknown_keys(player, &known, "player", &mut errors);
+        }
+        errors.extend(config.validate());
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(invalid_config(&errors))
+        }
+    }
+    /// checks the whole config and returns all problems found
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut v = Validator::new();
+        let (w, h) = (self.width, self.height);
+        v.check("width", MIN_WIDTH <= w, || {
+            format!("screen width {} is narrower than {}", w, MIN_WIDTH)
+        });
+        v.check("width", w <= MAX_WIDTH, || {
+            format!("screen width {} is wider than {}", w, MAX_WIDTH)
+        });
+        v.check("height", MIN_HEIGHT <= h, || {
+            format!("screen height {} is shorter than {}", h, MIN_HEIGHT)
+        });
+        v.check("height", h <= MAX_HEIGHT, || {
+            format!("screen height {} is taller than {}", h, MAX_HEIGHT)
+        });
+        let (dw, dh) = self.dungeon_size();
+        v.check("dungeon_width", w <= dw, || {
+            format!("dungeon width {} is narrower than the screen", dw)
+        });
+        v.check("dungeon_width", dw <= MAX_DUNGEON_WIDTH, || {
+            format!("dungeon width {} is wider than {}", dw, MAX_DUNGEON_WIDTH)
+        });
+        v.check("dungeon_height", h <= dh, || {
+            format!("dungeon height {} is shorter than the screen", dh)
+        });
+        v.check("dungeon_height", dh <= MAX_DUNGEON_HEIGHT, || {
+            format!(
+                "dungeon height {} is taller than {}",
+                dh, MAX_DUNGEON_HEIGHT
+            )
+        });
+        if let Some([start, end]) = self.seed_range {
+            v.check("seed_range", start < end, || {
+                "the range is empty".to_owned()
+            });
+        }
+        v.field("dungeon", |v| self.dungeon.validate(v, X(dw), Y(dh)));
+        v.field("item", |v| self.item.validate(v));
+        v.field("player", |v| self.player.validate(v));
+        v.field("enemies", |v| self.enemies.validate(v));
+        v.field("symbols", |v| self.symbols.validate(v));
+        v.finish()
+    }
     pub fn to_json(&self) -> GameResult<String> {
         serde_json::to_string_pretty(self).into_chained(|| "GameConfig::to_json")
     }
-    pub fn symbol_max(&self) -> Option<symbol::Symbol> {
-        match self.enemies.tile_max() {
-            Some(t) => symbol::Symbol::from_tile(t.into()),
-            None => symbol::Symbol::from_tile(b'A'.into()).map(|s| s.decrement()),
-        }
+    /// the symbol table used in images
+    pub fn symbol_table(&self) -> GameResult<SymbolTable> {
+        self.symbols
+            .table(self.enemies.tile_max().map(Tile::from))
+            .chain_err(|| "GameConfig::symbol_table")
     }
-    fn to_global(&self) -> GameResult<GlobalConfig> {
+    /// returns the size of dungeon, which is never smaller than the screen
+    pub fn dungeon_size(&self) -> (i32, i32) {
+        (
+            self.dungeon_width.unwrap_or(self.width),
+            self.dungeon_height.unwrap_or(self.height),
+        )
+    }
+    fn to_global(&self) -> GlobalConfig {
         let seed = if let Some(s) = self.seed {
             s
         } else {
@@ -178,39 +299,32 @@ impl GameConfig {
             }
         };
         let (w, h) = (self.width, self.height);
-        if w < MIN_WIDTH {
-            return Err(ErrorId::InvalidSetting.into_with(|| "screen width is too narrow"));
-        }
-        if w > MAX_WIDTH {
-            return Err(ErrorId::InvalidSetting.into_with(|| "screen width is too wide"));
-        }
-        if h < MIN_HEIGHT {
-            return Err(ErrorId::InvalidSetting.into_with(|| "screen height is too narrow"));
-        }
-        if h > MAX_HEIGHT {
-            return Err(ErrorId::InvalidSetting.into_with(|| "screen height is too wide"));
-        }
-        Ok(GlobalConfig {
-            width: w.into(),
-            height: h.into(),
+        let (dw, dh) = self.dungeon_size();
+        GlobalConfig {
+            width: dw.into(),
+            height: dh.into(),
+            screen_width: w.into(),
+            screen_height: h.into(),
             seed,
             hide_dungeon: self.hide_dungeon,
-        })
+        }
     }
     /// get runtime from config
     pub fn build(self) -> GameResult<RunTime> {
         const ERR_STR: &str = "GameConfig::build";
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Err(invalid_config(&errors));
+        }
         let game_info = GameInfo::new();
-        let config = self.to_global().chain_err(|| ERR_STR)?;
+        let config = self.to_global();
         debug!("Building dungeon with seed {}", config.seed);
-        // TODO: invalid checking
         let mut item = ItemHandler::new(self.item.clone(), config.seed);
         let mut enemies = self.enemies.build(config.seed);
         let mut dungeon = self
             .dungeon
             .build(&config, &mut item, &mut enemies, &game_info, config.seed)
             .chain_err(|| ERR_STR)?;
-        // TODO: invalid checking
         let mut player = self.player.build();
         player.init_items(&mut item).chain_err(|| ERR_STR)?;
         actions::new_level(
@@ -236,6 +350,11 @@ impl GameConfig {
     }
 }
 
+fn invalid_config(errors: &[ConfigError]) -> failure::Error {
+    let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    ErrorId::InvalidSetting.into_with(|| format!("Invalid config: {}", errors.join(", ")))
+}
+
 /// API entry point of rogue core
 pub struct RunTime {
     game_info: GameInfo,
@@ -337,6 +456,24 @@ impl RunTime {
             None => Err(ErrorId::InvalidInput(key).into()),
         }
     }
+    /// returns if the input can change the game state
+    /// movements to walls, `>` out of stairs and no-op are illegal,
+    /// and all actions are illegal when the dungeon isn't shown
+    pub fn is_legal(&self, input: InputCode) -> bool {
+        match (&self.ui, input) {
+            (UiState::Dungeon, InputCode::Act(act))
+            | (UiState::Dungeon, InputCode::Both { act, .. }) => {
+                actions::is_legal(act, &*self.dungeon, &self.player)
+            }
+            _ => false,
+        }
+    }
+    /// legality of each key, where keys not in the keymap are illegal
+    pub fn action_mask(&self, keys: impl IntoIterator<Item = Key>) -> Vec<bool> {
+        keys.into_iter()
+            .map(|key| matches!(self.keymap.get(key), Some(i) if self.is_legal(i)))
+            .collect()
+    }
     pub fn is_cancel(&self, key: Key) -> GameResult<bool> {
         match self.keymap.get(key) {
             Some(i) => match i {
@@ -352,8 +489,27 @@ impl RunTime {
         }
     }
     pub fn screen_size(&self) -> (X, Y) {
+        (self.config.screen_width, self.config.screen_height)
+    }
+    pub fn dungeon_size(&self) -> (X, Y) {
         (self.config.width, self.config.height)
     }
+    /// the part of the dungeon shown on the screen, centered on the player
+    pub fn viewport(&self) -> Viewport {
+        let player = self.dungeon.path_to_cd(&self.player.pos);
+        Viewport::centered(player, self.screen_size(), self.dungeon_size())
+    }
+    /// take draw function F and draw the viewport with it, in screen coordinates
+    pub fn draw_viewport(
+        &self,
+        mut drawer: impl FnMut(Positioned<Tile>) -> GameResult<()>,
+    ) -> GameResult<()> {
+        let viewport = self.viewport();
+        self.draw_screen(|Positioned(cd, tile)| match viewport.to_screen(cd) {
+            Some(cd) => drawer(Positioned(cd, tile)),
+            None => Ok(()),
+        })
+    }
     pub fn player_status(&self) -> player::Status {
         let mut status = player::Status::default();
         self.player.fill_status(&mut status);
@@ -366,6 +522,18 @@ impl RunTime {
         status.dungeon_level = self.dungeon.level();
         status
     }
+    /// game turns passed since the game started
+    pub fn turns(&self) -> u32 {
+        self.player.turns()
+    }
+    /// the seed this game is built with
+    pub fn seed(&self) -> u128 {
+        self.config.seed
+    }
+    /// stable fingerprint of the game state, see `HashGranularity`
+    pub fn state_hash(&self, granularity: hash::HashGranularity) -> GameResult<u64> {
+        hash::state_hash(self, granularity)
+    }
     pub fn saved_inputs(&self) -> &[InputCode] {
         &self.saved_inputs
     }
@@ -376,6 +544,19 @@ impl RunTime {
     pub fn history(&self, player_stat: &player::Status) -> Option<Array2<bool>> {
         self.dungeon.get_history(&player_stat)
     }
+    /// dumps the current floor, including hidden cells, items and enemies
+    pub fn export_floor(&self) -> FloorDump {
+        self.dungeon.export_floor(&self.enemies, &self.player.pos)
+    }
+    /// enemies, items, the stair and the room the player can see, in dungeon coordinates
+    /// use `Entities::in_viewport` to get them on the screen
+    pub fn entities(&self) -> observation::Entities {
+        observation::Entities::new(self)
+    }
+    /// what the player can see with `messages` of the last turn, to describe in English
+    pub fn scene(&self, messages: Vec<GameMsg>) -> GameResult<describe::Scene> {
+        describe::Scene::new(self, messages)
+    }
     pub fn itembox(&self) -> &item::ItemBox {
         debug!("itembox {:?}", self.player.itembox);
         &self.player.itembox
@@ -399,13 +580,18 @@ pub enum Reaction {
     Notify(GameMsg),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum GameMsg {
     CantMove(Direction),
     CantGetItem(ItemKind),
-    GotItem { kind: ItemKind, num: u32 },
-    HitTo(SmallStr),
-    HitFrom(SmallStr),
+    GotItem {
+        kind: ItemKind,
+        num: u32,
+    },
+    /// the player hit an enemy, with the damage
+    HitTo(SmallStr, HitPoint),
+    /// an enemy hit the player, with the damage
+    HitFrom(SmallStr, HitPoint),
     MissTo(SmallStr),
     MissFrom(SmallStr),
     Killed(SmallStr),
@@ -417,8 +603,12 @@ pub enum GameMsg {
 /// Global configuration
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GlobalConfig {
+    /// dungeon width
     pub width: X,
+    /// dungeon height
     pub height: Y,
+    pub screen_width: X,
+    pub screen_height: Y,
     pub seed: u128,
     pub hide_dungeon: bool,
 }
@@ -467,4 +657,43 @@ mod config_test {
         let config: GameConfig = serde_json::from_str("{}").unwrap();
         assert_eq!(config, GameConfig::default());
     }
+    #[test]
+    fn large_dungeon() {
+        let config = GameConfig::from_json(
+            r#"{
+    "dungeon_width": 160,
+    "dungeon_height": 48,
+    "seed": 1,
+    "hide_dungeon": false,
+    "dungeon": { "style": "rogue", "room_num_x": 6, "room_num_y": 4 }
+}"#,
+        )
+        .unwrap();
+        let runtime = config.build().unwrap();
+        assert_eq!(runtime.screen_size(), (X(80), Y(24)));
+        assert_eq!(runtime.dungeon_size(), (X(160), Y(48)));
+        let mut players = vec![];
+        runtime
+            .draw_viewport(|Positioned(cd, tile)| {
+                assert!(0 <= cd.x.0 && cd.x.0 < 80 && 1 <= cd.y.0 && cd.y.0 < 23);
+                if tile.to_byte() == b'@' {
+                    players.push(cd);
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(players.len(), 1);
+        assert_eq!(
+            runtime.viewport().to_dungeon(players[0]),
+            runtime.dungeon.path_to_cd(&runtime.player.pos)
+        );
+    }
+    #[test]
+    fn invalid_dungeon_size() {
+        let mut config = GameConfig::default();
+        config.dungeon_width = Some(40);
+        assert!(config.clone().build().is_err());
+        config.dungeon_width = Some(MAX_DUNGEON_WIDTH + 1);
+        assert!(config.build().is_err());
+    }
 }
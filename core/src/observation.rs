@@ -0,0 +1,744 @@
+//! structured observations of the game for agents
+use character::player::Status;
+use dungeon::Coord;
+use error::*;
+use item::ItemKind;
+use ndarray::{s, Array2, Array3, ArrayView3, ArrayViewMut2, ArrayViewMut3, Axis, Zip};
+use num_traits::Zero;
+use rect_iter::Get2D;
+use std::str::FromStr;
+use symbol::{self, InvalidTileError, SymbolTable};
+use tile::{Drawable, Tile};
+use ui::Viewport;
+use RunTime;
+
+/// an enemy the player can see
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnemyInfo {
+    pub cd: Coord,
+    pub tile: Tile,
+    pub name: String,
+    /// current hit point / max hit point, only after the player damaged the enemy
+    pub hp_rate: Option<f32>,
+}
+
+/// an item the player can see
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemInfo {
+    pub cd: Coord,
+    pub tile: Tile,
+    pub kind: ItemKind,
+    pub num: u32,
+}
+
+/// entities the player can see, in dungeon coordinates unless converted by `in_viewport`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Entities {
+    /// sorted by distance from the player
+    pub enemies: Vec<EnemyInfo>,
+    /// sorted by distance from the player
+    pub items: Vec<ItemInfo>,
+    pub stair: Option<Coord>,
+    /// [x_start, y_start, x_end, y_end) of the room where the player is
+    pub room: Option<[i32; 4]>,
+}
+
+impl Entities {
+    /// number of features per entity in `to_array`
+    pub const FEATURES: usize = 7;
+    pub(crate) fn new(runtime: &RunTime) -> Self {
+        let dungeon = &runtime.dungeon;
+        let player = dungeon.path_to_cd(&runtime.player.pos);
+        let mut res = Entities::default();
+        for path in dungeon.draw_ranges() {
+            let cd = dungeon.path_to_cd(&path);
+            if let Some(item) = dungeon.get_item(&path) {
+                let item = item.get();
+                res.items.push(ItemInfo {
+                    cd,
+                    tile: item.tile(),
+                    kind: item.kind.clone(),
+                    num: item.how_many.0,
+                });
+            }
+            if let Some(enemy) = runtime.enemies.get_enemy(&path) {
+                if dungeon.draw_enemy(&runtime.player.pos, &path) {
+                    res.enemies.push(EnemyInfo {
+                        cd,
+                        tile: enemy.tile(),
+                        name: enemy.name().to_string(),
+                        hp_rate: enemy.hp_rate(),
+                    });
+                }
+            }
+        }
+        res.enemies
+            .sort_by_key(|e| (e.cd.euc_dist_squared(player), e.cd.y, e.cd.x));
+        res.items
+            .sort_by_key(|i| (i.cd.euc_dist_squared(player), i.cd.y, i.cd.x));
+        res.stair = dungeon
+            .seen_stairs()
+            .first()
+            .map(|path| dungeon.path_to_cd(path));
+        res.room = dungeon.room_range(&runtime.player.pos).map(|range| {
+            let (x, y) = (range.get_x(), range.get_y());
+            [x.start, y.start, x.end, y.end]
+        });
+        res
+    }
+    /// converts coordinates into screen coordinates of `viewport`, dropping entities out of it
+    /// the room is clipped by the viewport
+    pub fn in_viewport(&self, viewport: &Viewport) -> Self {
+        let enemies = self
+            .enemies
+            .iter()
+            .filter_map(|enemy| {
+                let cd = viewport.to_screen(enemy.cd)?;
+                Some(EnemyInfo {
+                    cd,
+                    ..enemy.clone()
+                })
+            })
+            .collect();
+        let items = self
+            .items
+            .iter()
+            .filter_map(|item| {
+                let cd = viewport.to_screen(item.cd)?;
+                Some(ItemInfo { cd, ..item.clone() })
+            })
+            .collect();
+        let room = self.room.and_then(|[xs, ys, xe, ye]| {
+            let Coord { x, y } = viewport.offset;
+            let (w, h) = (viewport.width.0, viewport.height.0);
+            let (xs, xe) = ((xs - x.0).max(0), (xe - x.0).min(w));
+            let (ys, ye) = ((ys - y.0).max(1), (ye - y.0).min(h - 1));
+            if xs < xe && ys < ye {
+                Some([xs, ys, xe, ye])
+            } else {
+                None
+            }
+        });
+        Entities {
+            enemies,
+            items,
+            stair: self.stair.and_then(|cd| viewport.to_screen(cd)),
+            room,
+        }
+    }
+    /// converts entities into a fixed size array of
+    /// `[max_enemies + max_items + 2, FEATURES]`
+    /// rows are enemies, items, the stair and the room in this order, and each row is
+    /// `[exists, x, y, tile, hp_rate, width, height]`, where `hp_rate` is -1 if unknown
+    /// enemies and items far from the player are dropped if they overflow
+    pub fn to_array(&self, max_enemies: usize, max_items: usize) -> Array2<f32> {
+        let mut array = Array2::zeros([max_enemies + max_items + 2, Self::FEATURES]);
+        {
+            let mut fill = |row: usize, cd: Coord, tile: Option<Tile>, rest: [f32; 3]| {
+                let tile = tile.map_or(0.0, |t| f32::from(t.to_byte()));
+                let values = [1.0, cd.x.0 as f32, cd.y.0 as f32, tile];
+                for (i, &v) in values.iter().chain(rest.iter()).enumerate() {
+                    array[[row, i]] = v;
+                }
+            };
+            for (i, enemy) in self.enemies.iter().take(max_enemies).enumerate() {
+                let hp_rate = enemy.hp_rate.unwrap_or(-1.0);
+                fill(i, enemy.cd, Some(enemy.tile), [hp_rate, 0.0, 0.0]);
+            }
+            for (i, item) in self.items.iter().take(max_items).enumerate() {
+                fill(max_enemies + i, item.cd, Some(item.tile), [0.0; 3]);
+            }
+            let row = max_enemies + max_items;
+            if let Some(stair) = self.stair {
+                fill(row, stair, Some(b'%'.into()), [0.0; 3]);
+            }
+            if let Some([xs, ys, xe, ye]) = self.room {
+                let (w, h) = ((xe - xs) as f32, (ye - ys) as f32);
+                fill(row + 1, Coord::new(xs, ys), None, [0.0, w, h]);
+            }
+        }
+        array
+    }
+}
+
+/// flags to select status values used in observations
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusFlag(pub u32);
+
+#[rustfmt::skip]
+impl StatusFlag {
+    pub const DUNGEON_LEVEL: u32 = 0b000_000_001;
+    pub const HP_CURRENT: u32    = 0b000_000_010;
+    pub const HP_MAX: u32        = 0b000_000_100;
+    pub const STR_CURRENT: u32   = 0b000_001_000;
+    pub const STR_MAX: u32       = 0b000_010_000;
+    pub const DEFENSE: u32       = 0b000_100_000;
+    pub const PLAYER_LEVEL: u32  = 0b001_000_000;
+    pub const EXP: u32           = 0b010_000_000;
+    pub const HUNGER: u32        = 0b100_000_000;
+}
+
+impl From<Option<u32>> for StatusFlag {
+    fn from(u: Option<u32>) -> Self {
+        StatusFlag(u.unwrap_or(0))
+    }
+}
+
+impl StatusFlag {
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    pub fn to_vector(self, status: &Status) -> Vec<i32> {
+        let values = [
+            (Self::DUNGEON_LEVEL, status.dungeon_level as i32),
+            (Self::HP_CURRENT, status.hp.current.0 as i32),
+            (Self::HP_MAX, status.hp.max.0 as i32),
+            (Self::STR_CURRENT, status.strength.current.0 as i32),
+            (Self::STR_MAX, status.strength.max.0 as i32),
+            (Self::DEFENSE, status.defense.0),
+            (Self::PLAYER_LEVEL, status.player_level as i32),
+            (Self::EXP, status.exp.0 as i32),
+            (Self::HUNGER, status.hunger_level.to_u32() as i32),
+        ];
+        values
+            .iter()
+            .filter(|&&(flag, _)| self.0 & flag != 0)
+            .map(|&(_, value)| value)
+            .collect()
+    }
+    /// fills channels from `start` with selected status values and returns the next channel
+    pub fn copy_status(
+        self,
+        status: &Status,
+        start: usize,
+        array: &mut ArrayViewMut3<f32>,
+    ) -> usize {
+        let mut offset = start;
+        for value in self.to_vector(status) {
+            array.index_axis_mut(Axis(0), offset).fill(value as f32);
+            offset += 1;
+        }
+        offset
+    }
+}
+
+/// element type and layout of images
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// `f32` channels
+    #[default]
+    Float,
+    /// `u8` map of symbol indices + 1(0 for cells out of the map), a channel per scale instead
+    /// of symbol channels
+    Index,
+    /// `u8` one-hot channels with 8 cells packed in a byte along x, in the same bit order as
+    /// `numpy.packbits`
+    Packed,
+}
+
+impl Encoding {
+    pub fn is_float(self) -> bool {
+        self == Encoding::Float
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = ::failure::Error;
+    fn from_str(s: &str) -> GameResult<Self> {
+        match s {
+            "float" => Ok(Encoding::Float),
+            "index" => Ok(Encoding::Index),
+            "packed" => Ok(Encoding::Packed),
+            _ => Err(ErrorId::InvalidConversion.into_with(|| {
+                format!(
+                    "Unknown encoding {}, expected 'float', 'index' or 'packed'",
+                    s
+                )
+            })),
+        }
+    }
+}
+
+/// how to encode a map into an image of `[channel, y, x]`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageSetting {
+    /// 1 gray scale channel instead of symbol channels, only for `Float`
+    pub gray: bool,
+    pub status: StatusFlag,
+    pub includes_hist: bool,
+    /// (radius_x, radius_y) of the crop around the player, or None for the whole map
+    pub crop: Option<(usize, usize)>,
+    /// appends the downsampled whole map to the crop
+    pub multiscale: bool,
+    pub encoding: Encoding,
+    /// leaves status out of images, to be used as `StatusFlag::to_vector`
+    /// integer encodings always do
+    pub separate_status: bool,
+}
+
+impl ImageSetting {
+    /// setting for the whole map
+    pub fn new(gray: bool, status: StatusFlag, includes_hist: bool) -> Self {
+        ImageSetting {
+            gray,
+            status,
+            includes_hist,
+            crop: None,
+            multiscale: false,
+            encoding: Encoding::Float,
+            separate_status: false,
+        }
+    }
+    fn dungeon_channels(&self, symbols: &SymbolTable) -> usize {
+        match self.encoding {
+            Encoding::Float if self.gray => 1,
+            Encoding::Index => 1,
+            _ => symbols.len(),
+        }
+    }
+    /// whether status values are broadcasted into channels
+    fn status_planes(&self) -> bool {
+        self.encoding.is_float() && !self.separate_status
+    }
+    fn scales(&self) -> usize {
+        if self.crop.is_some() && self.multiscale {
+            2
+        } else {
+            1
+        }
+    }
+    /// `[channel, y, x]` shape of images of a map with `(height, width)`,
+    /// where x is in bytes for `Packed`
+    pub fn shape(&self, symbols: &SymbolTable, (h, w): (usize, usize)) -> [usize; 3] {
+        let (h, w) = self
+            .crop
+            .map_or((h, w), |(rx, ry)| (2 * ry + 1, 2 * rx + 1));
+        let w = match self.encoding {
+            Encoding::Packed => w.div_ceil(8),
+            _ => w,
+        };
+        let hist = usize::from(self.includes_hist);
+        let status = if self.status_planes() {
+            self.status.len()
+        } else {
+            0
+        };
+        let channels = (self.dungeon_channels(symbols) + hist) * self.scales() + status;
+        [channels, h, w]
+    }
+    /// writes the `Float` image into `out`, which has to be `shape` sized
+    /// channels are dungeon channels, status channels and history channels in this order
+    /// (with 2 scales for `multiscale`)
+    pub fn encode(
+        &self,
+        map: &impl Get2D<Item = u8>,
+        history: &Array2<bool>,
+        status: &Status,
+        symbols: &SymbolTable,
+        mut out: ArrayViewMut3<f32>,
+    ) -> Result<(), InvalidTileError> {
+        let dungeon = self.dungeon_channels(symbols);
+        let radius = match self.crop {
+            Some(radius) => radius,
+            None => {
+                self.encode_dungeon(map, symbols, out.slice_mut(s![..dungeon, .., ..]))?;
+                let offset = self.copy_status(status, dungeon, &mut out);
+                if self.includes_hist {
+                    copy_hist(history, out.index_axis_mut(Axis(0), offset));
+                }
+                return Ok(());
+            }
+        };
+        let (h, w) = history.dim();
+        let mut full = Array3::zeros([dungeon + usize::from(self.includes_hist), h, w]);
+        self.encode_dungeon(map, symbols, full.slice_mut(s![..dungeon, .., ..]))?;
+        if self.includes_hist {
+            copy_hist(history, full.index_axis_mut(Axis(0), dungeon));
+        }
+        let mut scales = vec![crop_around(full.view(), player_pos(map, (h, w)), radius)];
+        if self.multiscale {
+            scales.push(downsample(
+                full.view(),
+                (2 * radius.1 + 1, 2 * radius.0 + 1),
+            ));
+        }
+        for (i, scale) in scales.iter().enumerate() {
+            out.slice_mut(s![i * dungeon..(i + 1) * dungeon, .., ..])
+                .assign(&scale.slice(s![..dungeon, .., ..]));
+        }
+        let offset = self.copy_status(status, dungeon * scales.len(), &mut out);
+        if self.includes_hist {
+            for (i, scale) in scales.iter().enumerate() {
+                out.index_axis_mut(Axis(0), offset + i)
+                    .assign(&scale.index_axis(Axis(0), dungeon));
+            }
+        }
+        Ok(())
+    }
+    /// writes the `Index` or `Packed` image into `out`, which has to be `shape` sized
+    /// channels are in the same order as `encode` without status, and `Index` images have
+    /// symbol + 1 in cells, or 0 for cells out of the map
+    pub fn encode_u8(
+        &self,
+        map: &impl Get2D<Item = u8>,
+        history: &Array2<bool>,
+        symbols: &SymbolTable,
+        mut out: ArrayViewMut3<u8>,
+    ) -> GameResult<()> {
+        if self.encoding.is_float() {
+            return Err(ErrorId::InvalidSetting
+                .into_with(|| "ImageSetting::encode_u8 needs an integer encoding"));
+        }
+        let dungeon = self.dungeon_channels(symbols);
+        let (h, w) = history.dim();
+        let mut full = Array3::zeros([dungeon + usize::from(self.includes_hist), h, w]);
+        for y in 0..h {
+            for x in 0..w {
+                let t = *map.get_xy(x, y);
+                let sym = symbols
+                    .channel(t)
+                    .ok_or_else(|| InvalidTileError(t.into()))
+                    .chain_err(|| "in ImageSetting::encode_u8")?;
+                match self.encoding {
+                    Encoding::Index => full[[0, y, x]] = sym as u8 + 1,
+                    _ => full[[sym, y, x]] = 1,
+                }
+            }
+        }
+        if self.includes_hist {
+            Zip::from(full.index_axis_mut(Axis(0), dungeon))
+                .and(history)
+                .for_each(|p, &r| *p = u8::from(r));
+        }
+        let scales = match self.crop {
+            Some(radius) => {
+                let mut scales = vec![crop_around(full.view(), player_pos(map, (h, w)), radius)];
+                if self.multiscale {
+                    // `Index` cells pooled by `multiscale` have the largest symbol
+                    scales.push(downsample(
+                        full.view(),
+                        (2 * radius.1 + 1, 2 * radius.0 + 1),
+                    ));
+                }
+                scales
+            }
+            None => vec![full],
+        };
+        let dungeon_planes = scales
+            .iter()
+            .flat_map(|scale| (0..dungeon).map(move |c| scale.index_axis(Axis(0), c)));
+        let hist_planes = scales
+            .iter()
+            .filter(|_| self.includes_hist)
+            .map(|scale| scale.index_axis(Axis(0), dungeon));
+        for (plane, mut out) in dungeon_planes.chain(hist_planes).zip(out.outer_iter_mut()) {
+            if self.encoding == Encoding::Packed {
+                out.fill(0);
+                for ((y, x), &v) in plane.indexed_iter() {
+                    if v > 0 {
+                        out[[y, x / 8]] |= 0x80 >> (x % 8);
+                    }
+                }
+            } else {
+                out.assign(&plane);
+            }
+        }
+        Ok(())
+    }
+    fn copy_status(&self, status: &Status, start: usize, out: &mut ArrayViewMut3<f32>) -> usize {
+        if self.status_planes() {
+            self.status.copy_status(status, start, out)
+        } else {
+            start
+        }
+    }
+    fn encode_dungeon(
+        &self,
+        map: &impl Get2D<Item = u8>,
+        symbols: &SymbolTable,
+        mut out: ArrayViewMut3<f32>,
+    ) -> Result<(), InvalidTileError> {
+        let (_, h, w) = out.dim();
+        if self.gray {
+            for y in 0..h {
+                for x in 0..w {
+                    let t = *map.get_xy(x, y);
+                    let sym = symbols
+                        .channel(t)
+                        .ok_or_else(|| InvalidTileError(t.into()))?;
+                    out[[0, y, x]] = sym as f32 / symbols.len() as f32;
+                }
+            }
+            return Ok(());
+        }
+        // the returned references never outlive `out`
+        symbol::construct_symbol_map(map, h, w, symbols, |idx| unsafe {
+            &mut *(out.uget_mut(idx) as *mut f32)
+        })
+    }
+}
+
+fn copy_hist(history: &Array2<bool>, out: ArrayViewMut2<f32>) {
+    Zip::from(out).and(history).for_each(|p, &r| {
+        *p = if r { 1.0 } else { 0.0 };
+    });
+}
+
+/// (x, y) of the player in the map, or the center of the map if the player isn't found
+fn player_pos(map: &impl Get2D<Item = u8>, (h, w): (usize, usize)) -> (usize, usize) {
+    (0..h)
+        .find_map(|y| (0..w).find(|&x| *map.get_xy(x, y) == b'@').map(|x| (x, y)))
+        .unwrap_or((w / 2, h / 2))
+}
+
+/// crops a `[channel, y, x]` array to `[channel, 2 * ry + 1, 2 * rx + 1]` around
+/// `center`, filling cells out of the array with 0
+pub fn crop_around<A: Clone + Zero>(
+    array: ArrayView3<A>,
+    center: (usize, usize),
+    radius: (usize, usize),
+) -> Array3<A> {
+    let (c, h, w) = array.dim();
+    let ((cx, cy), (rx, ry)) = (center, radius);
+    let mut res = Array3::zeros([c, 2 * ry + 1, 2 * rx + 1]);
+    // the overlapping range in `array`
+    let (xs, xe) = (cx.saturating_sub(rx), w.min(cx + rx + 1));
+    let (ys, ye) = (cy.saturating_sub(ry), h.min(cy + ry + 1));
+    if xs >= xe || ys >= ye {
+        return res;
+    }
+    // where the range starts in `res`
+    let (dx, dy) = (xs + rx - cx, ys + ry - cy);
+    res.slice_mut(s![.., dy..dy + ye - ys, dx..dx + xe - xs])
+        .assign(&array.slice(s![.., ys..ye, xs..xe]));
+    res
+}
+
+/// downsamples a `[channel, y, x]` array to `[channel, h, w]` by max pooling
+pub fn downsample<A: Copy + Zero + PartialOrd>(
+    array: ArrayView3<A>,
+    size: (usize, usize),
+) -> Array3<A> {
+    let (c, h, w) = array.dim();
+    let (oh, ow) = size;
+    let block = |i: usize, len: usize, out: usize| {
+        let start = (i * len / out).min(len - 1);
+        (start, ((i + 1) * len / out).max(start + 1))
+    };
+    let mut res = Array3::zeros([c, oh, ow]);
+    for oy in 0..oh {
+        let (ys, ye) = block(oy, h, oh);
+        for ox in 0..ow {
+            let (xs, xe) = block(ox, w, ow);
+            let pooled = array.slice(s![.., ys..ye, xs..xe]);
+            for (ch, plane) in pooled.axis_iter(Axis(0)).enumerate() {
+                res[[ch, oy, ox]] = plane.fold(A::zero(), |acc, &v| if v > acc { v } else { acc });
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dungeon::{X, Y};
+    use GameConfig;
+    const CONFIG: &str = r#"
+{
+    "seed": 1,
+    "hide_dungeon": false
+}
+"#;
+    #[test]
+    fn entities() {
+        let runtime = GameConfig::from_json(CONFIG).unwrap().build().unwrap();
+        let entities = runtime.entities();
+        assert!(entities.stair.is_some());
+        assert!(!entities.items.is_empty());
+        let player = runtime.dungeon.path_to_cd(&runtime.player.pos);
+        let dists: Vec<_> = entities
+            .items
+            .iter()
+            .map(|i| i.cd.euc_dist_squared(player))
+            .collect();
+        assert!(dists.windows(2).all(|w| w[0] <= w[1]));
+        for enemy in &entities.enemies {
+            assert_eq!(enemy.hp_rate, None);
+        }
+        let array = entities.to_array(4, 2);
+        assert_eq!(array.shape(), &[8, Entities::FEATURES]);
+        assert_eq!(array[[4, 0]], 1.0);
+        assert_eq!(array[[6, 0]], 1.0);
+        assert_eq!(array[[6, 3]], f32::from(b'%'));
+    }
+    #[test]
+    fn room_of_player() {
+        let runtime = GameConfig::from_json(r#"{"seed": 1, "enemies": {"enemies": []}}"#)
+            .unwrap()
+            .build()
+            .unwrap();
+        let [xs, ys, xe, ye] = runtime.entities().room.unwrap();
+        let player = runtime.dungeon.path_to_cd(&runtime.player.pos);
+        assert!(xs <= player.x.0 && player.x.0 < xe && ys <= player.y.0 && player.y.0 < ye);
+    }
+    #[test]
+    fn entities_in_viewport() {
+        let enemy = |x, y| EnemyInfo {
+            cd: Coord::new(x, y),
+            tile: b'E'.into(),
+            name: "emu".to_owned(),
+            hp_rate: None,
+        };
+        let entities = Entities {
+            enemies: vec![enemy(12, 8), enemy(40, 8), enemy(12, 5)],
+            items: vec![],
+            stair: Some(Coord::new(29, 13)),
+            room: Some([5, 3, 15, 10]),
+        };
+        let viewport = Viewport {
+            offset: Coord::new(10, 5),
+            width: X(20),
+            height: Y(10),
+        };
+        let res = entities.in_viewport(&viewport);
+        assert_eq!(res.enemies, vec![enemy(2, 3)]);
+        assert_eq!(res.stair, Some(Coord::new(19, 8)));
+        assert_eq!(res.room, Some([0, 1, 5, 5]));
+        let far = Viewport {
+            offset: Coord::new(50, 5),
+            ..viewport
+        };
+        assert_eq!(entities.in_viewport(&far), Entities::default());
+    }
+    #[test]
+    fn encode_image() {
+        let map = vec![b"    ".to_vec(), b".@..".to_vec(), b"----".to_vec()];
+        let history = Array2::from_shape_fn([3, 4], |(y, _)| y == 1);
+        let status = Status::default();
+        let setting = ImageSetting::default();
+        let symbols = symbol::Config::default().table(Some(b'A'.into())).unwrap();
+        assert_eq!(setting.shape(&symbols, (3, 4)), [18, 3, 4]);
+        let mut out = Array3::from_elem([18, 3, 4], 2.0);
+        setting
+            .encode(&map, &history, &status, &symbols, out.view_mut())
+            .unwrap();
+        assert_eq!(out[[1, 1, 1]], 1.0);
+        assert_eq!(out[[0, 1, 1]], 0.0);
+        assert_eq!(out.sum(), 12.0);
+        let setting = ImageSetting {
+            status: StatusFlag(StatusFlag::DUNGEON_LEVEL | StatusFlag::HP_MAX),
+            includes_hist: true,
+            crop: Some((1, 1)),
+            multiscale: true,
+            ..ImageSetting::default()
+        };
+        let shape = setting.shape(&symbols, (3, 4));
+        assert_eq!(shape, [40, 3, 3]);
+        let mut out = Array3::zeros(shape);
+        setting
+            .encode(&map, &history, &status, &symbols, out.view_mut())
+            .unwrap();
+        assert_eq!(out[[1, 1, 1]], 1.0);
+        assert_eq!(out[[36, 0, 0]], status.dungeon_level as f32);
+        assert_eq!(out[[37, 0, 0]], status.hp.max.0 as f32);
+        assert_eq!(out[[38, 1, 1]], 1.0);
+        assert_eq!(out[[38, 0, 1]], 0.0);
+    }
+    #[test]
+    fn compact_encodings() {
+        let map = vec![
+            b"         ".to_vec(),
+            b".@.......".to_vec(),
+            b"---------".to_vec(),
+        ];
+        let history = Array2::from_shape_fn([3, 9], |(y, _)| y == 1);
+        let symbols = symbol::Config::default().table(None).unwrap();
+        let setting = ImageSetting {
+            status: StatusFlag(StatusFlag::HP_MAX),
+            includes_hist: true,
+            encoding: Encoding::Index,
+            ..ImageSetting::default()
+        };
+        assert_eq!(setting.shape(&symbols, (3, 9)), [2, 3, 9]);
+        let mut out = Array3::zeros([2, 3, 9]);
+        setting
+            .encode_u8(&map, &history, &symbols, out.view_mut())
+            .unwrap();
+        assert_eq!(out[[0, 1, 0]], 4);
+        assert_eq!(out[[0, 1, 1]], 2);
+        assert_eq!(out[[0, 2, 8]], 5);
+        assert_eq!(out[[0, 0, 0]], 1);
+        assert_eq!(out[[1, 1, 4]], 1);
+        assert_eq!(out[[1, 0, 4]], 0);
+        let setting = ImageSetting {
+            encoding: Encoding::Packed,
+            ..setting
+        };
+        let shape = setting.shape(&symbols, (3, 9));
+        assert_eq!(shape, [symbols.len() + 1, 3, 2]);
+        let mut out = Array3::zeros(shape);
+        setting
+            .encode_u8(&map, &history, &symbols, out.view_mut())
+            .unwrap();
+        assert_eq!(out[[1, 1, 0]], 0b0100_0000);
+        assert_eq!(out[[3, 1, 0]], 0b1011_1111);
+        assert_eq!(out[[3, 1, 1]], 0b1000_0000);
+        assert_eq!(out[[symbols.len(), 1, 1]], 0b1000_0000);
+        let setting = ImageSetting {
+            separate_status: true,
+            ..ImageSetting::new(false, StatusFlag(StatusFlag::HP_MAX), false)
+        };
+        assert_eq!(setting.shape(&symbols, (3, 9)), [symbols.len(), 3, 9]);
+        let mut out = Array3::zeros([symbols.len(), 3, 9]);
+        assert!(setting
+            .encode_u8(&map, &history, &symbols, out.view_mut())
+            .is_err());
+        // cells out of the map are 0 in `Index` images
+        let setting = ImageSetting {
+            includes_hist: false,
+            crop: Some((2, 1)),
+            multiscale: true,
+            encoding: Encoding::Index,
+            ..ImageSetting::default()
+        };
+        let shape = setting.shape(&symbols, (3, 9));
+        assert_eq!(shape, [2, 3, 5]);
+        let mut out = Array3::zeros(shape);
+        setting
+            .encode_u8(&map, &history, &symbols, out.view_mut())
+            .unwrap();
+        assert_eq!(out[[0, 1, 0]], 0);
+        assert_eq!(out[[0, 1, 2]], 2);
+        assert_eq!(out[[0, 1, 1]], 4);
+        assert!(out.index_axis(Axis(0), 1).iter().all(|&v| v > 0));
+        assert_eq!("packed".parse::<Encoding>().unwrap(), Encoding::Packed);
+        assert!("int".parse::<Encoding>().is_err());
+    }
+    #[test]
+    fn crop() {
+        let array = Array3::from_shape_fn([2, 4, 6], |(c, y, x)| (c * 100 + y * 10 + x) as f32);
+        let res = crop_around(array.view(), (0, 1), (2, 1));
+        assert_eq!(res.dim(), (2, 3, 5));
+        assert_eq!(res[[1, 0, 2]], 100.0);
+        assert_eq!(res[[0, 2, 4]], 22.0);
+        assert_eq!(res[[0, 0, 1]], 0.0);
+        let res = crop_around(array.view(), (5, 3), (1, 1));
+        assert_eq!(res[[0, 1, 1]], 35.0);
+        assert_eq!(res[[0, 2, 2]], 0.0);
+    }
+    #[test]
+    fn downsample_max() {
+        let array = Array3::from_shape_fn([1, 4, 6], |(_, y, x)| (y * 10 + x) as f32);
+        let res = downsample(array.view(), (2, 3));
+        assert_eq!(res.dim(), (1, 2, 3));
+        assert_eq!(res[[0, 0, 0]], 11.0);
+        assert_eq!(res[[0, 1, 2]], 35.0);
+        let res = downsample(array.view(), (8, 12));
+        assert_eq!(res[[0, 7, 11]], 35.0);
+    }
+}
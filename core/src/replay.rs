@@ -0,0 +1,173 @@
+//! self-contained replay files
+//!
+//! A replay is a json object with the config, the seed the game was built with,
+//! inputs and hashes of states after each input, so that it can be verified by
+//! re-simulating the game.
+use error::*;
+use hash::full_hash;
+use input::InputCode;
+use serde_json;
+use std::fmt;
+use {GameConfig, RunTime};
+
+pub const REPLAY_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    /// the config given by the user, which may not have the seed
+    pub config: GameConfig,
+    /// the seed the game was actually built with
+    pub seed: u128,
+    pub inputs: Vec<InputCode>,
+    /// hashes of the initial state and states after each input
+    pub hashes: Vec<u64>,
+    /// hash of the final state
+    pub final_hash: u64,
+}
+
+/// the first state which differs from the replay
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Divergence {
+    /// number of inputs before the state, where 0 means the initial state
+    pub step: usize,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "diverged at step {}: expected hash {:016x}, but got {:016x}",
+            self.step, self.expected, self.actual
+        )
+    }
+}
+
+/// re-simulates the game and returns hashes of all states
+fn simulate(config: GameConfig, inputs: &[InputCode]) -> GameResult<Vec<u64>> {
+    let mut runtime = config.build()?;
+    let mut hashes = vec![full_hash(&runtime)?];
+    for &input in inputs {
+        // ignored inputs are saved too, as they are when playing
+        if let Err(e) = runtime.react_to_input(input) {
+            debug!("replay::simulate: {}", e);
+        }
+        hashes.push(full_hash(&runtime)?);
+    }
+    Ok(hashes)
+}
+
+impl Replay {
+    /// makes a replay of the game played on `runtime`, built from `config`
+    pub fn new(config: &GameConfig, runtime: &RunTime) -> GameResult<Self> {
+        let mut replay = Replay {
+            version: REPLAY_VERSION,
+            config: config.clone(),
+            seed: runtime.seed(),
+            inputs: runtime.saved_inputs().to_vec(),
+            hashes: vec![],
+            final_hash: full_hash(runtime)?,
+        };
+        replay.hashes = simulate(replay.game_config(), &replay.inputs)?;
+        if replay.hashes.last() != Some(&replay.final_hash) {
+            return Err(ErrorId::MaybeBug
+                .into_with(|| "Replay::new: the game can't be reproduced by its inputs"));
+        }
+        Ok(replay)
+    }
+    /// the config to build the replayed game
+    pub fn game_config(&self) -> GameConfig {
+        let mut config = self.config.clone();
+        config.seed = Some(self.seed);
+        config
+    }
+    pub fn to_json(&self) -> GameResult<String> {
+        serde_json::to_string(self).into_chained(|| "Replay::to_json")
+    }
+    pub fn from_json(json: &str) -> GameResult<Self> {
+        let replay: Replay = serde_json::from_str(json).into_chained(|| "Replay::from_json")?;
+        if replay.version > REPLAY_VERSION {
+            let version = replay.version;
+            return Err(ErrorId::InvalidConversion
+                .into_with(move || format!("Unsupported replay version {}", version)));
+        }
+        Ok(replay)
+    }
+    /// re-simulates the game and returns the first divergence, if any
+    pub fn verify(&self) -> GameResult<Option<Divergence>> {
+        let hashes = simulate(self.game_config(), &self.inputs)?;
+        let expected = self.hashes.iter().chain(Some(&self.final_hash));
+        let steps = (0..self.hashes.len()).chain(Some(self.inputs.len()));
+        let actual = |step: usize| hashes.get(step).cloned().unwrap_or(0);
+        Ok(steps
+            .zip(expected)
+            .find(|&(step, &hash)| actual(step) != hash)
+            .map(|(step, &expected)| Divergence {
+                step,
+                expected,
+                actual: actual(step),
+            }))
+    }
+}
+
+/// reads a replay file, or a list of inputs(the old format) played with `config`,
+/// and returns the config and inputs to replay
+pub fn load_replay(json: &str, config: GameConfig) -> GameResult<(GameConfig, Vec<InputCode>)> {
+    if json.trim_start().starts_with('[') {
+        let inputs = serde_json::from_str(json).into_chained(|| "load_replay")?;
+        return Ok((config, inputs));
+    }
+    let replay = Replay::from_json(json)?;
+    Ok((replay.game_config(), replay.inputs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use input::{Key, KeyMap};
+    const CONFIG: &str = r#"{ "dungeon": { "style": "rogue", "room_num_x": 2, "room_num_y": 2 } }"#;
+    fn play(keys: &str) -> (GameConfig, RunTime) {
+        let config = GameConfig::from_json(CONFIG).unwrap();
+        let mut runtime = config.clone().build().unwrap();
+        runtime.keymap = KeyMap::ai();
+        for c in keys.chars() {
+            let _ = runtime.react_to_key(Key::Char(c));
+        }
+        (config, runtime)
+    }
+    #[test]
+    fn verify_replay() {
+        let (config, runtime) = play("hjklhhhhjjjj");
+        let replay = Replay::new(&config, &runtime).unwrap();
+        assert_eq!(replay.hashes.len(), replay.inputs.len() + 1);
+        let json = replay.to_json().unwrap();
+        let loaded = Replay::from_json(&json).unwrap();
+        assert_eq!(loaded, replay);
+        assert_eq!(loaded.verify().unwrap(), None);
+        let (replay_config, inputs) = load_replay(&json, GameConfig::default()).unwrap();
+        assert_eq!(replay_config.seed, Some(runtime.seed()));
+        assert_eq!(inputs, runtime.saved_inputs());
+    }
+    #[test]
+    fn find_divergence() {
+        let (config, runtime) = play("llll");
+        let mut replay = Replay::new(&config, &runtime).unwrap();
+        replay.hashes[2] ^= 1;
+        let div = replay.verify().unwrap().unwrap();
+        assert_eq!(div.step, 2);
+        assert_eq!(div.expected ^ 1, div.actual);
+        replay.hashes[2] ^= 1;
+        replay.seed += 1;
+        assert_eq!(replay.verify().unwrap().unwrap().step, 0);
+    }
+    #[test]
+    fn load_old_format() {
+        let (_, runtime) = play("hjkl");
+        let json = runtime.saved_inputs_as_json().unwrap();
+        let (config, inputs) = load_replay(&json, GameConfig::default()).unwrap();
+        assert_eq!(config, GameConfig::default());
+        assert_eq!(inputs, runtime.saved_inputs());
+    }
+}
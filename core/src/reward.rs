@@ -0,0 +1,274 @@
+//! reward specification for reinforcement learning
+use character::player::Status;
+use std::ops::Mul;
+use ui::{MordalKind, UiState};
+use {GameMsg, Reaction, RunTime};
+
+/// weight of a reward term
+/// compared bitwise, so that configurations can be `Eq`
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Weight(pub f32);
+
+impl PartialEq for Weight {
+    fn eq(&self, other: &Weight) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Weight {}
+
+impl Mul<f32> for Weight {
+    type Output = f32;
+    fn mul(self, rhs: f32) -> f32 {
+        self.0 * rhs
+    }
+}
+
+/// weights of reward terms
+/// use negative weights for penalties(e.g. damage, death, time)
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Config {
+    /// per gold the player picked up
+    #[serde(default = "default_gold")]
+    pub gold: Weight,
+    /// per level the player descended
+    #[serde(default)]
+    pub descend: Weight,
+    /// per enemy the player killed
+    #[serde(default)]
+    pub kill: Weight,
+    /// per hit point the player lost
+    #[serde(default)]
+    pub damage: Weight,
+    /// per cell the player visited for the first time
+    #[serde(default)]
+    pub explore: Weight,
+    /// per secret door the player found
+    #[serde(default)]
+    pub search: Weight,
+    /// when the player died
+    #[serde(default)]
+    pub death: Weight,
+    /// per game turn the player spent
+    #[serde(default)]
+    pub time: Weight,
+}
+
+const fn default_gold() -> Weight {
+    Weight(1.0)
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            gold: default_gold(),
+            descend: Weight::default(),
+            kill: Weight::default(),
+            damage: Weight::default(),
+            explore: Weight::default(),
+            search: Weight::default(),
+            death: Weight::default(),
+            time: Weight::default(),
+        }
+    }
+}
+
+/// weighted reward terms of a step
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Reward {
+    pub gold: f32,
+    pub descend: f32,
+    pub kill: f32,
+    pub damage: f32,
+    pub explore: f32,
+    pub search: f32,
+    pub death: f32,
+    pub time: f32,
+}
+
+impl Reward {
+    pub fn total(&self) -> f32 {
+        self.to_dict_vec().into_iter().map(|(_, r)| r).sum()
+    }
+    pub fn to_dict_vec(&self) -> Vec<(&'static str, f32)> {
+        vec![
+            ("gold", self.gold),
+            ("descend", self.descend),
+            ("kill", self.kill),
+            ("damage", self.damage),
+            ("explore", self.explore),
+            ("search", self.search),
+            ("death", self.death),
+            ("time", self.time),
+        ]
+    }
+}
+
+/// computes rewards of each step from reactions
+#[derive(Clone, Debug)]
+pub struct RewardTracker {
+    config: Config,
+    status: Status,
+    explored: usize,
+    turns: u32,
+}
+
+impl RewardTracker {
+    pub fn new(config: Config, runtime: &RunTime) -> Self {
+        let status = runtime.player_status();
+        let explored = count_explored(runtime, &status);
+        RewardTracker {
+            config,
+            status,
+            explored,
+            turns: runtime.turns(),
+        }
+    }
+    /// computes the reward of a step, from the reactions to the input
+    pub fn step(&mut self, runtime: &RunTime, reactions: &[Reaction]) -> Reward {
+        let (mut kills, mut found, mut dead) = (0, 0, false);
+        let (mut status_updated, mut redrawn) = (false, false);
+        for reaction in reactions {
+            match reaction {
+                Reaction::Notify(GameMsg::Killed(_)) => kills += 1,
+                Reaction::Notify(GameMsg::SecretDoor) => found += 1,
+                Reaction::UiTransition(UiState::Mordal(MordalKind::Grave(_))) => dead = true,
+                Reaction::StatusUpdated => status_updated = true,
+                Reaction::Redraw => redrawn = true,
+                _ => {}
+            }
+        }
+        let config = &self.config;
+        let mut reward = Reward {
+            kill: config.kill * kills as f32,
+            search: config.search * found as f32,
+            death: if dead { config.death.0 } else { 0.0 },
+            ..Reward::default()
+        };
+        let turns = runtime.turns();
+        reward.time = config.time * turns.saturating_sub(self.turns) as f32;
+        self.turns = turns;
+        let prev = self.status.clone();
+        // the status isn't updated on the turn the player dies, so read it from the runtime
+        if status_updated || dead {
+            self.status = runtime.player_status();
+            let (cur, prev) = (&self.status, &prev);
+            reward.gold = config.gold * cur.gold.saturating_sub(prev.gold) as f32;
+            let descended = cur.dungeon_level.saturating_sub(prev.dungeon_level);
+            reward.descend = config.descend * descended as f32;
+            let damage = (prev.hp.current.0 - cur.hp.current.0).max(0);
+            reward.damage = config.damage * damage as f32;
+        }
+        if redrawn {
+            let explored = count_explored(runtime, &self.status);
+            let new_cells = if self.status.dungeon_level != prev.dungeon_level {
+                explored
+            } else {
+                explored.saturating_sub(self.explored)
+            };
+            reward.explore = config.explore * new_cells as f32;
+            self.explored = explored;
+        }
+        reward
+    }
+}
+
+fn count_explored(runtime: &RunTime, status: &Status) -> usize {
+    runtime
+        .history(status)
+        .map_or(0, |hist| hist.iter().filter(|&&b| b).count())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use input::Key;
+    use {GameConfig, SmallStr};
+    const CONFIG: &str = r#"
+{
+    "seed": 1,
+    "enemies": {
+        "enemies": []
+    },
+    "reward": {
+        "gold": 2.0,
+        "explore": 0.5,
+        "time": -0.01
+    }
+}
+"#;
+    #[test]
+    fn reward_terms() {
+        let config = GameConfig::from_json(CONFIG).unwrap();
+        let mut runtime = config.clone().build().unwrap();
+        let mut tracker = RewardTracker::new(config.reward, &runtime);
+        let (mut gold, mut explore) = (0.0, 0.0);
+        for c in "hjklhjklyubnhhhhjjjjkkkkllll".chars() {
+            let turns = runtime.turns();
+            let reactions = match runtime.react_to_key(Key::Char(c)) {
+                Ok(r) => r,
+                Err(_) => continue,
+            };
+            let reward = tracker.step(&runtime, &reactions);
+            let spent = runtime.turns() - turns;
+            assert_eq!(reward.time, -0.01 * spent as f32);
+            assert_eq!(reward.kill, 0.0);
+            assert!(reward.explore >= 0.0);
+            gold += reward.gold;
+            explore += reward.explore;
+        }
+        let status = runtime.player_status();
+        assert_eq!(gold, 2.0 * status.gold as f32);
+        assert!(explore > 0.0);
+    }
+    #[test]
+    fn time_per_turn() {
+        let config = GameConfig::from_json(CONFIG).unwrap();
+        let mut runtime = config.clone().build().unwrap();
+        let mut tracker = RewardTracker::new(config.reward, &runtime);
+        let mut max_turns = 0;
+        for c in "hjklyubn".chars() {
+            let turns = runtime.turns();
+            let reactions = match runtime.react_to_key(Key::Alt(c)) {
+                Ok(r) => r,
+                Err(_) => continue,
+            };
+            let spent = runtime.turns() - turns;
+            assert_eq!(
+                tracker.step(&runtime, &reactions).time,
+                -0.01 * spent as f32
+            );
+            max_turns = max_turns.max(spent);
+        }
+        assert!(max_turns > 1);
+    }
+    #[test]
+    fn lethal_damage() {
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        config.reward.damage = Weight(-1.0);
+        config.reward.death = Weight(-10.0);
+        let mut runtime = config.clone().build().unwrap();
+        let mut tracker = RewardTracker::new(config.reward, &runtime);
+        let hp = runtime.player_status().hp.current;
+        runtime.player.get_damage(hp);
+        let reactions = vec![
+            Reaction::Notify(GameMsg::HitFrom(SmallStr::from_str("bat"), hp)),
+            Reaction::UiTransition(UiState::die("Killed by bat".to_owned())),
+        ];
+        let reward = tracker.step(&runtime, &reactions);
+        assert_eq!(reward.damage, -(hp.0 as f32));
+        assert_eq!(reward.death, -10.0);
+    }
+    #[test]
+    fn default_is_gold() {
+        let config = Config::default();
+        assert_eq!(config.gold, Weight(1.0));
+        let reward = Reward {
+            gold: 3.0,
+            time: -1.0,
+            ..Reward::default()
+        };
+        assert_eq!(reward.total(), 2.0);
+    }
+}
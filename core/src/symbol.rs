@@ -1,70 +1,212 @@
 //! Symbol representation of tiles for neural network
+use error::*;
 use rect_iter::Get2D;
+use std::convert::TryFrom;
 use tile::Tile;
+use validation::Validator;
 
-/// Symbol
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
-pub struct Symbol(u8);
+/// tiles of the dungeon and the player, each of which has its own channel
+const DUNGEON_TILES: [&str; 8] = [" ", "@", "#", ".", "-|", "%", "+", "^"];
+/// tiles of item classes
+const ITEM_TILES: [&str; 9] = ["!", "?", "]", ")", "/", "*", ":", "=", ","];
+/// tiles of builtin enemies
+const ENEMY_TILES: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
-impl Symbol {
-    pub fn to_byte(self) -> u8 {
-        self.0
+/// how tiles in a group are assigned to channels
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grouping {
+    /// a channel per tile
+    #[default]
+    Each,
+    /// one channel for all tiles in the group
+    One,
+}
+
+/// Symbol table configuration
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// `each` for a channel per enemy, up to the last enemy in the enemy config,
+    /// or `one` for all enemies in one channel
+    #[serde(default)]
+    pub enemies: Grouping,
+    /// `each` for a channel per item class, or `one` for all items in one channel
+    #[serde(default)]
+    pub items: Grouping,
+    /// channels as strings of tiles(e.g. `[" ", "@", "#.+", "-|", "ABC"]`),
+    /// which overrides `enemies` and `items`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<Vec<String>>,
+}
+
+impl Config {
+    pub(crate) fn validate(&self, v: &mut Validator) {
+        if let Some(channels) = &self.channels {
+            v.field("channels", |v| {
+                if let Err(ChannelError(i, msg)) = SymbolTable::from_channels(channels) {
+                    v.index(i, |v| v.error(msg));
+                }
+            });
+        }
+    }
+    /// resolves the table, where `enemy_max` is the largest enemy tile in the game
+    pub fn table(&self, enemy_max: Option<Tile>) -> GameResult<SymbolTable> {
+        if let Some(channels) = &self.channels {
+            return SymbolTable::from_channels(channels)
+                .map_err(|e| ErrorId::InvalidSetting.into_with(|| e));
+        }
+        let mut channels: Vec<String> = DUNGEON_TILES.iter().map(|&s| s.to_owned()).collect();
+        match self.items {
+            Grouping::Each => channels.extend(ITEM_TILES.iter().map(|&s| s.to_owned())),
+            Grouping::One => channels.push(ITEM_TILES.concat()),
+        }
+        match self.enemies {
+            Grouping::Each => {
+                let max = enemy_max.map_or(0, Tile::to_byte);
+                let enemies = ENEMY_TILES.chars().filter(|&c| c as u8 <= max);
+                channels.extend(enemies.map(String::from));
+            }
+            Grouping::One => channels.push(ENEMY_TILES.to_owned()),
+        }
+        SymbolTable::from_channels(&channels).map_err(|e| ErrorId::MaybeBug.into_with(|| e))
+    }
+}
+
+/// a problem in the `i`-th channel
+#[derive(Clone, Debug, Fail, PartialEq)]
+#[fail(display = "channel {}: {}", _0, _1)]
+pub struct ChannelError(pub usize, pub String);
+
+/// tiles grouped into channels of observations
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(into = "Vec<String>", try_from = "Vec<String>")]
+pub struct SymbolTable {
+    /// channel of each ascii tile, or `NONE`
+    channels: [u8; 128],
+    len: u8,
+}
+
+impl SymbolTable {
+    const NONE: u8 = u8::MAX;
+    /// makes the table from channels as strings of tiles
+    pub fn from_channels<S: AsRef<str>>(channels: &[S]) -> Result<Self, ChannelError> {
+        let mut res = SymbolTable {
+            channels: [Self::NONE; 128],
+            len: 0,
+        };
+        if channels.is_empty() {
+            return Err(ChannelError(0, "needs at least one channel".to_owned()));
+        }
+        for (i, tiles) in channels.iter().enumerate() {
+            let tiles = tiles.as_ref();
+            let err = |msg: String| Err(ChannelError(i, msg));
+            if i >= usize::from(Self::NONE) {
+                return err(format!("more than {} channels", Self::NONE));
+            }
+            if tiles.is_empty() {
+                return err("has no tiles".to_owned());
+            }
+            for c in tiles.chars() {
+                if !c.is_ascii() {
+                    return err(format!("{:?} is not an ascii tile", c));
+                }
+                match res.channels[c as usize] {
+                    Self::NONE => res.channels[c as usize] = i as u8,
+                    j => return err(format!("{:?} is already in channel {}", c, j)),
+                }
+            }
+        }
+        res.len = channels.len() as u8;
+        Ok(res)
+    }
+    /// number of channels
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
     }
-    pub fn decrement(self) -> Self {
-        Symbol(self.0 - 1)
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
-    pub fn from_tile(t: Tile) -> Option<Symbol> {
-        let sym = |u| Some(Symbol(u));
-        match t.to_byte() {
-            b' ' => sym(0),
-            b'@' => sym(1),
-            b'#' => sym(2),
-            b'.' => sym(3),
-            b'-' | b'|' => sym(4),
-            b'%' => sym(5),
-            b'+' => sym(6),
-            b'^' => sym(7),
-            b'!' => sym(8),
-            b'?' => sym(9),
-            b']' => sym(10),
-            b')' => sym(11),
-            b'/' => sym(12),
-            b'*' => sym(13),
-            b':' => sym(14),
-            b'=' => sym(15),
-            b',' => sym(16),
-            x if b'A' <= x && x <= b'Z' => sym(x - b'A' + 17),
+    /// channel of the tile, if it has
+    pub fn channel(&self, tile: u8) -> Option<usize> {
+        match self.channels.get(usize::from(tile)) {
+            Some(&c) if c != Self::NONE => Some(usize::from(c)),
             _ => None,
         }
     }
+    /// tiles in each channel
+    pub fn channels(&self) -> Vec<String> {
+        let mut res = vec![String::new(); self.len()];
+        for (tile, &c) in self.channels.iter().enumerate() {
+            if c != Self::NONE {
+                res[usize::from(c)].push(tile as u8 as char);
+            }
+        }
+        res
+    }
 }
 
-pub fn tile_to_sym(t: u8) -> Option<u8> {
-    Symbol::from_tile(Tile::from(t)).map(|s| s.0)
+impl From<SymbolTable> for Vec<String> {
+    fn from(table: SymbolTable) -> Self {
+        table.channels()
+    }
+}
+
+impl TryFrom<Vec<String>> for SymbolTable {
+    type Error = ChannelError;
+    fn try_from(channels: Vec<String>) -> Result<Self, ChannelError> {
+        SymbolTable::from_channels(&channels)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Fail)]
-#[fail(display = "Invalid tile: {}, while max is {}", _0, _1)]
-pub struct InvalidTileError(Tile, u8);
+#[fail(display = "Tile {} has no channel in the symbol table", _0)]
+pub struct InvalidTileError(pub(crate) Tile);
 
 pub fn construct_symbol_map<'c>(
     map: &impl Get2D<Item = u8>,
     h: usize,
     w: usize,
-    symbol_max: u8,
+    table: &SymbolTable,
     mut res: impl 'c + FnMut([usize; 3]) -> &'c mut f32,
 ) -> Result<(), InvalidTileError> {
-    for i in 0..usize::from(symbol_max) {
-        for y in 0..h {
-            for x in 0..w {
-                let t = *map.get_xy(x, y);
-                let sym = tile_to_sym(t).ok_or_else(|| InvalidTileError(t.into(), symbol_max))?;
-                if sym >= symbol_max {
-                    return Err(InvalidTileError(t.into(), symbol_max));
-                }
-                *res([i, y, x]) = if usize::from(sym) == i { 1.0 } else { 0.0 };
+    for y in 0..h {
+        for x in 0..w {
+            let t = *map.get_xy(x, y);
+            let sym = table.channel(t).ok_or_else(|| InvalidTileError(t.into()))?;
+            for i in 0..table.len() {
+                *res([i, y, x]) = if i == sym { 1.0 } else { 0.0 };
             }
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn tables() {
+        let table = Config::default().table(Some(b'C'.into())).unwrap();
+        assert_eq!(table.len(), 20);
+        assert_eq!(table.channel(b'|'), table.channel(b'-'));
+        assert_eq!(table.channel(b'C'), Some(19));
+        assert_eq!(table.channel(b'D'), None);
+        let config = Config {
+            enemies: Grouping::One,
+            items: Grouping::One,
+            channels: None,
+        };
+        let table = config.table(None).unwrap();
+        assert_eq!(table.len(), 10);
+        assert_eq!(table.channel(b'!'), table.channel(b','));
+        assert_eq!(table.channel(b'Z'), Some(9));
+        let channels = table.channels();
+        assert_eq!(channels[4], "-|");
+        assert_eq!(SymbolTable::from_channels(&channels), Ok(table));
+        assert_eq!(
+            SymbolTable::from_channels(&["@", "#.", "-#"]),
+            Err(ChannelError(2, "'#' is already in channel 1".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,502 @@
+//! compact binary format of trajectories, for offline RL and imitation learning
+//!
+//! A file starts with `MAGIC` and `VERSION`(u16), followed by records.
+//! Each record starts with a tag byte and the episode id(u32), and records of
+//! multiple episodes can be interleaved.
+//! All numbers are little endian.
+use character::player::Status;
+use dungeon::Positioned;
+use error::*;
+use events::StepEvents;
+use ndarray::Array2;
+use reward::{Reward, RewardTracker};
+use serde_json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use {GameConfig, Reaction, RunTime};
+
+pub const MAGIC: [u8; 4] = *b"RGTJ";
+pub const VERSION: u16 = 1;
+
+const TAG_BEGIN: u8 = 1;
+const TAG_STEP: u8 = 2;
+const TAG_END: u8 = 3;
+
+/// an observation in trajectories
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// tiles as `[height, width]`
+    pub map: Array2<u8>,
+    /// cells the player has visited as `[height, width]`
+    pub history: Array2<bool>,
+    /// same as `Status::to_vec`
+    pub status: Vec<u32>,
+}
+
+impl Frame {
+    pub fn new(map: Array2<u8>, history: Array2<bool>, status: &Status) -> Self {
+        Frame {
+            map,
+            history,
+            status: status.to_vec(),
+        }
+    }
+    /// observation of the whole dungeon
+    pub fn from_runtime(runtime: &RunTime) -> GameResult<Self> {
+        let (w, h) = runtime.dungeon_size();
+        let (w, h) = (w.0 as usize, h.0 as usize);
+        let mut map = Array2::from_elem([h, w], b' ');
+        runtime.draw_screen(|Positioned(cd, tile)| {
+            let idx = [cd.y.0 as usize, cd.x.0 as usize];
+            *map.get_mut(idx).ok_or_else(|| {
+                ErrorId::MaybeBug.into_with(|| "Frame::from_runtime: out of the dungeon")
+            })? = tile.to_byte();
+            Ok(())
+        })?;
+        let status = runtime.player_status();
+        let history = runtime
+            .history(&status)
+            .unwrap_or_else(|| Array2::from_elem([h, w], false));
+        Ok(Frame::new(map, history, &status))
+    }
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        let (h, wid) = self.map.dim();
+        w.write_all(&(h as u16).to_le_bytes())?;
+        w.write_all(&(wid as u16).to_le_bytes())?;
+        w.write_all(&self.map.iter().cloned().collect::<Vec<_>>())?;
+        let mut bits = vec![0u8; (h * wid).div_ceil(8)];
+        for (i, _) in self.history.iter().enumerate().filter(|(_, &b)| b) {
+            bits[i / 8] |= 1 << (i % 8);
+        }
+        w.write_all(&bits)?;
+        w.write_all(&[self.status.len() as u8])?;
+        for s in &self.status {
+            w.write_all(&s.to_le_bytes())?;
+        }
+        Ok(())
+    }
+    fn read(r: &mut impl Read) -> GameResult<Self> {
+        let (h, w) = (read_u16(r)? as usize, read_u16(r)? as usize);
+        let map = Array2::from_shape_vec([h, w], read_bytes(r, h * w)?).compat()?;
+        let bits = read_bytes(r, (h * w).div_ceil(8))?;
+        let history = Array2::from_shape_fn([h, w], |(y, x)| {
+            let i = y * w + x;
+            bits[i / 8] & (1 << (i % 8)) != 0
+        });
+        let len = read_bytes(r, 1)?[0];
+        let status = (0..len).map(|_| read_u32(r)).collect::<GameResult<_>>()?;
+        Ok(Frame {
+            map,
+            history,
+            status,
+        })
+    }
+}
+
+/// a record in trajectory files
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+    /// start of an episode, with the initial observation
+    Begin {
+        episode: u32,
+        config: Box<GameConfig>,
+        seed: u128,
+        frame: Frame,
+    },
+    /// a step, with the observation after the action
+    Step {
+        episode: u32,
+        action: u8,
+        reward: Reward,
+        events: StepEvents,
+        frame: Frame,
+    },
+    End {
+        episode: u32,
+    },
+}
+
+/// writes trajectories of one or more episodes
+#[derive(Debug)]
+pub struct TrajectoryWriter<W: Write> {
+    inner: W,
+    next_episode: u32,
+}
+
+impl TrajectoryWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> GameResult<Self> {
+        let file = File::create(path).into_chained(|| "TrajectoryWriter::create")?;
+        TrajectoryWriter::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write> TrajectoryWriter<W> {
+    pub fn new(mut inner: W) -> GameResult<Self> {
+        inner
+            .write_all(&MAGIC)
+            .and_then(|_| inner.write_all(&VERSION.to_le_bytes()))
+            .into_chained(|| "TrajectoryWriter::new")?;
+        Ok(TrajectoryWriter {
+            inner,
+            next_episode: 0,
+        })
+    }
+    /// starts a new episode and returns its id
+    pub fn begin(&mut self, config: &GameConfig, seed: u128, frame: &Frame) -> GameResult<u32> {
+        let episode = self.next_episode;
+        self.next_episode += 1;
+        let config = config.to_json()?;
+        let w = &mut self.inner;
+        (|| {
+            write_head(w, TAG_BEGIN, episode)?;
+            write_bytes(w, config.as_bytes())?;
+            w.write_all(&seed.to_le_bytes())?;
+            frame.write(w)
+        })()
+        .into_chained(|| "TrajectoryWriter::begin")?;
+        Ok(episode)
+    }
+    pub fn step(
+        &mut self,
+        episode: u32,
+        action: u8,
+        reward: &Reward,
+        events: &StepEvents,
+        frame: &Frame,
+    ) -> GameResult<()> {
+        // most steps have no events, so they're written as an empty string
+        let events = if *events == StepEvents::default() {
+            String::new()
+        } else {
+            serde_json::to_string(events).into_chained(|| "TrajectoryWriter::step")?
+        };
+        let w = &mut self.inner;
+        (|| {
+            write_head(w, TAG_STEP, episode)?;
+            w.write_all(&[action])?;
+            for (_, r) in reward.to_dict_vec() {
+                w.write_all(&r.to_le_bytes())?;
+            }
+            write_bytes(w, events.as_bytes())?;
+            frame.write(w)
+        })()
+        .into_chained(|| "TrajectoryWriter::step")
+    }
+    pub fn end(&mut self, episode: u32) -> GameResult<()> {
+        write_head(&mut self.inner, TAG_END, episode).into_chained(|| "TrajectoryWriter::end")
+    }
+    pub fn flush(&mut self) -> GameResult<()> {
+        self.inner
+            .flush()
+            .into_chained(|| "TrajectoryWriter::flush")
+    }
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// records a game played on `RunTime` directly(e.g. human play in devui)
+pub struct GameRecorder<W: Write> {
+    writer: TrajectoryWriter<W>,
+    reward: RewardTracker,
+    status: Status,
+    episode: u32,
+}
+
+impl<W: Write> GameRecorder<W> {
+    /// starts an episode from the current state of `runtime`
+    pub fn new(
+        mut writer: TrajectoryWriter<W>,
+        config: &GameConfig,
+        runtime: &RunTime,
+    ) -> GameResult<Self> {
+        let frame = Frame::from_runtime(runtime)?;
+        let episode = writer.begin(config, runtime.seed(), &frame)?;
+        Ok(GameRecorder {
+            writer,
+            reward: RewardTracker::new(config.reward.clone(), runtime),
+            status: runtime.player_status(),
+            episode,
+        })
+    }
+    /// records a step, where `reactions` are the reactions to `action`
+    pub fn step(
+        &mut self,
+        action: u8,
+        runtime: &RunTime,
+        reactions: &[Reaction],
+    ) -> GameResult<()> {
+        let reward = self.reward.step(runtime, reactions);
+        let status = runtime.player_status();
+        let events = StepEvents::new(reactions, &self.status, &status);
+        self.status = status;
+        let frame = Frame::from_runtime(runtime)?;
+        self.writer
+            .step(self.episode, action, &reward, &events, &frame)
+    }
+    /// ends the episode and flushes the writer
+    pub fn finish(mut self) -> GameResult<W> {
+        self.writer.end(self.episode)?;
+        self.writer.flush()?;
+        Ok(self.writer.into_inner())
+    }
+}
+
+/// a transition in trajectories
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    pub episode: u32,
+    pub observation: Frame,
+    pub action: u8,
+    pub reward: Reward,
+    pub events: StepEvents,
+    pub next_observation: Frame,
+    /// the episode terminated(by death or the step limit) at this step
+    pub done: bool,
+}
+
+/// reads records written by `TrajectoryWriter`
+pub struct TrajectoryReader<R: Read> {
+    inner: R,
+    version: u16,
+    /// the last observation of each running episode
+    frames: HashMap<u32, Frame>,
+}
+
+impl TrajectoryReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> GameResult<Self> {
+        let file = File::open(path).into_chained(|| "TrajectoryReader::open")?;
+        TrajectoryReader::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> TrajectoryReader<R> {
+    pub fn new(mut inner: R) -> GameResult<Self> {
+        let magic = read_bytes(&mut inner, MAGIC.len())?;
+        if magic != MAGIC {
+            return Err(ErrorId::InvalidConversion.into_with(|| "Not a trajectory file"));
+        }
+        let version = read_u16(&mut inner)?;
+        if version > VERSION {
+            return Err(ErrorId::InvalidConversion
+                .into_with(move || format!("Unsupported trajectory version {}", version)));
+        }
+        Ok(TrajectoryReader {
+            inner,
+            version,
+            frames: HashMap::new(),
+        })
+    }
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+    /// reads the next record, or returns None at the end of the file
+    pub fn read_record(&mut self) -> GameResult<Option<Record>> {
+        let mut tag = [0u8];
+        let r = &mut self.inner;
+        if r.read(&mut tag).into_chained(|| "TrajectoryReader")? == 0 {
+            return Ok(None);
+        }
+        let episode = read_u32(r)?;
+        let record = match tag[0] {
+            TAG_BEGIN => {
+                let config = String::from_utf8(read_vec(r)?).compat()?;
+                let config = GameConfig::from_json(&config)?;
+                let seed = u128::from_le_bytes(read_array(r)?);
+                let frame = Frame::read(r)?;
+                Record::Begin {
+                    episode,
+                    config: Box::new(config),
+                    seed,
+                    frame,
+                }
+            }
+            TAG_STEP => {
+                let action = read_bytes(r, 1)?[0];
+                let mut terms = [0.0f32; 8];
+                for t in &mut terms {
+                    *t = f32::from_le_bytes(read_array(r)?);
+                }
+                let events = read_vec(r)?;
+                let events = if events.is_empty() {
+                    StepEvents::default()
+                } else {
+                    serde_json::from_slice(&events).into_chained(|| "TrajectoryReader")?
+                };
+                let frame = Frame::read(r)?;
+                Record::Step {
+                    episode,
+                    action,
+                    reward: reward_from_terms(terms),
+                    events,
+                    frame,
+                }
+            }
+            TAG_END => Record::End { episode },
+            tag => {
+                return Err(ErrorId::InvalidConversion
+                    .into_with(move || format!("Invalid record tag {}", tag)))
+            }
+        };
+        Ok(Some(record))
+    }
+    /// reads the next transition, or returns None at the end of the file
+    pub fn read_transition(&mut self) -> GameResult<Option<Transition>> {
+        while let Some(record) = self.read_record()? {
+            match record {
+                Record::Begin { episode, frame, .. } => {
+                    self.frames.insert(episode, frame);
+                }
+                Record::Step {
+                    episode,
+                    action,
+                    reward,
+                    events,
+                    frame,
+                } => {
+                    let observation =
+                        self.frames.insert(episode, frame.clone()).ok_or_else(|| {
+                            ErrorId::InvalidConversion
+                                .into_with(move || format!("Episode {} has no beginning", episode))
+                        })?;
+                    let done = events.termination.is_some();
+                    return Ok(Some(Transition {
+                        episode,
+                        observation,
+                        action,
+                        reward,
+                        events,
+                        next_observation: frame,
+                        done,
+                    }));
+                }
+                Record::End { episode } => {
+                    self.frames.remove(&episode);
+                }
+            }
+        }
+        Ok(None)
+    }
+    /// reads at most `size` transitions, or returns None at the end of the file
+    pub fn read_batch(&mut self, size: usize) -> GameResult<Option<Vec<Transition>>> {
+        let mut res = vec![];
+        while res.len() < size {
+            match self.read_transition()? {
+                Some(t) => res.push(t),
+                None => break,
+            }
+        }
+        Ok(if res.is_empty() { None } else { Some(res) })
+    }
+}
+
+fn reward_from_terms(t: [f32; 8]) -> Reward {
+    Reward {
+        gold: t[0],
+        descend: t[1],
+        kill: t[2],
+        damage: t[3],
+        explore: t[4],
+        search: t[5],
+        death: t[6],
+        time: t[7],
+    }
+}
+
+fn write_head(w: &mut impl Write, tag: u8, episode: u32) -> io::Result<()> {
+    w.write_all(&[tag])?;
+    w.write_all(&episode.to_le_bytes())
+}
+
+fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    w.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    w.write_all(bytes)
+}
+
+fn read_bytes(r: &mut impl Read, len: usize) -> GameResult<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    r.read_exact(&mut buf)
+        .into_chained(|| "TrajectoryReader: unexpected EOF")?;
+    Ok(buf)
+}
+
+fn read_array<A: Default + AsMut<[u8]>>(r: &mut impl Read) -> GameResult<A> {
+    let mut buf = A::default();
+    r.read_exact(buf.as_mut())
+        .into_chained(|| "TrajectoryReader: unexpected EOF")?;
+    Ok(buf)
+}
+
+fn read_vec(r: &mut impl Read) -> GameResult<Vec<u8>> {
+    let len = read_u32(r)? as usize;
+    read_bytes(r, len)
+}
+
+fn read_u16(r: &mut impl Read) -> GameResult<u16> {
+    read_array(r).map(u16::from_le_bytes)
+}
+
+fn read_u32(r: &mut impl Read) -> GameResult<u32> {
+    read_array(r).map(u32::from_le_bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use input::Key;
+    const CONFIG: &str = r#"
+{
+    "seed": 5,
+    "enemies": {
+        "enemies": []
+    }
+}
+"#;
+    #[test]
+    fn write_and_read() {
+        let config = GameConfig::from_json(CONFIG).unwrap();
+        let mut runtime = config.clone().build().unwrap();
+        let writer = TrajectoryWriter::new(vec![]).unwrap();
+        let mut recorder = GameRecorder::new(writer, &config, &runtime).unwrap();
+        let actions = b"hjklhhjjkkll";
+        let mut frames = vec![Frame::from_runtime(&runtime).unwrap()];
+        for &a in actions {
+            let reactions = runtime.react_to_key(Key::Char(a as char)).unwrap();
+            recorder.step(a, &runtime, &reactions).unwrap();
+            frames.push(Frame::from_runtime(&runtime).unwrap());
+        }
+        let bytes = recorder.finish().unwrap();
+        let mut reader = TrajectoryReader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.version(), VERSION);
+        match reader.read_record().unwrap().unwrap() {
+            Record::Begin {
+                episode,
+                seed,
+                frame,
+                ..
+            } => {
+                assert_eq!((episode, seed), (0, 5));
+                assert_eq!(frame, frames[0]);
+            }
+            r => panic!("Unexpected record {:?}", r),
+        }
+        let mut reader = TrajectoryReader::new(&bytes[..]).unwrap();
+        let batch = reader.read_batch(8).unwrap().unwrap();
+        assert_eq!(batch.len(), 8);
+        let rest = reader.read_batch(8).unwrap().unwrap();
+        assert_eq!(rest.len(), actions.len() - 8);
+        assert!(reader.read_batch(8).unwrap().is_none());
+        for (i, t) in batch.iter().chain(rest.iter()).enumerate() {
+            assert_eq!(t.action, actions[i]);
+            assert_eq!(t.observation, frames[i]);
+            assert_eq!(t.next_observation, frames[i + 1]);
+            assert!(!t.done);
+        }
+    }
+    #[test]
+    fn invalid_magic() {
+        assert!(TrajectoryReader::new(&b"RGTX\x01\x00"[..]).is_err());
+    }
+}
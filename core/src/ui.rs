@@ -1,3 +1,4 @@
+use dungeon::{Coord, X, Y};
 use input::System;
 
 /// A representation of Ui transition
@@ -47,3 +48,80 @@ pub enum MordalMsg {
     Cancel,
     None,
 }
+
+/// the part of the dungeon shown on the screen
+/// the first and last rows of the screen are left for the message and the status
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Viewport {
+    /// the dungeon coordinate drawn at the upper left corner of the screen
+    pub offset: Coord,
+    pub width: X,
+    pub height: Y,
+}
+
+impl Viewport {
+    /// the viewport which has the size of `screen` and is centered on `center` as much as
+    /// possible without going out of `dungeon`
+    pub fn centered(center: Coord, screen: (X, Y), dungeon: (X, Y)) -> Self {
+        let clamp = |c: i32, s: i32, d: i32| (c - s / 2).min(d - s).max(0);
+        Viewport {
+            offset: Coord::new(
+                clamp(center.x.0, screen.0 .0, dungeon.0 .0),
+                clamp(center.y.0, screen.1 .0, dungeon.1 .0),
+            ),
+            width: screen.0,
+            height: screen.1,
+        }
+    }
+    /// converts a dungeon coordinate into a screen coordinate,
+    /// or returns None if it's not shown on the screen
+    pub fn to_screen(&self, cd: Coord) -> Option<Coord> {
+        let res = cd - self.offset;
+        if res.x.0 < 0 || res.x >= self.width || res.y.0 < 1 || res.y.0 >= self.height.0 - 1 {
+            None
+        } else {
+            Some(res)
+        }
+    }
+    /// converts a screen coordinate into a dungeon coordinate
+    pub fn to_dungeon(&self, cd: Coord) -> Coord {
+        cd + self.offset
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn viewport_follows_player() {
+        let (screen, dungeon) = ((X(80), Y(24)), (X(160), Y(48)));
+        let viewport = Viewport::centered(Coord::new(5, 3), screen, dungeon);
+        assert_eq!(viewport.offset, Coord::new(0, 0));
+        let viewport = Viewport::centered(Coord::new(100, 30), screen, dungeon);
+        assert_eq!(viewport.offset, Coord::new(60, 18));
+        assert_eq!(
+            viewport.to_screen(Coord::new(100, 30)),
+            Some(Coord::new(40, 12))
+        );
+        assert_eq!(viewport.to_dungeon(Coord::new(40, 12)), Coord::new(100, 30));
+        let viewport = Viewport::centered(Coord::new(158, 46), screen, dungeon);
+        assert_eq!(viewport.offset, Coord::new(80, 24));
+        assert_eq!(
+            viewport.to_screen(Coord::new(159, 46)),
+            Some(Coord::new(79, 22))
+        );
+        assert_eq!(viewport.to_screen(Coord::new(159, 47)), None);
+        assert_eq!(viewport.to_screen(Coord::new(79, 40)), None);
+    }
+    #[test]
+    fn viewport_of_same_size() {
+        let size = (X(80), Y(24));
+        let viewport = Viewport::centered(Coord::new(70, 20), size, size);
+        assert_eq!(viewport.offset, Coord::new(0, 0));
+        assert_eq!(
+            viewport.to_screen(Coord::new(70, 20)),
+            Some(Coord::new(70, 20))
+        );
+        assert_eq!(viewport.to_screen(Coord::new(70, 0)), None);
+    }
+}
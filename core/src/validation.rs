@@ -0,0 +1,187 @@
+//! validation of configs, which reports problems with their json paths
+use std::fmt;
+
+/// a problem in a config
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigError {
+    /// json path of the value, like `dungeon.room_num_x` or `enemies.enemies[3]`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// collects problems, tracking the path of the value being checked
+#[derive(Debug, Default)]
+pub(crate) struct Validator {
+    path: Vec<String>,
+    errors: Vec<ConfigError>,
+}
+
+impl Validator {
+    pub(crate) fn new() -> Self {
+        Validator::default()
+    }
+    fn path_with(&self, last: Option<&str>) -> String {
+        let mut res = String::new();
+        for seg in self.path.iter().map(String::as_str).chain(last) {
+            if !res.is_empty() && !seg.starts_with('[') {
+                res.push('.');
+            }
+            res.push_str(seg);
+        }
+        res
+    }
+    /// checks a child value named `name`
+    pub(crate) fn field(&mut self, name: &str, f: impl FnOnce(&mut Self)) {
+        self.path.push(name.to_owned());
+        f(self);
+        self.path.pop();
+    }
+    /// checks the `i`-th element of a sequence
+    pub(crate) fn index(&mut self, i: usize, f: impl FnOnce(&mut Self)) {
+        self.path.push(format!("[{}]", i));
+        f(self);
+        self.path.pop();
+    }
+    /// reports a problem of the current value
+    pub(crate) fn error(&mut self, message: impl Into<String>) {
+        let path = self.path_with(None);
+        self.errors.push(ConfigError {
+            path,
+            message: message.into(),
+        });
+    }
+    /// reports a problem of the child `name` unless `ok`
+    pub(crate) fn check(&mut self, name: &str, ok: bool, message: impl FnOnce() -> String) {
+        if !ok {
+            let path = self.path_with(Some(name));
+            self.errors.push(ConfigError {
+                path,
+                message: message(),
+            });
+        }
+    }
+    /// reports the child `name` if it's zero, which makes probabilities or ranges empty
+    pub(crate) fn positive(&mut self, name: &str, value: u32) {
+        self.check(name, value > 0, || "must be positive".to_owned());
+    }
+    /// reports the child `name` if it's over 100%
+    pub(crate) fn parcent(&mut self, name: &str, value: u32) {
+        self.check(name, value <= 100, || format!("{}% is over 100%", value));
+    }
+    pub(crate) fn finish(self) -> Vec<ConfigError> {
+        self.errors
+    }
+}
+
+/// converts a path of serde_ignored into the same format as `ConfigError::path`
+pub(crate) fn ignored_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+    fn write(path: &Path, buf: &mut String) {
+        match path {
+            Path::Root => {}
+            Path::Seq { parent, index } => {
+                write(parent, buf);
+                buf.push_str(&format!("[{}]", index));
+            }
+            Path::Map { parent, key } => {
+                write(parent, buf);
+                if !buf.is_empty() {
+                    buf.push('.');
+                }
+                buf.push_str(key);
+            }
+            Path::Some { parent }
+            | Path::NewtypeStruct { parent }
+            | Path::NewtypeVariant { parent } => write(parent, buf),
+        }
+    }
+    let mut buf = String::new();
+    write(path, &mut buf);
+    buf
+}
+
+/// reports keys in `input` which don't appear in `known`, recursively
+pub(crate) fn unknown_keys(
+    input: &serde_json::Value,
+    known: &serde_json::Value,
+    path: &str,
+    errors: &mut Vec<ConfigError>,
+) {
+    if let (Some(input), Some(known)) = (input.as_object(), known.as_object()) {
+        for (key, value) in input {
+            let path = format!("{}.{}", path, key);
+            match known.get(key) {
+                Some(known) => unknown_keys(value, known, &path, errors),
+                None => errors.push(ConfigError {
+                    path,
+                    message: "unknown field".to_owned(),
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use GameConfig;
+    #[test]
+    fn paths() {
+        let mut v = Validator::new();
+        v.field("enemies", |v| {
+            v.check("appear_rate_gold", false, || "bad".to_owned());
+            v.field("enemies", |v| v.index(3, |v| v.error("bad")));
+        });
+        v.positive("width", 0);
+        let paths: Vec<_> = v.finish().into_iter().map(|e| e.path).collect();
+        assert_eq!(
+            paths,
+            vec!["enemies.appear_rate_gold", "enemies.enemies[3]", "width"]
+        );
+    }
+    #[test]
+    fn invalid_config() {
+        let json = ::read_file("../data/config-invalid.json").unwrap();
+        let config = GameConfig::from_json(&json).unwrap();
+        let errors: Vec<_> = config
+            .validate()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "seed_range: the range is empty",
+                "dungeon.room_num_y: must be positive",
+                "dungeon.min_room_size.x: 30 is larger than room cells, which are 26 wide",
+                "dungeon.maze_rate_inv: must be positive",
+                "item.armor.armors[1]: no builtin armor 8, there are 8",
+                "item.weapon.weapons: needs at least one weapon",
+                "player.max_items: must be positive",
+                "enemies.enemies[2]: no builtin enemy 26, there are 26",
+                "enemies.appear_rate_gold: 120% is over 100%",
+            ]
+        );
+        // panics without validation
+        assert!(config.build().is_err());
+        let strict = GameConfig::from_json_strict(&json).unwrap_err().to_string();
+        assert!(strict.contains("dungeon.treasure_room.rate: unknown field"));
+        assert!(strict.contains("dungeon.room_num: unknown field"));
+        assert!(strict.contains("player.hp: unknown field"));
+        assert!(strict.contains("enemies.appear_rate_gold: 120% is over 100%"));
+    }
+    #[test]
+    fn valid_configs() {
+        for name in &["default", "mini", "nohide"] {
+            let path = format!("../data/config-{}.json", name);
+            let json = ::read_file(&path).unwrap();
+            assert!(GameConfig::from_json_strict(&json).is_ok(), "{}", path);
+        }
+    }
+}
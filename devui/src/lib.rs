@@ -12,11 +12,14 @@ extern crate tuple_map;
 
 pub mod error;
 pub mod screen;
+mod stats;
 use error::*;
 use rogue_gym_core::input::InputCode;
+use rogue_gym_core::trajectory::{GameRecorder, TrajectoryWriter};
 use rogue_gym_core::{GameConfig, RunTime};
 use rogue_gym_uilib::{process_reaction, Screen, Transition};
 use screen::{RawTerm, TermScreen};
+pub use stats::{export_floor, show_floor_stats, write_floor_dump};
 use std::io;
 use std::sync::mpsc;
 use std::thread;
@@ -41,8 +44,24 @@ fn setup_screen(
 }
 
 pub fn play_game(config: GameConfig, is_default: bool) -> GameResult<RunTime> {
+    play_game_(config, is_default, None)
+}
+
+/// same as `play_game`, but records the trajectory into `path`
+pub fn record_game(config: GameConfig, is_default: bool, path: &str) -> GameResult<RunTime> {
+    play_game_(config, is_default, Some(path))
+}
+
+fn play_game_(config: GameConfig, is_default: bool, record: Option<&str>) -> GameResult<RunTime> {
     debug!("devui::play_game config: {:?}", config);
-    let (mut screen, mut runtime) = setup_screen(config, is_default)?;
+    let (mut screen, mut runtime) = setup_screen(config.clone(), is_default)?;
+    let mut recorder = match record {
+        Some(path) => {
+            let writer = TrajectoryWriter::create(path)?;
+            Some(GameRecorder::new(writer, &config, &runtime)?)
+        }
+        None => None,
+    };
     let stdin = io::stdin();
     // let's receive keyboard inputs(our main loop)
     let mut pending = false;
@@ -64,6 +83,13 @@ pub fn play_game(config: GameConfig, is_default: bool) -> GameResult<RunTime> {
                 continue;
             }
         };
+        if let Some(recorder) = recorder.as_mut() {
+            let action = match key {
+                Key::Char(c) if c.is_ascii() => c as u8,
+                _ => 0,
+            };
+            recorder.step(action, &runtime, &res)?;
+        }
         for reaction in res {
             let result = process_reaction(&mut screen, &mut runtime, reaction)
                 .chain_err(|| "in play_game")?;
@@ -75,6 +101,9 @@ pub fn play_game(config: GameConfig, is_default: bool) -> GameResult<RunTime> {
         pending = screen.display_msg()?;
     }
     screen.clear_screen()?;
+    if let Some(recorder) = recorder {
+        recorder.finish()?;
+    }
     Ok(runtime)
 }
 
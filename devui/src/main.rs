@@ -9,13 +9,19 @@ extern crate tuple_map;
 
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
+use std::ops::Range;
 
 use clap::ArgMatches;
-use rogue_gym_core::{json_to_inputs, read_file, GameConfig};
+use rogue_gym_core::replay::{load_replay, Replay};
+use rogue_gym_core::{read_file, GameConfig};
 use rogue_gym_devui::error::*;
-use rogue_gym_devui::{play_game, show_replay};
+use rogue_gym_devui::{
+    export_floor, play_game, record_game, show_floor_stats, show_replay, write_floor_dump,
+};
 
 const DEFAULT_INTERVAL_MS: u64 = 500;
+const DEFAULT_STAT_LEVELS: u32 = 10;
+const DEFAULT_STAT_SEEDS: u128 = 100;
 
 fn main() {
     if let Err(err) = main_() {
@@ -38,7 +44,7 @@ fn main_() -> GameResult<()> {
     if let Some(replay_arg) = args.subcommand_matches("replay") {
         let fname = replay_arg.value_of("file").unwrap();
         let replay = read_file(fname).into_chained(|| "Failed to read replay file!")?;
-        let replay = json_to_inputs(&replay)?;
+        let (config, replay) = load_replay(&replay, config)?;
         let mut interval = DEFAULT_INTERVAL_MS;
         if let Some(inter) = replay_arg.value_of("interval") {
             interval = inter
@@ -46,17 +52,64 @@ fn main_() -> GameResult<()> {
                 .into_chained(|| "Failed to parse 'interval' arg!")?;
         }
         show_replay(config, replay, interval)
+    } else if let Some(verify_arg) = args.subcommand_matches("verify") {
+        let fname = verify_arg.value_of("file").unwrap();
+        let replay = read_file(fname).into_chained(|| "Failed to read replay file!")?;
+        let replay = Replay::from_json(&replay)?;
+        match replay.verify()? {
+            Some(div) => Err(ErrorID::InvalidArg.into_with(move || format!("Replay {}", div))),
+            None => {
+                println!("OK: {} inputs replayed", replay.inputs.len());
+                Ok(())
+            }
+        }
+    } else if let Some(stats_arg) = args.subcommand_matches("floor-stats") {
+        let seeds = match stats_arg.value_of("seeds") {
+            Some(seeds) => parse_range(seeds)?,
+            None => 0..DEFAULT_STAT_SEEDS,
+        };
+        let mut levels = DEFAULT_STAT_LEVELS;
+        if let Some(lev) = stats_arg.value_of("levels") {
+            levels = lev
+                .parse()
+                .into_chained(|| "Failed to parse 'levels' arg!")?;
+        }
+        show_floor_stats(config, seeds, levels)
+    } else if let Some(export_arg) = args.subcommand_matches("export") {
+        let out = export_arg.value_of("out").unwrap();
+        let level = match export_arg.value_of("level") {
+            Some(lev) => Some(
+                lev.parse()
+                    .into_chained(|| "Failed to parse 'level' arg!")?,
+            ),
+            None => None,
+        };
+        export_floor(config, level, out)
     } else {
-        let runtime = play_game(config, is_default)?;
+        let runtime = match args.value_of("record") {
+            Some(record_file) => record_game(config.clone(), is_default, record_file)?,
+            None => play_game(config.clone(), is_default)?,
+        };
         if let Some(save_file) = args.value_of("save") {
-            let s = runtime.saved_inputs_as_json()?;
+            let s = Replay::new(&config, &runtime)?.to_json()?;
             let mut file = File::create(save_file)?;
             file.write_all(s.as_bytes())?;
         }
+        if let Some(export_file) = args.value_of("export") {
+            write_floor_dump(&runtime.export_floor(), export_file)?;
+        }
         Ok(())
     }
 }
 
+fn parse_range(s: &str) -> GameResult<Range<u128>> {
+    let err = || ErrorID::InvalidArg.into_with(|| "Seed range has to be like '0..100'");
+    let mut iter = s.splitn(2, "..");
+    let start = iter.next().ok_or_else(err)?.parse().map_err(|_| err())?;
+    let end = iter.next().ok_or_else(err)?.parse().map_err(|_| err())?;
+    Ok(start..end)
+}
+
 fn get_config(args: &ArgMatches) -> GameResult<(GameConfig, bool)> {
     let file_name = match args.value_of("config") {
         Some(fname) => fname,
@@ -117,6 +170,20 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                 .help("save replay file")
                 .takes_value(true),
         )
+        .arg(
+            clap::Arg::with_name("record")
+                .long("record")
+                .value_name("RECORD")
+                .help("record the trajectory as a dataset file")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("export")
+                .long("export")
+                .value_name("EXPORT")
+                .help("export the last floor(.json file or directory)")
+                .takes_value(true),
+        )
         .subcommand(
             clap::SubCommand::with_name("replay")
                 .about("Show replay by json file")
@@ -139,6 +206,61 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            clap::SubCommand::with_name("verify")
+                .about("Re-simulate a replay file and report the first divergence")
+                .version("0.1")
+                .arg(
+                    clap::Arg::with_name("file")
+                        .short("f")
+                        .long("file")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("replay json file")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("floor-stats")
+                .about("Check invariants of generated floors and show statistics of them")
+                .version("0.1")
+                .arg(
+                    clap::Arg::with_name("seeds")
+                        .long("seeds")
+                        .value_name("SEEDS")
+                        .help("Range of seeds(e.g. 0..100)")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("levels")
+                        .short("n")
+                        .long("levels")
+                        .value_name("LEVELS")
+                        .help("Number of floors generated per seed")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("export")
+                .about("Export a generated floor as json or npy arrays")
+                .version("0.1")
+                .arg(
+                    clap::Arg::with_name("out")
+                        .short("o")
+                        .long("out")
+                        .required(true)
+                        .value_name("OUT")
+                        .help(".json file or directory to write the floor in")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("level")
+                        .long("level")
+                        .value_name("LEVEL")
+                        .help("Level to generate")
+                        .takes_value(true),
+                ),
+        )
         .get_matches()
 }
 
@@ -0,0 +1,130 @@
+//! statistics and dumps of generated floors
+use error::*;
+use rogue_gym_core::dungeon::rogue::validate::{FloorSample, FloorStats};
+use rogue_gym_core::dungeon::FloorDump;
+use rogue_gym_core::GameConfig;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::ops::Range;
+
+#[derive(Clone, Debug, Default)]
+struct LevelSummary {
+    floors: usize,
+    rooms: usize,
+    mazes: usize,
+    dark_rooms: usize,
+    passages: usize,
+    passage_cells: usize,
+    gold: Vec<u32>,
+}
+
+impl LevelSummary {
+    fn add(&mut self, stats: &FloorStats) {
+        self.floors += 1;
+        self.rooms += stats.rooms;
+        self.mazes += stats.mazes;
+        self.dark_rooms += stats.dark_rooms;
+        self.passages += stats.passage_lengths.len();
+        self.passage_cells += stats.passage_lengths.iter().sum::<usize>();
+        self.gold.push(stats.gold);
+    }
+}
+
+fn ratio(a: usize, b: usize) -> f64 {
+    if b == 0 {
+        0.0
+    } else {
+        a as f64 / b as f64
+    }
+}
+
+fn print_histogram(name: &str, hist: &BTreeMap<usize, usize>) {
+    println!("{}:", name);
+    let total: usize = hist.values().sum();
+    for (value, count) in hist {
+        println!("  {:>4}: {:>6} ({:.3})", value, count, ratio(*count, total));
+    }
+}
+
+/// writes a floor dump to `path`: as JSON if it ends with `.json`, otherwise as a directory
+/// which contains `floor.json` and `.npy` arrays
+pub fn write_floor_dump(dump: &FloorDump, path: &str) -> GameResult<()> {
+    if path.ends_with(".json") {
+        let mut file = File::create(path).into_chained(|| "in write_floor_dump")?;
+        file.write_all(dump.to_json()?.as_bytes())
+            .into_chained(|| "in write_floor_dump")
+    } else {
+        dump.write_bundle(path)
+    }
+}
+
+/// exports a floor of a new game, or the floor of `level` generated by the seed
+pub fn export_floor(config: GameConfig, level: Option<u32>, path: &str) -> GameResult<()> {
+    let dump = match level {
+        Some(level) => {
+            let seed = config.seed.unwrap_or_default();
+            FloorSample::generate(&config, seed, level)
+                .chain_err(|| "in export_floor")?
+                .pop()
+                .ok_or_else(|| ErrorID::InvalidArg.into_with(|| "level has to be positive"))?
+                .dump()
+        }
+        None => config.build()?.export_floor(),
+    };
+    write_floor_dump(&dump, path)
+}
+
+/// generates floors for each seed in `seeds` from level 1 to `levels`, and reports invariant
+/// failures and statistics of them
+pub fn show_floor_stats(config: GameConfig, seeds: Range<u128>, levels: u32) -> GameResult<()> {
+    let mut failures = 0;
+    let mut summaries = BTreeMap::new();
+    let mut room_hist = BTreeMap::new();
+    let mut passage_hist = BTreeMap::new();
+    for seed in seeds {
+        let samples =
+            FloorSample::generate(&config, seed, levels).chain_err(|| "in show_floor_stats")?;
+        for sample in samples {
+            let violations = sample.validate();
+            if !violations.is_empty() {
+                failures += 1;
+            }
+            for v in violations {
+                println!("seed: {} level: {}: {}", seed, sample.level, v);
+            }
+            let stats = FloorStats::from_floor(&sample.floor);
+            *room_hist.entry(stats.rooms).or_insert(0) += 1;
+            for &len in &stats.passage_lengths {
+                *passage_hist.entry(len).or_insert(0) += 1;
+            }
+            summaries
+                .entry(sample.level)
+                .or_insert_with(LevelSummary::default)
+                .add(&stats);
+        }
+    }
+    let floors: usize = summaries.values().map(|s: &LevelSummary| s.floors).sum();
+    println!("floors: {} failures: {}", floors, failures);
+    println!(
+        "{:>5} {:>6} {:>9} {:>9} {:>11} {:>9} {:>6} {:>6}",
+        "level", "rooms", "maze rate", "dark rate", "passage len", "gold", "min", "max"
+    );
+    for (level, s) in &summaries {
+        let gold_sum: u32 = s.gold.iter().sum();
+        println!(
+            "{:>5} {:>6.2} {:>9.3} {:>9.3} {:>11.2} {:>9.2} {:>6} {:>6}",
+            level,
+            ratio(s.rooms, s.floors),
+            ratio(s.mazes, s.rooms),
+            ratio(s.dark_rooms, s.rooms),
+            ratio(s.passage_cells, s.passages),
+            ratio(gold_sum as usize, s.floors),
+            s.gold.iter().min().unwrap_or(&0),
+            s.gold.iter().max().unwrap_or(&0),
+        );
+    }
+    print_histogram("room counts", &room_hist);
+    print_histogram("passage lengths", &passage_hist);
+    Ok(())
+}
@@ -1,3 +1,6 @@
 from .parallel import ParallelRogueEnv
-from .rogue_env import DungeonType, ImageSetting, PlayerState, RogueEnv, StatusFlag
+from .rogue_env import Action, ActionSpace, Direction, DungeonStyle, DungeonType, \
+    EnemiesConfig, Encoding, GameConfig, ImageSetting, InputCode, ItemConfig, PlayerConfig, \
+    PlayerState, RogueConfig, RogueEnv, StatusFlag, System, TrajectoryReader, show_replay, \
+    verify_replay
 from .wrappers import FirstFloorEnv, StairRewardEnv, StairRewardParallel
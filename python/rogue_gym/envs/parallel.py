@@ -1,27 +1,48 @@
 """Provides ParallelRogueEnv, rogue_gym_core::Runtime wrapper as gym environment"""
 from gym import spaces
 import json
-from typing import Dict, Iterable, List, Tuple, Union
-from rogue_gym_python._rogue_gym import ParallelGameState, PlayerState
-from .rogue_env import ImageSetting, RogueEnv
+from numpy import ndarray
+from typing import Dict, Iterable, List, Optional, Tuple, Union
+from rogue_gym_python._rogue_gym import GameConfig, ParallelGameState, PlayerState
+from .rogue_env import DungeonType, ImageSetting, RogueEnv
 
 
 class ParallelRogueEnv:
     """Special executor to exec rogue-gym parallely.
     """
     metadata = RogueEnv.metadata
-    SYMBOLS = RogueEnv.SYMBOLS
     ACTION_MEANINGS = RogueEnv.ACTION_MEANINGS
     ACTIONS = RogueEnv.ACTIONS
     ACTION_LEN = len(ACTIONS)
 
     def __init__(
             self,
-            config_dicts: Iterable[dict],
+            config_dicts: Iterable[Union[dict, GameConfig]],
             max_steps: int = 1000,
             image_setting: ImageSetting = ImageSetting(),
+            viewport: bool = False,
+            threads: Optional[int] = None,
+            describe: bool = False,
     ) -> None:
-        self.game = ParallelGameState(max_steps, [json.dumps(d) for d in config_dicts])
+        """
+        @param threads: number of threads to run environments in,
+            as many as CPUs by default
+        @param describe: make states describable in English, which takes time every step
+        """
+        self.game = ParallelGameState(
+            max_steps,
+            [GameConfig.from_dict(d) if isinstance(d, dict) else d for d in config_dicts],
+            viewport,
+            describe,
+            gray=image_setting.dungeon == DungeonType.GRAY,
+            flag=image_setting.status.value,
+            hist=image_setting.includes_hist,
+            crop=image_setting.crop,
+            multiscale=image_setting.multiscale,
+            encoding=image_setting.encoding.value,
+            separate_status=image_setting.separate_status,
+            threads=threads,
+        )
         self.result = None
         self.max_steps = max_steps
         self.steps = 0
@@ -32,13 +53,33 @@ class ParallelRogueEnv:
         self.states = self.game.states()
         self.num_workers = len(config_dicts)
 
+    def action_masks(self) -> List[ndarray]:
+        """
+        returns legality of ACTIONS in the current states
+        """
+        return [s.action_mask[:self.ACTION_LEN] for s in self.states]
+
     def get_key_to_action(self) -> Dict[str, str]:
         return self.ACION_MEANINGS
 
+    def symbol_table(self) -> List[str]:
+        """
+        returns tiles in each channel of symbol images, resolved from the 'symbols' config
+        """
+        return self.game.symbol_table()
+
     def get_configs(self) -> dict:
         config = self.game.dump_config()
         return json.loads(config)
 
+    def __to_inputs(self, action: Union[Iterable[int], str]) -> List[int]:
+        if isinstance(action, str) and len(action) == self.num_workers:
+            return [ord(c) for c in action]
+        try:
+            return [ord(self.ACTIONS[x]) for x in action]
+        except Exception:
+            raise ValueError("Invalid action: {}".format(action))
+
     def step(
             self,
             action: Union[Iterable[int], str]
@@ -48,24 +89,62 @@ class ParallelRogueEnv:
         @param actions(string):
              key board inputs to rogue(e.g. "hjk" or "hh>")
         """
-        if isinstance(action, str) and len(action) == self.num_workers:
-            action = [ord(c) for c in action]
-        else:
-            try:
-                action = [ord(self.ACTIONS[x]) for x in action]
-            except Exception:
-                raise ValueError("Invalid action: {}".format(action))
-        states = self.game.step(action)
-        rewards = [max(0, after.gold - before.gold) for before, after in zip(self.states, states)]
+        self.step_async(action)
+        return self.step_wait()
+
+    def step_async(self, action: Union[Iterable[int], str]) -> None:
+        """Send actions to all workers without waiting for them"""
+        self.game.step_async(self.__to_inputs(action))
+
+    def step_wait(self) -> Tuple[List[PlayerState], List[float], List[bool], List[dict]]:
+        """Wait for step_async and returns the results like step"""
+        states = self.game.step_wait()
+        rewards = [s.reward for s in states]
         done = [s.is_terminal for s in states]
         self.states = states
-        return self.states, rewards, done, [{}] * self.num_workers
+        return self.states, rewards, done, [s.info for s in states]
 
     def reset(self) -> List[PlayerState]:
         """reset game state"""
         self.states = self.game.reset()
         return self.states
 
+    def step_batched(
+            self,
+            action: Union[Iterable[int], str]
+    ) -> Tuple[ndarray, ndarray, ndarray, dict]:
+        """
+        Same as step, but returns observations of all workers as arrays, which rust side
+        writes directly in place of image_setting.expand + np.stack.
+        Returned arrays are reused and overwritten by the next call.
+        """
+        self.game.step_batched(self.__to_inputs(action))
+        return self.game.images, self.game.rewards, self.game.dones, self.__batched_info()
+
+    def step_wait_batched(self) -> Tuple[ndarray, ndarray, ndarray, dict]:
+        """Wait for step_async and returns the results like step_batched"""
+        self.game.step_wait_batched()
+        return self.game.images, self.game.rewards, self.game.dones, self.__batched_info()
+
+    def reset_batched(self) -> ndarray:
+        """reset game state and returns images of all workers, like step_batched"""
+        self.game.reset_batched()
+        return self.game.images
+
+    def __batched_info(self) -> dict:
+        return {
+            'status': self.game.status,
+            'action_masks': self.game.action_masks[:, :self.ACTION_LEN],
+        }
+
+    def start_recording(self, fname: str) -> None:
+        """Record trajectories of all environments into fname
+        """
+        self.game.start_recording(fname)
+
+    def stop_recording(self) -> None:
+        self.game.stop_recording()
+
     def close(self) -> None:
         self.game.close()
 
@@ -7,7 +7,9 @@ import numpy as np
 from numpy import ndarray
 from typing import Dict, List, NamedTuple, Optional, Tuple, Union
 from rogue_gym_python import _rogue_gym as rogue_gym_inner
-from rogue_gym_python._rogue_gym import GameState, PlayerState
+from rogue_gym_python._rogue_gym import Action, ActionSpace, Direction, DungeonStyle, \
+    EnemiesConfig, GameConfig, GameState, InputCode, ItemConfig, PlayerConfig, PlayerState, \
+    RogueConfig, System, TrajectoryReader
 
 
 class StatusFlag(Flag):
@@ -46,6 +48,17 @@ class StatusFlag(Flag):
         self.__check_input(state)
         return state.gray_image_with_hist(flag=self.value)
 
+    def egocentric_image(
+            self,
+            state: PlayerState,
+            radius: Tuple[int, int],
+            gray: bool = False,
+            hist: bool = False,
+            multiscale: bool = False,
+    ) -> ndarray:
+        self.__check_input(state)
+        return state.egocentric_image(radius, self.value, gray, hist, multiscale)
+
     def status_vec(self, state: PlayerState) -> List[int]:
         self.__check_input(state)
         return state.status_vec(flag=self.value)
@@ -60,28 +73,85 @@ class DungeonType(Enum):
     SYMBOL = 2
 
 
+class Encoding(Enum):
+    # float32 channels
+    FLOAT = 'float'
+    # uint8 map of symbol indices + 1, instead of symbol channels
+    # (0 is for cells out of the map)
+    INDEX = 'index'
+    # uint8 one-hot channels packed along x, which np.unpackbits(axis=-1) restores
+    PACKED = 'packed'
+
+
 class ImageSetting(NamedTuple):
     dungeon: DungeonType = DungeonType.SYMBOL
     status: StatusFlag = StatusFlag.FULL
     includes_hist: bool = False
+    # (radius_x, radius_y) of the crop around the player, or None for the whole map
+    crop: Optional[Tuple[int, int]] = None
+    # appends the downsampled whole map to the crop
+    multiscale: bool = False
+    # integer encodings ignore dungeon and always leave status out of images
+    encoding: Encoding = Encoding.FLOAT
+    # leaves status out of images, to be used as status_vec(state)
+    separate_status: bool = False
+
+    def status_planes(self) -> bool:
+        return self.encoding == Encoding.FLOAT and not self.separate_status
 
     def dim(self, channels: int) -> int:
-        s = channels if self.dungeon == DungeonType.SYMBOL else 1
-        s += self.status.count_one()
-        s += 1 if self.includes_hist else 0
+        scales = 2 if self.crop is not None and self.multiscale else 1
+        if self.encoding == Encoding.INDEX:
+            s = 1
+        elif self.encoding == Encoding.FLOAT and self.dungeon == DungeonType.GRAY:
+            s = 1
+        else:
+            s = channels
+        s *= scales
+        s += self.status.count_one() if self.status_planes() else 0
+        s += scales if self.includes_hist else 0
         return s
 
     def detect_space(self, h: int, w: int, symbols: int) -> gym.Space:
+        if self.crop is not None:
+            h, w = 2 * self.crop[1] + 1, 2 * self.crop[0] + 1
+        if self.encoding == Encoding.FLOAT:
+            high, dtype = 1, np.float32
+        elif self.encoding == Encoding.INDEX:
+            high, dtype = symbols, np.uint8
+        else:
+            high, dtype, w = 255, np.uint8, (w + 7) // 8
         return spaces.box.Box(
             low=0,
-            high=1,
+            high=high,
             shape=(self.dim(symbols), h, w),
-            dtype=np.float32,
+            dtype=dtype,
         )
 
+    def status_vec(self, state: PlayerState) -> List[int]:
+        return self.status.status_vec(state)
+
     def expand(self, state: PlayerState) -> ndarray:
         if not isinstance(state, PlayerState):
             raise TypeError("Needs PlayerState, but {} was given".format(type(state)))
+        if not self.status_planes():
+            return state.encoded_image(
+                flag=self.status.value,
+                gray=self.dungeon == DungeonType.GRAY,
+                hist=self.includes_hist,
+                crop=self.crop,
+                multiscale=self.multiscale,
+                encoding=self.encoding.value,
+                separate_status=self.separate_status,
+            )
+        if self.crop is not None:
+            return self.status.egocentric_image(
+                state,
+                self.crop,
+                gray=self.dungeon == DungeonType.GRAY,
+                hist=self.includes_hist,
+                multiscale=self.multiscale,
+            )
         if self.dungeon == DungeonType.SYMBOL:
             if self.includes_hist:
                 return self.status.symbol_image_with_hist(state)
@@ -94,22 +164,46 @@ class ImageSetting(NamedTuple):
                 return self.status.gray_image(state)
 
 
+def merge_info(infos: List[dict]) -> dict:
+    """Merge infos of consecutive inputs into one"""
+    res = dict(infos[0], reward_terms=dict(infos[0]['reward_terms']))
+    for info in infos[1:]:
+        for key, value in info['reward_terms'].items():
+            res['reward_terms'][key] += value
+        for key in ['messages', 'items', 'kills']:
+            res[key] = res[key] + info[key]
+        for key in ['damage_dealt', 'damage_taken']:
+            res[key] += info[key]
+        change = info['level_change']
+        if change is not None:
+            prev = res['level_change'] or change
+            res['level_change'] = (prev[0], change[1])
+        for key in ['termination', 'grave', 'episode']:
+            if info.get(key) is not None:
+                res[key] = info[key]
+    return res
+
+
+def verify_replay(fname: str) -> Optional[str]:
+    """Re-simulate a replay file and return the first divergence, or None if it's reproduced
+    """
+    with open(fname) as f:
+        return rogue_gym_inner.verify_replay(f.read())
+
+
+def show_replay(fname: str, interval_ms: int = 100) -> None:
+    """Show a replay file written by RogueEnv.save_replay or devui
+    """
+    if not hasattr(rogue_gym_inner, 'replay_file'):
+        raise RuntimeError('Currently replay is only supported on UNIX')
+    with open(fname) as f:
+        rogue_gym_inner.replay_file(f.read(), interval_ms)
+    print()
+
+
 class RogueEnv(gym.Env):
     metadata = {'render.modes': ['human', 'ascii']}
 
-    # defined in core/src/tile.rs
-    SYMBOLS = [
-        ' ', '@', '#', '.', '-',
-        '%', '+', '^', '!', '?',
-        ']', ')', '/', '*', ':',
-        '=', ',', 'A', 'B', 'C',
-        'D', 'E', 'F', 'G', 'H',
-        'I', 'J', 'K', 'L', 'M',
-        'N', 'O', 'P', 'Q', 'R',
-        'S', 'T', 'U', 'V', 'W',
-        'X', 'Y', 'Z',
-    ]
-
     # Same as data/keymaps/ai.json
     ACTION_MEANINGS = {
         '.': 'NO_OPERATION',
@@ -135,21 +229,32 @@ class RogueEnv(gym.Env):
     def __init__(
             self,
             config_path: Optional[str] = None,
-            config_dict: dict = {},
+            config_dict: Union[dict, GameConfig] = {},
             max_steps: int = 1000,
             image_setting: ImageSetting = ImageSetting(),
+            viewport: bool = False,
+            actions: Optional[ActionSpace] = None,
+            describe: bool = False,
             **kwargs,
     ) -> None:
+        """
+        Unknown keys in config_dict or kwargs raise ValueError with their paths
+        (e.g. 'dungeon.room_num'), while config_path is parsed as is.
+        """
         super().__init__()
         if config_path:
             with open(config_path, 'r') as f:
                 config = f.read()
         else:
-            config_dict.update(kwargs)
-            config = json.dumps(config_dict)
-        self.game = GameState(max_steps, config)
+            if isinstance(config_dict, GameConfig):
+                config_dict = config_dict.to_dict()
+            config = GameConfig.from_dict(dict(config_dict, **kwargs))
+        self.game = GameState(max_steps, config, viewport, describe)
         self.result = None
-        self.action_space = spaces.discrete.Discrete(self.ACTION_LEN)
+        # a custom action space replaces ACTIONS
+        self.actions = actions
+        n_actions = self.ACTION_LEN if actions is None else len(actions)
+        self.action_space = spaces.discrete.Discrete(n_actions)
         self.observation_space = \
             image_setting.detect_space(*self.game.screen_size(), self.game.symbols())
         self.image_setting = image_setting
@@ -160,13 +265,29 @@ class RogueEnv(gym.Env):
 
     def screen_size(self) -> Tuple[int, int]:
         """
-        returns (height, width)
+        returns (height, width) of observations,
+        which is the viewport size if viewport=True otherwise the dungeon size
         """
         return self.game.screen_size()
 
+    def action_mask(self) -> ndarray:
+        """
+        returns legality of ACTIONS, or actions of the custom action space, in the current state
+        """
+        if self.actions is not None:
+            return np.array(self.game.action_mask_of(self.actions), dtype=bool)
+        return self.result.action_mask[:self.ACTION_LEN]
+
     def get_key_to_action(self) -> Dict[str, str]:
         return self.ACION_MEANINGS
 
+    def symbol_table(self) -> List[str]:
+        """
+        returns tiles in each channel of symbol images, resolved from the 'symbols' config
+        (e.g. [' ', '@', '#', '.', '-|', ...])
+        """
+        return self.game.symbol_table()
+
     def get_dungeon(self) -> List[str]:
         return self.result.dungeon
 
@@ -182,6 +303,21 @@ class RogueEnv(gym.Env):
         with open(fname, 'w') as f:
             f.write(self.game.dump_history())
 
+    def save_replay(self, fname: str) -> None:
+        """Save the config, seed and actions with state hashes, which can be verified
+        by verify_replay
+        """
+        with open(fname, 'w') as f:
+            f.write(self.game.dump_replay())
+
+    def start_recording(self, fname: str) -> None:
+        """Record trajectories into fname, readable by TrajectoryReader
+        """
+        self.game.start_recording(fname)
+
+    def stop_recording(self) -> None:
+        self.game.stop_recording()
+
     def replay(self, interval_ms: int = 100) -> None:
         if not hasattr(rogue_gym_inner, 'replay'):
             raise RuntimeError('Currently replay is only supported on UNIX')
@@ -205,29 +341,45 @@ class RogueEnv(gym.Env):
             setting = self.image_setting
         return setting.expand(state)
 
-    def __step_str(self, actions: str) -> int:
-        for act in actions:
-            self.game.react(ord(act))
-        return len(actions)
+    def __step_str(self, actions: str) -> dict:
+        return merge_info([self.game.react(ord(act)) for act in actions])
+
+    def __step_inputs(self, inputs: Union[Action, InputCode, List[InputCode]]) -> dict:
+        return merge_info(self.game.react_inputs(inputs))
 
-    def step(self, action: Union[int, str]) -> Tuple[PlayerState, float, bool, dict]:
+    def step(
+            self,
+            action: Union[int, str, Action, InputCode, List[Union[Action, InputCode]]],
+    ) -> Tuple[PlayerState, float, bool, dict]:
         """
         Do action.
         @param actions(string):
              key board inputs to rogue(e.g. "hjk" or "hh>")
+        @param actions(int):
+             index of ACTIONS, or the custom action space if given
+        @param actions(Action, InputCode or a list of them):
+             typed inputs(e.g. Action.move(Direction.Left))
+        Reward is the weighted sum of terms specified by 'reward' in the config,
+        and info['reward_terms'] contains each term.
+        info also has messages, items picked up, enemies killed, damage dealt and taken,
+        level changes and the cause of termination('death' or 'max_steps').
+        On terminal steps, info['episode'] has aggregates of the episode.
         """
-        gold_before = self.result.gold
         if isinstance(action, str):
-            self.__step_str(action)
+            info = self.__step_str(action)
+        elif isinstance(action, (Action, InputCode, list, tuple)):
+            info = self.__step_inputs(action)
+        elif self.actions is not None:
+            info = self.__step_inputs(self.actions[action])
         else:
             try:
                 s = self.ACTIONS[action]
-                self.__step_str(s)
+                info = self.__step_str(s)
             except Exception as e:
                 raise ValueError("Invalid action: {} causes {}".format(action, e))
         self.__cache()
-        reward = self.result.gold - gold_before
-        return self.result, reward, self.result.is_terminal, {}
+        reward = sum(info['reward_terms'].values())
+        return self.result, reward, self.result.is_terminal, info
 
     def seed(self, seed: int) -> None:
         """
@@ -0,0 +1,415 @@
+//! typed actions and input codes, and discrete action spaces over them
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyIndexError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use rogue_gym_core::character::Action;
+use rogue_gym_core::dungeon::Direction;
+use rogue_gym_core::env::AI_ACTIONS;
+use rogue_gym_core::input::{InputCode, Key, KeyMap, System};
+use rogue_gym_core::RunTime;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+fn hash_of(t: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    t.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Directions of movements, where Up is north
+#[pyclass(name = "Direction")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PyDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+    LeftUp,
+    RightUp,
+    LeftDown,
+    RightDown,
+}
+
+impl From<PyDirection> for Direction {
+    fn from(d: PyDirection) -> Direction {
+        match d {
+            PyDirection::Up => Direction::Up,
+            PyDirection::Down => Direction::Down,
+            PyDirection::Left => Direction::Left,
+            PyDirection::Right => Direction::Right,
+            PyDirection::LeftUp => Direction::LeftUp,
+            PyDirection::RightUp => Direction::RightUp,
+            PyDirection::LeftDown => Direction::LeftDown,
+            PyDirection::RightDown => Direction::RightDown,
+        }
+    }
+}
+
+impl PyDirection {
+    fn from_core(d: Direction) -> Option<Self> {
+        Some(match d {
+            Direction::Up => PyDirection::Up,
+            Direction::Down => PyDirection::Down,
+            Direction::Left => PyDirection::Left,
+            Direction::Right => PyDirection::Right,
+            Direction::LeftUp => PyDirection::LeftUp,
+            Direction::RightUp => PyDirection::RightUp,
+            Direction::LeftDown => PyDirection::LeftDown,
+            Direction::RightDown => PyDirection::RightDown,
+            Direction::Stay => return None,
+        })
+    }
+}
+
+/// System inputs, used in dialogs like the inventory or quitting
+#[pyclass(name = "System")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PySystem {
+    Cancel,
+    Continue,
+    Enter,
+    Inventory,
+    No,
+    Save,
+    Quit,
+    Yes,
+}
+
+impl From<PySystem> for System {
+    fn from(s: PySystem) -> System {
+        match s {
+            PySystem::Cancel => System::Cancel,
+            PySystem::Continue => System::Continue,
+            PySystem::Enter => System::Enter,
+            PySystem::Inventory => System::Inventory,
+            PySystem::No => System::No,
+            PySystem::Save => System::Save,
+            PySystem::Quit => System::Quit,
+            PySystem::Yes => System::Yes,
+        }
+    }
+}
+
+impl From<System> for PySystem {
+    fn from(s: System) -> PySystem {
+        match s {
+            System::Cancel => PySystem::Cancel,
+            System::Continue => PySystem::Continue,
+            System::Enter => PySystem::Enter,
+            System::Inventory => PySystem::Inventory,
+            System::No => PySystem::No,
+            System::Save => PySystem::Save,
+            System::Quit => PySystem::Quit,
+            System::Yes => PySystem::Yes,
+        }
+    }
+}
+
+const DIRECTIONS: [Direction; 8] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+    Direction::LeftUp,
+    Direction::RightUp,
+    Direction::LeftDown,
+    Direction::RightDown,
+];
+
+/// An action of the player, made by static methods like `Action.move(Direction.Left)`
+#[pyclass(name = "Action")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PyAction(pub(crate) Action);
+
+#[pymethods]
+impl PyAction {
+    #[staticmethod]
+    fn r#move(d: PyDirection) -> Self {
+        PyAction(Action::Move(d.into()))
+    }
+    /// Moves until something interesting happens
+    #[staticmethod]
+    fn move_until(d: PyDirection) -> Self {
+        PyAction(Action::MoveUntil(d.into()))
+    }
+    /// Runs along passages, turning at corners and stopping at forks
+    #[staticmethod]
+    fn run(d: PyDirection) -> Self {
+        PyAction(Action::Run(d.into()))
+    }
+    /// Runs until something interesting comes into view
+    #[staticmethod]
+    fn run_cautiously(d: PyDirection) -> Self {
+        PyAction(Action::RunCautiously(d.into()))
+    }
+    #[staticmethod]
+    fn up_stair() -> Self {
+        PyAction(Action::UpStair)
+    }
+    #[staticmethod]
+    fn down_stair() -> Self {
+        PyAction(Action::DownStair)
+    }
+    #[staticmethod]
+    fn search() -> Self {
+        PyAction(Action::Search)
+    }
+    #[staticmethod]
+    fn no_op() -> Self {
+        PyAction(Action::NoOp)
+    }
+    /// All actions, with all directions
+    #[staticmethod]
+    fn all() -> Vec<Self> {
+        let moves: [fn(Direction) -> Action; 4] = [
+            Action::Move,
+            Action::MoveUntil,
+            Action::Run,
+            Action::RunCautiously,
+        ];
+        let others = [
+            Action::UpStair,
+            Action::DownStair,
+            Action::Search,
+            Action::NoOp,
+        ];
+        moves
+            .iter()
+            .flat_map(|f| DIRECTIONS.iter().map(move |&d| f(d)))
+            .chain(others.iter().cloned())
+            .map(PyAction)
+            .collect()
+    }
+    /// Name of the action, like 'move' or 'search'
+    #[getter]
+    fn kind(&self) -> &'static str {
+        match self.0 {
+            Action::Move(_) => "move",
+            Action::MoveUntil(_) => "move_until",
+            Action::Run(_) => "run",
+            Action::RunCautiously(_) => "run_cautiously",
+            Action::UpStair => "up_stair",
+            Action::DownStair => "down_stair",
+            Action::Search => "search",
+            Action::NoOp => "no_op",
+        }
+    }
+    #[getter]
+    fn direction(&self) -> Option<PyDirection> {
+        match self.0 {
+            Action::Move(d) | Action::MoveUntil(d) | Action::Run(d) | Action::RunCautiously(d) => {
+                PyDirection::from_core(d)
+            }
+            _ => None,
+        }
+    }
+    fn __repr__(&self) -> String {
+        match self.direction() {
+            Some(d) => format!("Action.{}(Direction.{:?})", self.kind(), d),
+            None => format!("Action.{}()", self.kind()),
+        }
+    }
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python) -> PyObject {
+        match op {
+            CompareOp::Eq => (self == other).into_py(py),
+            CompareOp::Ne => (self != other).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+    fn __hash__(&self) -> u64 {
+        hash_of(&self.0)
+    }
+}
+
+/// An input to the game, which is an action, a system input or both
+#[pyclass(name = "InputCode")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PyInputCode(pub(crate) InputCode);
+
+#[pymethods]
+impl PyInputCode {
+    #[staticmethod]
+    fn act(action: PyAction) -> Self {
+        PyInputCode(InputCode::Act(action.0))
+    }
+    #[staticmethod]
+    fn sys(sys: PySystem) -> Self {
+        PyInputCode(InputCode::Sys(sys.into()))
+    }
+    #[staticmethod]
+    fn both(action: PyAction, sys: PySystem) -> Self {
+        PyInputCode(InputCode::Both {
+            act: action.0,
+            sys: sys.into(),
+        })
+    }
+    /// The input code of the key in the AI keymap(e.g. 'h'), or None
+    #[staticmethod]
+    fn from_key(key: char) -> Option<Self> {
+        KeyMap::ai().get(Key::Char(key)).map(PyInputCode)
+    }
+    #[getter]
+    fn action(&self) -> Option<PyAction> {
+        match self.0 {
+            InputCode::Act(act) | InputCode::Both { act, .. } => Some(PyAction(act)),
+            InputCode::Sys(_) => None,
+        }
+    }
+    #[getter]
+    fn system(&self) -> Option<PySystem> {
+        match self.0 {
+            InputCode::Sys(sys) | InputCode::Both { sys, .. } => Some(sys.into()),
+            InputCode::Act(_) => None,
+        }
+    }
+    fn __repr__(&self) -> String {
+        match (self.action(), self.system()) {
+            (Some(act), None) => format!("InputCode.act({})", act.__repr__()),
+            (None, Some(sys)) => format!("InputCode.sys(System.{:?})", sys),
+            (Some(act), Some(sys)) => {
+                format!("InputCode.both({}, System.{:?})", act.__repr__(), sys)
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python) -> PyObject {
+        match op {
+            CompareOp::Eq => (self == other).into_py(py),
+            CompareOp::Ne => (self != other).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+    fn __hash__(&self) -> u64 {
+        hash_of(&self.0)
+    }
+}
+
+/// converts an `Action` or `InputCode` object into an input code
+pub(crate) fn extract_input(obj: &PyAny) -> PyResult<InputCode> {
+    if let Ok(action) = obj.extract::<PyAction>() {
+        Ok(InputCode::Act(action.0))
+    } else if let Ok(code) = obj.extract::<PyInputCode>() {
+        Ok(code.0)
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "Needs Action or InputCode, but {} was given",
+            obj.get_type().name()?
+        )))
+    }
+}
+
+/// converts an `Action`, `InputCode` or a sequence of them into input codes
+pub(crate) fn extract_inputs(obj: &PyAny) -> PyResult<Vec<InputCode>> {
+    if let Ok(input) = extract_input(obj) {
+        return Ok(vec![input]);
+    }
+    let inputs = obj
+        .iter()
+        .map_err(|_| {
+            PyTypeError::new_err("Needs Action, InputCode or a sequence of them".to_owned())
+        })?
+        .map(|item| extract_input(item?))
+        .collect::<PyResult<Vec<_>>>()?;
+    if inputs.is_empty() {
+        return Err(PyValueError::new_err("Empty sequence of inputs"));
+    }
+    Ok(inputs)
+}
+
+/// A discrete action space, where each action is a sequence of inputs processed
+/// in one step, e.g. `[InputCode.sys(System.Inventory), InputCode.sys(System.Cancel)]`
+#[pyclass]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ActionSpace {
+    pub(crate) entries: Vec<Vec<InputCode>>,
+}
+
+impl ActionSpace {
+    pub(crate) fn entry(&self, i: usize) -> PyResult<&[InputCode]> {
+        self.entries.get(i).map(|v| v.as_slice()).ok_or_else(|| {
+            PyIndexError::new_err(format!(
+                "Action {} is out of the space of {}",
+                i,
+                self.entries.len()
+            ))
+        })
+    }
+    /// actions of keys in the AI keymap, or the first key not in it
+    pub(crate) fn with_keys(keys: &str) -> Result<Self, char> {
+        let keymap = KeyMap::ai();
+        let entries = keys
+            .chars()
+            .map(|c| keymap.get(Key::Char(c)).map(|code| vec![code]).ok_or(c))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ActionSpace { entries })
+    }
+    /// legality of each action, which is legality of its first input
+    pub(crate) fn mask(&self, runtime: &RunTime) -> Vec<bool> {
+        self.entries
+            .iter()
+            .map(|inputs| runtime.is_legal(inputs[0]))
+            .collect()
+    }
+}
+
+#[pymethods]
+impl ActionSpace {
+    /// `actions` is a list of `Action`, `InputCode`, or sequences of them
+    #[new]
+    fn __new__(actions: &PyAny) -> PyResult<Self> {
+        let entries = actions
+            .iter()?
+            .map(|obj| extract_inputs(obj?))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(ActionSpace { entries })
+    }
+    /// All actions of the AI keymap, in the order of `PlayerState.action_mask`
+    #[staticmethod]
+    fn ai() -> Self {
+        ActionSpace {
+            entries: AI_ACTIONS
+                .iter()
+                .map(|&act| vec![InputCode::Act(act)])
+                .collect(),
+        }
+    }
+    /// Actions of keys in the AI keymap, like `ActionSpace.from_keys('hjkl')`
+    #[staticmethod]
+    fn from_keys(keys: &str) -> PyResult<Self> {
+        ActionSpace::with_keys(keys)
+            .map_err(|c| PyValueError::new_err(format!("Invalid key {:?}", c)))
+    }
+    /// Index of the action, which is an `Action`, `InputCode` or a sequence of them
+    fn index(&self, action: &PyAny) -> PyResult<Option<usize>> {
+        let inputs = extract_inputs(action)?;
+        Ok(self.entries.iter().position(|e| *e == inputs))
+    }
+    fn __len__(&self) -> usize {
+        self.entries.len()
+    }
+    fn __getitem__(&self, i: usize) -> PyResult<Vec<PyInputCode>> {
+        Ok(self.entry(i)?.iter().map(|&c| PyInputCode(c)).collect())
+    }
+    fn __repr__(&self) -> String {
+        format!("ActionSpace(len={})", self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn ai_space() {
+        let space = ActionSpace::ai();
+        let keys: String = KeyMap::AI_KEYS.iter().collect();
+        assert_eq!(ActionSpace::with_keys(&keys).unwrap(), space);
+        assert_eq!(PyAction::all().len(), 36);
+        let runtime = rogue_gym_core::GameConfig::default().build().unwrap();
+        let mask = space.mask(&runtime);
+        let keys = KeyMap::AI_KEYS.iter().map(|&c| Key::Char(c));
+        assert_eq!(ActionSpace::with_keys("hjx"), Err('x'));
+        let mut runtime = runtime;
+        runtime.keymap = KeyMap::ai();
+        assert_eq!(mask, runtime.action_mask(keys));
+    }
+}
@@ -0,0 +1,458 @@
+//! typed config classes, which check types and unknown fields with their paths
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::PyClass;
+use pyresult_with;
+use rogue_gym_core::character::{enemies, player};
+use rogue_gym_core::dungeon::{rogue, DungeonStyle};
+use rogue_gym_core::{item, GameConfig};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// a pyclass mirroring a config struct of the core
+pub(crate) trait ConfigClass: PyClass + Sized {
+    type Inner: Clone + Default + PartialEq + Serialize;
+    fn from_inner(py: Python, inner: Self::Inner) -> PyResult<Self>;
+    fn to_inner(&self, py: Python) -> Self::Inner;
+    /// sets fields in `dict`, where `path` is the path of `self` used in errors
+    fn update(&mut self, py: Python, dict: &PyDict, path: &str) -> PyResult<()>;
+    fn with_dict(py: Python, dict: Option<&PyDict>, path: &str) -> PyResult<Self> {
+        let mut res = Self::from_inner(py, Self::Inner::default())?;
+        if let Some(dict) = dict {
+            res.update(py, dict, path)?;
+        }
+        Ok(res)
+    }
+}
+
+fn join_path(parent: &str, child: &str) -> String {
+    if parent.is_empty() || child.is_empty() || child.starts_with('[') {
+        format!("{}{}", parent, child)
+    } else {
+        format!("{}.{}", parent, child)
+    }
+}
+
+fn ignored_path(path: &serde_ignored::Path, buf: &mut String) {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => {}
+        Path::Seq { parent, index } => {
+            ignored_path(parent, buf);
+            buf.push_str(&format!("[{}]", index));
+        }
+        Path::Map { parent, key } => {
+            ignored_path(parent, buf);
+            if !buf.is_empty() {
+                buf.push('.');
+            }
+            buf.push_str(key);
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => ignored_path(parent, buf),
+    }
+}
+
+fn unknown_field(path: &str, expected: &[&str]) -> PyErr {
+    if expected.is_empty() {
+        PyValueError::new_err(format!("Unknown field {}", path))
+    } else {
+        PyValueError::new_err(format!(
+            "Unknown field {}, expected one of {}",
+            path,
+            expected.join(", ")
+        ))
+    }
+}
+
+/// converts a value of the core into python objects, via json
+pub(crate) fn to_py<T: Serialize>(py: Python, value: &T) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(py.import("json")?.call_method1("loads", (json,))?.into())
+}
+
+/// converts python objects into a value of the core, via json
+pub(crate) fn from_py<T: DeserializeOwned>(obj: &PyAny, path: &str) -> PyResult<T> {
+    let json: String = obj
+        .py()
+        .import("json")?
+        .call_method1("dumps", (obj,))
+        .and_then(|s| s.extract())
+        .map_err(|e| PyTypeError::new_err(format!("{}: {}", path, e)))?;
+    let mut unknown = None;
+    let res = {
+        let mut de = serde_json::Deserializer::from_str(&json);
+        let mut callback = |p: serde_ignored::Path| {
+            if unknown.is_none() {
+                let mut buf = String::new();
+                ignored_path(&p, &mut buf);
+                unknown = Some(buf);
+            }
+        };
+        serde_path_to_error::deserialize(serde_ignored::Deserializer::new(&mut de, &mut callback))
+    };
+    let value = res.map_err(|e| {
+        let inner = e.path().to_string();
+        let inner = if inner == "." { "" } else { &inner };
+        // positions in the json made from python objects don't make sense
+        let msg = e.inner().to_string();
+        let msg = msg.rfind(" at line ").map_or(&*msg, |i| &msg[..i]);
+        PyValueError::new_err(format!("{}: {}", join_path(path, inner), msg))
+    })?;
+    match unknown {
+        Some(p) => Err(unknown_field(&join_path(path, &p), &[])),
+        None => Ok(value),
+    }
+}
+
+fn extract_dict<'a>(obj: &'a PyAny, path: &str) -> PyResult<&'a PyDict> {
+    obj.downcast()
+        .map_err(|_| PyTypeError::new_err(format!("{}: needs dict", path)))
+}
+
+/// defines a config class, where `field, setter => path;` makes an attribute `field`
+/// converted via json, `field, setter => Class;` makes a nested config class,
+/// and `methods` adds extra python methods
+macro_rules! config_class {
+    (
+        $(#[$meta:meta])*
+        $name:ident($inner:ty) {
+            $($field:ident, $setter:ident => $($path:ident).+;)*
+        }
+        nested {
+            $($nfield:ident, $nsetter:ident => $nty:ty;)*
+        }
+        $(methods {
+            $($method:item)*
+        })?
+    ) => {
+        $(#[$meta])*
+        pub(crate) struct $name {
+            inner: $inner,
+            $($nfield: Py<$nty>,)*
+        }
+
+        impl $name {
+            const FIELDS: &'static [&'static str] =
+                &[$(stringify!($field),)* $(stringify!($nfield),)*];
+        }
+
+        impl ConfigClass for $name {
+            type Inner = $inner;
+            #[allow(unused_variables)]
+            fn from_inner(py: Python, inner: $inner) -> PyResult<Self> {
+                Ok($name {
+                    $($nfield: Py::new(py, <$nty>::from_inner(py, inner.$nfield.clone())?)?,)*
+                    inner,
+                })
+            }
+            #[allow(unused_variables)]
+            fn to_inner(&self, py: Python) -> $inner {
+                #[allow(unused_mut)]
+                let mut inner = self.inner.clone();
+                $(inner.$nfield = self.$nfield.borrow(py).to_inner(py);)*
+                inner
+            }
+            #[allow(unused_variables)]
+            fn update(&mut self, py: Python, dict: &PyDict, path: &str) -> PyResult<()> {
+                for (key, value) in dict {
+                    let key: &str = key.extract()?;
+                    let path = join_path(path, key);
+                    match key {
+                        $(stringify!($field) => self.inner.$($path).+ = from_py(value, &path)?,)*
+                        $(stringify!($nfield) => self
+                            .$nfield
+                            .borrow_mut(py)
+                            .update(py, extract_dict(value, &path)?, &path)?,)*
+                        _ => return Err(unknown_field(&path, Self::FIELDS)),
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            #[pyo3(signature = (**kwargs))]
+            fn __new__(py: Python, kwargs: Option<&PyDict>) -> PyResult<Self> {
+                Self::with_dict(py, kwargs, "")
+            }
+            /// Makes the config from a dict, raising ValueError for unknown fields
+            #[staticmethod]
+            fn from_dict(py: Python, dict: &PyDict) -> PyResult<Self> {
+                Self::with_dict(py, Some(dict), "")
+            }
+            fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+                to_py(py, &self.to_inner(py))
+            }
+            $(
+                #[getter]
+                fn $field(&self, py: Python) -> PyResult<PyObject> {
+                    to_py(py, &self.inner.$($path).+)
+                }
+                #[setter]
+                fn $setter(&mut self, value: &PyAny) -> PyResult<()> {
+                    self.inner.$($path).+ = from_py(value, stringify!($field))?;
+                    Ok(())
+                }
+            )*
+            $(
+                #[getter]
+                fn $nfield(&self, py: Python) -> Py<$nty> {
+                    self.$nfield.clone_ref(py)
+                }
+                /// accepts the config class or a dict
+                #[setter]
+                fn $nsetter(&mut self, py: Python, value: &PyAny) -> PyResult<()> {
+                    self.$nfield = match value.extract::<Py<$nty>>() {
+                        Ok(obj) => obj,
+                        Err(_) => {
+                            let path = stringify!($nfield);
+                            let dict = extract_dict(value, path)?;
+                            Py::new(py, <$nty>::with_dict(py, Some(dict), path)?)?
+                        }
+                    };
+                    Ok(())
+                }
+            )*
+            $($($method)*)?
+            fn __repr__(&self, py: Python) -> String {
+                let json = serde_json::to_string(&self.to_inner(py)).unwrap_or_default();
+                format!("{}({})", stringify!($name).trim_start_matches("Py"), json)
+            }
+            fn __richcmp__(&self, py: Python, other: &PyAny, op: CompareOp) -> PyObject {
+                let other = match other.extract::<PyRef<Self>>() {
+                    Ok(other) => other.to_inner(py),
+                    Err(_) => return py.NotImplemented(),
+                };
+                match op {
+                    CompareOp::Eq => (self.to_inner(py) == other).into_py(py),
+                    CompareOp::Ne => (self.to_inner(py) != other).into_py(py),
+                    _ => py.NotImplemented(),
+                }
+            }
+        }
+    };
+}
+
+config_class! {
+    /// Configuration of rogue-like dungeons
+    #[pyclass(name = "RogueConfig")]
+    PyRogueConfig(rogue::Config) {
+        room_num_x, set_room_num_x => room_num_x;
+        room_num_y, set_room_num_y => room_num_y;
+        min_room_size, set_min_room_size => min_room_size;
+        enable_trap, set_enable_trap => enable_trap;
+        max_empty_rooms, set_max_empty_rooms => max_empty_rooms;
+        amulet_level, set_amulet_level => amulet_level;
+        maze_rate_inv, set_maze_rate_inv => maze_rate_inv;
+        dark_level, set_dark_level => dark_level;
+        hidden_passage_rate_inv, set_hidden_passage_rate_inv => hidden_passage_rate_inv;
+        locked_door_rate_inv, set_locked_door_rate_inv => locked_door_rate_inv;
+        max_extra_edges, set_max_extra_edges => max_extra_edges;
+        door_unlock_rate_inv, set_door_unlock_rate_inv => door_unlock_rate_inv;
+        passage_unlock_rate_inv, set_passage_unlock_rate_inv => passage_unlock_rate_inv;
+        treasure_room, set_treasure_room => treasure_room;
+        monster_zoo, set_monster_zoo => monster_zoo;
+    }
+    nested {}
+}
+
+config_class! {
+    /// Configuration of items
+    #[pyclass(name = "ItemConfig")]
+    PyItemConfig(item::Config) {
+        armor, set_armor => armor;
+        gold, set_gold => gold;
+        weapon, set_weapon => weapon;
+    }
+    nested {}
+}
+
+config_class! {
+    /// Configuration of the player
+    #[pyclass(name = "PlayerConfig")]
+    PyPlayerConfig(player::Config) {
+        exps, set_exps => level.exps;
+        hunger_time, set_hunger_time => hunger_time;
+        init_hp, set_init_hp => init_hp;
+        init_str, set_init_str => init_str;
+        max_items, set_max_items => max_items;
+        init_items, set_init_items => init_items;
+        heal_threshold, set_heal_threshold => heal_threshold;
+    }
+    nested {}
+}
+
+config_class! {
+    /// Configuration of enemies, where `enemies` has builtin indices or custom enemies
+    #[pyclass(name = "EnemiesConfig")]
+    PyEnemiesConfig(enemies::Config) {
+        enemies, set_enemies => enemies;
+        appear_rate_gold, set_appear_rate_gold => appear_rate_gold;
+        appear_rate_nogold, set_appear_rate_nogold => appear_rate_nogold;
+    }
+    nested {}
+}
+
+config_class! {
+    /// Game configuration, same as the json config
+    #[pyclass(name = "GameConfig")]
+    PyGameConfig(GameConfig) {
+        width, set_width => width;
+        height, set_height => height;
+        dungeon_width, set_dungeon_width => dungeon_width;
+        dungeon_height, set_dungeon_height => dungeon_height;
+        seed, set_seed => seed;
+        seed_range, set_seed_range => seed_range;
+        keymap, set_keymap => keymap;
+        reward, set_reward => reward;
+        symbols, set_symbols => symbols;
+        hide_dungeon, set_hide_dungeon => hide_dungeon;
+    }
+    nested {
+        dungeon, set_dungeon => PyDungeonStyle;
+        item, set_item => PyItemConfig;
+        player, set_player => PyPlayerConfig;
+        enemies, set_enemies => PyEnemiesConfig;
+    }
+    methods {
+        /// Returns problems of the config like `['dungeon.room_num_x: must be positive']`
+        fn validate(&self, py: Python) -> Vec<String> {
+            self.to_inner(py)
+                .validate()
+                .into_iter()
+                .map(|e| e.to_string())
+                .collect()
+        }
+    }
+}
+
+const ROGUE_STYLE: &str = "rogue";
+
+/// Style of the dungeon and its configuration, where only 'rogue' is implemented
+#[pyclass(name = "DungeonStyle")]
+pub(crate) struct PyDungeonStyle {
+    rogue: Py<PyRogueConfig>,
+}
+
+impl ConfigClass for PyDungeonStyle {
+    type Inner = DungeonStyle;
+    fn from_inner(py: Python, inner: DungeonStyle) -> PyResult<Self> {
+        match inner {
+            DungeonStyle::Rogue(config) => Ok(PyDungeonStyle {
+                rogue: Py::new(py, PyRogueConfig::from_inner(py, config)?)?,
+            }),
+            style => Err(PyValueError::new_err(format!(
+                "Dungeon style {:?} is not implemented",
+                style
+            ))),
+        }
+    }
+    fn to_inner(&self, py: Python) -> DungeonStyle {
+        DungeonStyle::Rogue(self.rogue.borrow(py).to_inner(py))
+    }
+    /// the style and fields of the style's config are in the same dict
+    fn update(&mut self, py: Python, dict: &PyDict, path: &str) -> PyResult<()> {
+        let rest = PyDict::new(py);
+        for (key, value) in dict {
+            if key.extract::<&str>()? == "style" {
+                let path = join_path(path, "style");
+                let style: String = from_py(value, &path)?;
+                if style != ROGUE_STYLE {
+                    return Err(PyValueError::new_err(format!(
+                        "{}: only {} is implemented, but {} was given",
+                        path, ROGUE_STYLE, style
+                    )));
+                }
+            } else {
+                rest.set_item(key, value)?;
+            }
+        }
+        self.rogue.borrow_mut(py).update(py, rest, path)
+    }
+}
+
+#[pymethods]
+impl PyDungeonStyle {
+    #[new]
+    #[pyo3(signature = (**kwargs))]
+    fn __new__(py: Python, kwargs: Option<&PyDict>) -> PyResult<Self> {
+        Self::with_dict(py, kwargs, "")
+    }
+    #[staticmethod]
+    fn from_dict(py: Python, dict: &PyDict) -> PyResult<Self> {
+        Self::with_dict(py, Some(dict), "")
+    }
+    fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        to_py(py, &self.to_inner(py))
+    }
+    #[getter]
+    fn style(&self) -> &'static str {
+        ROGUE_STYLE
+    }
+    #[getter]
+    fn rogue(&self, py: Python) -> Py<PyRogueConfig> {
+        self.rogue.clone_ref(py)
+    }
+    #[setter]
+    fn set_rogue(&mut self, rogue: Py<PyRogueConfig>) {
+        self.rogue = rogue;
+    }
+    fn __repr__(&self, py: Python) -> String {
+        let json = serde_json::to_string(&self.to_inner(py)).unwrap_or_default();
+        format!("DungeonStyle({})", json)
+    }
+    fn __richcmp__(&self, py: Python, other: &PyAny, op: CompareOp) -> PyObject {
+        let other = match other.extract::<PyRef<Self>>() {
+            Ok(other) => other.to_inner(py),
+            Err(_) => return py.NotImplemented(),
+        };
+        match op {
+            CompareOp::Eq => (self.to_inner(py) == other).into_py(py),
+            CompareOp::Ne => (self.to_inner(py) != other).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+}
+
+/// converts a json string or a `GameConfig` into the config of the core
+pub(crate) fn extract_config(obj: &PyAny) -> PyResult<GameConfig> {
+    if let Ok(json) = obj.extract::<&str>() {
+        return pyresult_with(GameConfig::from_json(json), "Failed to parse config");
+    }
+    match obj.extract::<PyRef<PyGameConfig>>() {
+        Ok(config) => Ok(config.to_inner(obj.py())),
+        Err(_) => Err(PyTypeError::new_err(format!(
+            "Needs json string or GameConfig, but {} was given",
+            obj.get_type().name()?
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    #[test]
+    fn paths() {
+        assert_eq!(join_path("", "dungeon"), "dungeon");
+        assert_eq!(join_path("dungeon", "room_num_x"), "dungeon.room_num_x");
+        assert_eq!(join_path("enemies", "[3].name"), "enemies[3].name");
+        assert_eq!(join_path("seed", ""), "seed");
+        let json = r#"{"a": [{"rate_inv": 1}, {"rate": 2}]}"#;
+        let mut ignored = vec![];
+        let _: HashMap<String, Vec<rogue::SpecialRoomConfig>> =
+            serde_ignored::deserialize(&mut serde_json::Deserializer::from_str(json), |p| {
+                let mut buf = String::new();
+                ignored_path(&p, &mut buf);
+                ignored.push(buf);
+            })
+            .unwrap();
+        assert_eq!(ignored, vec!["a[1].rate".to_owned()]);
+    }
+}
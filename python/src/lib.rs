@@ -1,5 +1,6 @@
 #[macro_use]
 extern crate failure;
+#[macro_use]
 extern crate ndarray;
 extern crate numpy;
 extern crate pyo3;
@@ -7,25 +8,50 @@ extern crate rect_iter;
 extern crate rogue_gym_core;
 #[cfg(unix)]
 extern crate rogue_gym_devui;
+#[macro_use]
+extern crate serde;
+extern crate serde_ignored;
+extern crate serde_json;
+extern crate serde_path_to_error;
 
+mod actions;
+mod config;
 mod fearures;
 mod state_impls;
 mod thread_impls;
 
-use fearures::{MessageFlagInner, StatusFlagInner};
-use ndarray::{Array2, Axis, Zip};
-use numpy::PyArray3;
-use pyo3::exceptions::PyRuntimeError;
+use actions::{extract_inputs, ActionSpace, PyAction, PyDirection, PyInputCode, PySystem};
+use config::{
+    extract_config, from_py, to_py, PyDungeonStyle, PyEnemiesConfig, PyGameConfig, PyItemConfig,
+    PyPlayerConfig, PyRogueConfig,
+};
+use fearures::MessageFlagInner;
+use ndarray::{stack, Array2, ArrayView1, ArrayViewMut3, Axis};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3, PyArray4};
+use pyo3::exceptions::{PyDeprecationWarning, PyRuntimeError, PyTypeError};
 use pyo3::prelude::*;
-use rect_iter::{Get2D, GetMut2D, RectRange};
+use pyo3::types::{PyDict, PyList};
+use rect_iter::GetMut2D;
 use rogue_gym_core::character::player::Status;
+use rogue_gym_core::describe::{Scene, Verbosity};
 use rogue_gym_core::dungeon::{Positioned, X, Y};
-use rogue_gym_core::{error::*, symbol, GameConfig, RunTime};
-use state_impls::GameStateImpl;
+use rogue_gym_core::events::{EpisodeStats, StepEvents, Termination};
+use rogue_gym_core::hash::{HashGranularity, MapHash};
+use rogue_gym_core::input::{Key, KeyMap};
+use rogue_gym_core::observation::{Encoding, Entities, ImageSetting, StatusFlag};
+use rogue_gym_core::replay::Replay;
+use rogue_gym_core::reward::Reward;
+use rogue_gym_core::symbol::{InvalidTileError, SymbolTable};
+use rogue_gym_core::trajectory::{self, Frame, TrajectoryWriter};
+use rogue_gym_core::{error::*, tile::Tile, GameConfig, GameMsg, RunTime};
+use state_impls::{GameSnapshot, GameStateImpl};
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::fs::File;
+use std::io::BufReader;
 use std::str::from_utf8_unchecked;
-use thread_impls::ThreadConductor;
+use std::sync::{Arc, Mutex};
+use thread_impls::{BatchImagesView, BatchView, ThreadConductor};
 
 fn pyresult<T, E: Display>(result: Result<T, E>) -> PyResult<T> {
     pyresult_with(result, "Error in rogue-gym")
@@ -35,94 +61,349 @@ fn pyresult_with<T, E: Display>(result: Result<T, E>, msg: &str) -> PyResult<T>
     result.map_err(|e| PyErr::new::<PyRuntimeError, _>(format!("{}: {}", msg, e)))
 }
 
+fn reward_dict(reward: &Reward) -> HashMap<String, f32> {
+    reward
+        .to_dict_vec()
+        .into_iter()
+        .map(|(s, v)| (s.to_owned(), v))
+        .collect()
+}
+
+fn message_dict<'py>(py: Python<'py>, msg: &GameMsg) -> PyResult<&'py PyDict> {
+    let dict = PyDict::new(py);
+    let kind = match msg {
+        GameMsg::CantMove(d) => {
+            dict.set_item("direction", format!("{:?}", d))?;
+            "cant_move"
+        }
+        GameMsg::CantGetItem(kind) => {
+            dict.set_item("item", kind.category())?;
+            "cant_get_item"
+        }
+        GameMsg::GotItem { kind, num } => {
+            dict.set_item("item", kind.category())?;
+            dict.set_item("num", num)?;
+            "got_item"
+        }
+        GameMsg::HitTo(name, hp) => {
+            dict.set_item("enemy", name.as_ref())?;
+            dict.set_item("damage", hp.0)?;
+            "hit_to"
+        }
+        GameMsg::HitFrom(name, hp) => {
+            dict.set_item("enemy", name.as_ref())?;
+            dict.set_item("damage", hp.0)?;
+            "hit_from"
+        }
+        GameMsg::MissTo(name) => {
+            dict.set_item("enemy", name.as_ref())?;
+            "miss_to"
+        }
+        GameMsg::MissFrom(name) => {
+            dict.set_item("enemy", name.as_ref())?;
+            "miss_from"
+        }
+        GameMsg::Killed(name) => {
+            dict.set_item("enemy", name.as_ref())?;
+            "killed"
+        }
+        GameMsg::NoDownStair => "no_downstair",
+        GameMsg::SecretDoor => "secret_door",
+        GameMsg::Quit => "quit",
+    };
+    dict.set_item("kind", kind)?;
+    Ok(dict)
+}
+
+fn termination_str(termination: &Option<Termination>) -> Option<&'static str> {
+    termination.as_ref().map(|t| match t {
+        Termination::Death(_) => "death",
+        Termination::MaxSteps => "max_steps",
+    })
+}
+
+fn episode_dict<'py>(py: Python<'py>, episode: &EpisodeStats) -> PyResult<&'py PyDict> {
+    let dict = PyDict::new(py);
+    dict.set_item("steps", episode.steps)?;
+    dict.set_item("reward", episode.reward)?;
+    dict.set_item("kills", episode.kills)?;
+    dict.set_item("items", episode.items)?;
+    dict.set_item("damage_dealt", episode.damage_dealt)?;
+    dict.set_item("damage_taken", episode.damage_taken)?;
+    dict.set_item("max_level", episode.max_level)?;
+    dict.set_item("gold", episode.gold)?;
+    dict.set_item("termination", termination_str(&episode.termination))?;
+    Ok(dict)
+}
+
+fn info_dict<'py>(
+    py: Python<'py>,
+    reward: &Reward,
+    events: &StepEvents,
+    episode: Option<&EpisodeStats>,
+) -> PyResult<&'py PyDict> {
+    let res = PyDict::new(py);
+    res.set_item("reward_terms", reward_dict(reward))?;
+    let messages = PyList::empty(py);
+    for msg in &events.messages {
+        messages.append(message_dict(py, msg)?)?;
+    }
+    res.set_item("messages", messages)?;
+    let items = PyList::empty(py);
+    for (kind, num) in &events.items {
+        let dict = PyDict::new(py);
+        dict.set_item("item", kind.category())?;
+        dict.set_item("num", num)?;
+        items.append(dict)?;
+    }
+    res.set_item("items", items)?;
+    let kills: Vec<_> = events.kills.iter().map(|s| s.to_string()).collect();
+    res.set_item("kills", kills)?;
+    res.set_item("damage_dealt", events.damage_dealt)?;
+    res.set_item("damage_taken", events.damage_taken)?;
+    res.set_item("level_change", events.level_change)?;
+    res.set_item("termination", termination_str(&events.termination))?;
+    if let Some(Termination::Death(grave)) = &events.termination {
+        res.set_item("grave", grave)?;
+    }
+    if let Some(episode) = episode {
+        res.set_item("episode", episode_dict(py, episode)?)?;
+    }
+    Ok(res)
+}
+
+/// legality of `KeyMap::AI_KEYS` in the current state
+pub(crate) fn ai_action_mask(runtime: &RunTime) -> Vec<bool> {
+    let keys = KeyMap::AI_KEYS.iter().map(|&c| Key::Char(c));
+    runtime.action_mask(keys)
+}
+
 /// A memory efficient representation of Agent observation.
-#[pyclass]
+#[pyclass(module = "rogue_gym_python._rogue_gym")]
 #[derive(Clone, Debug, PartialEq)]
 struct PlayerState {
     map: Vec<Vec<u8>>,
+    /// updated when cells of `map` are redrawn
+    map_hash: MapHash,
+    /// known hash, entities, the scene and the action mask are computed only when the state is
+    /// handed out, by `observe`
+    known_hash: u64,
     history: Array2<bool>,
     status: Status,
-    symbols: u8,
+    symbols: SymbolTable,
     message: MessageFlagInner,
+    reward: Reward,
+    events: StepEvents,
+    /// aggregates of the episode, only for terminal states
+    episode: Option<EpisodeStats>,
+    /// entities, exits and messages, to describe the state in English,
+    /// computed only when descriptions are enabled
+    scene: Option<Scene>,
+    /// entities in the coordinates of `map`
+    entities: Entities,
+    action_mask: Vec<bool>,
     is_terminal: bool,
 }
 
 impl PlayerState {
-    fn new(w: X, h: Y, symbols: u8) -> Self {
+    fn new(w: X, h: Y, symbols: SymbolTable) -> Self {
         let (w, h) = (w.0 as usize, h.0 as usize);
         PlayerState {
             map: vec![vec![b' '; w]; h],
+            map_hash: MapHash::new(&Array2::from_elem([h, w], b' ')),
+            known_hash: 0,
             history: Array2::from_elem([h, w], false),
             status: Status::default(),
             symbols,
             message: MessageFlagInner::new(),
+            reward: Reward::default(),
+            events: StepEvents::default(),
+            episode: None,
+            scene: None,
+            entities: Entities::default(),
+            action_mask: vec![false; KeyMap::AI_KEYS.len()],
             is_terminal: false,
         }
     }
-    fn reset(&mut self, runtime: &RunTime) -> GameResult<()> {
+    fn reset(&mut self, runtime: &RunTime, viewport: bool) -> GameResult<()> {
         self.status = runtime.player_status();
-        self.draw_map(runtime)?;
+        self.draw_map(runtime, viewport)?;
         self.message = MessageFlagInner::new();
+        self.reward = Reward::default();
+        self.events = StepEvents::default();
+        self.episode = None;
         self.is_terminal = false;
         Ok(())
     }
-    fn draw_map(&mut self, runtime: &RunTime) -> GameResult<()> {
-        self.history = runtime.history(&self.status).unwrap();
-        runtime.draw_screen(|Positioned(cd, tile)| -> GameResult<()> {
-            *self
-                .map
+    /// computes the known hash, entities, the action mask, and the scene if `describe` is true
+    /// They take time, so games skip them every step and do them only for states handed out.
+    fn observe(&mut self, runtime: &RunTime, viewport: bool, describe: bool) -> GameResult<()> {
+        self.known_hash = runtime.state_hash(HashGranularity::Known)?;
+        self.scene = if describe {
+            Some(runtime.scene(self.events.messages.clone())?)
+        } else {
+            None
+        };
+        let entities = match &self.scene {
+            Some(scene) => scene.entities.clone(),
+            None => runtime.entities(),
+        };
+        self.entities = if viewport {
+            entities.in_viewport(&runtime.viewport())
+        } else {
+            entities
+        };
+        self.action_mask = ai_action_mask(runtime);
+        Ok(())
+    }
+    fn draw_map(&mut self, runtime: &RunTime, viewport: bool) -> GameResult<()> {
+        let history = runtime.history(&self.status).unwrap();
+        let (map, map_hash) = (&mut self.map, &mut self.map_hash);
+        let draw = |Positioned(cd, tile): Positioned<Tile>| -> GameResult<()> {
+            let cell = map
                 .try_get_mut_p(cd)
-                .into_chained(|| "in python::GameState::react")? = tile.to_byte();
+                .into_chained(|| "in python::GameState::react")?;
+            map_hash.update(cd.x.0 as usize, cd.y.0 as usize, *cell, tile.to_byte());
+            *cell = tile.to_byte();
             Ok(())
-        })
+        };
+        if viewport {
+            let viewport = runtime.viewport();
+            let (x, y) = (viewport.offset.x.0 as usize, viewport.offset.y.0 as usize);
+            let (w, h) = (viewport.width.0 as usize, viewport.height.0 as usize);
+            self.history = history.slice(s![y..y + h, x..x + w]).to_owned();
+            self.history.row_mut(0).fill(false);
+            self.history.row_mut(h - 1).fill(false);
+            runtime.draw_viewport(draw)
+        } else {
+            self.history = history;
+            runtime.draw_screen(draw)
+        }
+    }
+    fn info_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        info_dict(py, &self.reward, &self.events, self.episode.as_ref())
+    }
+    fn frame(&self) -> Frame {
+        let (h, w) = self.history.dim();
+        let map = Array2::from_shape_fn([h, w], |(y, x)| self.map[y][x]);
+        Frame::new(map, self.history.clone(), &self.status)
     }
     fn dungeon_str(&self) -> impl Iterator<Item = &str> {
         self.map.iter().map(|v| unsafe { from_utf8_unchecked(v) })
     }
-    fn gray_image_with_offset<'py>(
+    fn encode(
         &self,
-        py: Python<'py>,
-        offset: usize,
-    ) -> PyResult<&'py PyArray3<f32>> {
-        let (h, w) = (self.map.len(), self.map[0].len());
-        let py_array = PyArray3::zeros(py, [1 + offset, h, w], false);
-        RectRange::zero_start(w, h)
-            .unwrap()
-            .into_iter()
-            .for_each(|(x, y)| unsafe {
-                let symbol = symbol::tile_to_sym(*self.map.get_xy(x, y)).unwrap();
-                *py_array.uget_mut([0, y, x]) = f32::from(symbol) / f32::from(self.symbols);
-            });
-        Ok(py_array)
+        setting: &ImageSetting,
+        out: ArrayViewMut3<f32>,
+    ) -> Result<(), InvalidTileError> {
+        setting.encode(&self.map, &self.history, &self.status, &self.symbols, out)
     }
-    fn symbol_image_with_offset<'py>(
-        &self,
-        py: Python<'py>,
-        offset: usize,
-    ) -> PyResult<&'py PyArray3<f32>> {
-        let (h, w) = (self.map.len(), self.map[0].len());
-        let channels = usize::from(self.symbols);
-        let py_array = PyArray3::zeros(py, [channels + offset, h, w], false);
-        pyresult(symbol::construct_symbol_map(
-            &self.map,
-            h,
-            w,
-            self.symbols - 1,
-            |idx| unsafe { py_array.uget_mut(idx) },
-        ))?;
-        Ok(py_array)
+    fn encode_u8(&self, setting: &ImageSetting, out: ArrayViewMut3<u8>) -> GameResult<()> {
+        setting.encode_u8(&self.map, &self.history, &self.symbols, out)
     }
-    fn copy_hist(&self, py_array: &PyArray3<f32>, offset: usize) {
-        let mut array = unsafe { py_array.as_array_mut() };
-        let hist_array = array.index_axis_mut(Axis(0), usize::from(offset));
-        Zip::from(hist_array).and(&self.history).apply(|p, &r| {
-            *p = if r { 1.0 } else { 0.0 };
-        });
+    fn image<'py>(&self, py: Python<'py>, setting: ImageSetting) -> PyResult<&'py PyArray3<f32>> {
+        let shape = setting.shape(&self.symbols, self.history.dim());
+        let array = PyArray3::zeros(py, shape, false);
+        pyresult(self.encode(&setting, unsafe { array.as_array_mut() }))?;
+        Ok(array)
+    }
+    fn image_u8<'py>(&self, py: Python<'py>, setting: ImageSetting) -> PyResult<&'py PyArray3<u8>> {
+        let shape = setting.shape(&self.symbols, self.history.dim());
+        let array = PyArray3::zeros(py, shape, false);
+        pyresult(self.encode_u8(&setting, unsafe { array.as_array_mut() }))?;
+        Ok(array)
+    }
+}
+
+/// `PlayerState` as pickled, where `map_hash` is computed again from `map`
+#[derive(Serialize, Deserialize)]
+struct PlayerStateDump {
+    map: Vec<String>,
+    /// rows of '1'(visited) and '0'
+    history: Vec<String>,
+    status: Status,
+    message: u32,
+    symbols: SymbolTable,
+    known_hash: u64,
+    reward: Reward,
+    events: StepEvents,
+    episode: Option<EpisodeStats>,
+    #[serde(default)]
+    scene: Option<Scene>,
+    entities: Entities,
+    action_mask: Vec<bool>,
+    is_terminal: bool,
+}
+
+impl PlayerStateDump {
+    fn new(state: &PlayerState) -> Self {
+        let history = state
+            .history
+            .outer_iter()
+            .map(|row| row.iter().map(|&b| if b { '1' } else { '0' }).collect())
+            .collect();
+        PlayerStateDump {
+            map: state.dungeon_str().map(ToOwned::to_owned).collect(),
+            history,
+            status: state.status.clone(),
+            message: state.message.0,
+            symbols: state.symbols,
+            known_hash: state.known_hash,
+            reward: state.reward,
+            events: state.events.clone(),
+            episode: state.episode.clone(),
+            scene: state.scene.clone(),
+            entities: state.entities.clone(),
+            action_mask: state.action_mask.clone(),
+            is_terminal: state.is_terminal,
+        }
+    }
+    fn into_state(self) -> GameResult<PlayerState> {
+        let (h, w) = (self.map.len(), self.map.first().map_or(0, String::len));
+        let invalid = || ErrorId::InvalidConversion.into_with(|| "PlayerState: broken map");
+        if self.history.len() != h || self.map.iter().chain(&self.history).any(|r| r.len() != w) {
+            return Err(invalid());
+        }
+        let map: Vec<Vec<u8>> = self.map.into_iter().map(String::into_bytes).collect();
+        let cells = Array2::from_shape_fn([h, w], |(y, x)| map[y][x]);
+        let history = self
+            .history
+            .iter()
+            .flat_map(|r| r.bytes().map(|b| b == b'1'));
+        Ok(PlayerState {
+            map_hash: MapHash::new(&cells),
+            map,
+            known_hash: self.known_hash,
+            history: Array2::from_shape_vec([h, w], history.collect()).map_err(|_| invalid())?,
+            status: self.status,
+            symbols: self.symbols,
+            message: MessageFlagInner(self.message),
+            reward: self.reward,
+            events: self.events,
+            episode: self.episode,
+            scene: self.scene,
+            entities: self.entities,
+            action_mask: self.action_mask,
+            is_terminal: self.is_terminal,
+        })
     }
 }
 
 #[pymethods]
 impl PlayerState {
+    /// Stable hash of the state, where granularity is 'known'(the map the player has seen)
+    /// or 'map_and_status'(the observed map and status).
+    /// 'full' is only available on `GameState`.
+    #[pyo3(signature = (granularity = "map_and_status"))]
+    fn state_hash(&self, granularity: &str) -> PyResult<u64> {
+        match pyresult(granularity.parse())? {
+            HashGranularity::Known => Ok(self.known_hash),
+            HashGranularity::MapAndStatus => Ok(self.map_hash.with_status(&self.status)),
+            HashGranularity::Full => Err(PyRuntimeError::new_err(
+                "Full hash is only available on GameState",
+            )),
+        }
+    }
     #[getter]
     fn status(&self) -> PyResult<HashMap<String, u32>> {
         Ok(self
@@ -146,59 +427,173 @@ impl PlayerState {
     }
     #[getter]
     fn symbols(&self) -> PyResult<usize> {
-        Ok(usize::from(self.symbols))
+        Ok(self.symbols.len())
     }
     #[getter]
     fn is_terminal(&self) -> PyResult<bool> {
         Ok(self.is_terminal)
     }
+    /// Weighted sum of reward terms of the last step
+    #[getter]
+    fn reward(&self) -> PyResult<f32> {
+        Ok(self.reward.total())
+    }
+    /// Weighted reward terms of the last step
+    #[getter]
+    fn reward_terms(&self) -> PyResult<HashMap<String, f32>> {
+        Ok(reward_dict(&self.reward))
+    }
+    /// What happened in the last step, as a dict
+    /// 'episode' has aggregates of the episode if the state is terminal
+    #[getter]
+    fn info(&self) -> PyResult<PyObject> {
+        let py = unsafe { Python::assume_gil_acquired() };
+        Ok(self.info_dict(py)?.to_object(py))
+    }
     fn status_vec(&self, flag: u32) -> Vec<i32> {
-        let flag = StatusFlagInner(flag);
-        flag.to_vector(&self.status)
+        StatusFlag(flag).to_vector(&self.status)
     }
     fn gray_image(&self, flag: Option<u32>) -> PyResult<&PyArray3<f32>> {
-        let (py, flag) = (
-            unsafe { Python::assume_gil_acquired() },
-            StatusFlagInner::from(flag),
-        );
-        let array = self.gray_image_with_offset(py, flag.len())?;
-        flag.copy_status(&self.status, 1, &mut unsafe { array.as_array_mut() });
-        Ok(array)
+        let py = unsafe { Python::assume_gil_acquired() };
+        self.image(py, ImageSetting::new(true, flag.into(), false))
     }
     fn gray_image_with_hist(&self, flag: Option<u32>) -> PyResult<&PyArray3<f32>> {
-        let (py, flag) = (
-            unsafe { Python::assume_gil_acquired() },
-            StatusFlagInner::from(flag),
-        );
-        let array = self.gray_image_with_offset(py, flag.len() + 1)?;
-        let offset = flag.copy_status(&self.status, 1, &mut unsafe { array.as_array_mut() });
-        self.copy_hist(&array, offset);
-        Ok(array)
+        let py = unsafe { Python::assume_gil_acquired() };
+        self.image(py, ImageSetting::new(true, flag.into(), true))
     }
     /// Convert PlayerSelf with 3D symbol image dungeon(like AlphaGo's inputs)
     fn symbol_image(&self, flag: Option<u32>) -> PyResult<&PyArray3<f32>> {
-        let (py, flag) = (
-            unsafe { Python::assume_gil_acquired() },
-            StatusFlagInner::from(flag),
-        );
-        let array = self.symbol_image_with_offset(py, flag.len())?;
-        flag.copy_status(&self.status, usize::from(self.symbols), &mut unsafe {
-            array.as_array_mut()
-        });
-        Ok(array)
+        let py = unsafe { Python::assume_gil_acquired() };
+        self.image(py, ImageSetting::new(false, flag.into(), false))
     }
     /// Convert PlayerState to 3D symbol image, with player history
     fn symbol_image_with_hist(&self, flag: Option<u32>) -> PyResult<&PyArray3<f32>> {
-        let (py, flag) = (
-            unsafe { Python::assume_gil_acquired() },
-            StatusFlagInner::from(flag),
-        );
-        let array = self.symbol_image_with_offset(py, flag.len() + 1)?;
-        let offset = flag.copy_status(&self.status, usize::from(self.symbols), &mut unsafe {
-            array.as_array_mut()
-        });
-        self.copy_hist(&array, offset);
-        Ok(array)
+        let py = unsafe { Python::assume_gil_acquired() };
+        self.image(py, ImageSetting::new(false, flag.into(), true))
+    }
+    /// Image of `[2 * ry + 1, 2 * rx + 1]` cropped around the player, where `radius` is (rx, ry)
+    /// Cells out of the map are filled with 0.
+    /// If `multiscale` is true, the whole map downsampled to the same size is appended to
+    /// dungeon channels(and history channels).
+    #[pyo3(signature = (radius, flag = None, gray = false, hist = false, multiscale = false))]
+    fn egocentric_image(
+        &self,
+        radius: (usize, usize),
+        flag: Option<u32>,
+        gray: bool,
+        hist: bool,
+        multiscale: bool,
+    ) -> PyResult<&PyArray3<f32>> {
+        let py = unsafe { Python::assume_gil_acquired() };
+        let setting = ImageSetting {
+            crop: Some(radius),
+            multiscale,
+            ..ImageSetting::new(gray, flag.into(), hist)
+        };
+        self.image(py, setting)
+    }
+    /// Image by all options of `ImageSetting`, where `encoding` is 'float', 'index'(uint8 map
+    /// of symbol indices + 1) or 'packed'(uint8 one-hot channels packed along x by numpy.packbits)
+    /// Integer encodings and `separate_status` leave status out of the image.
+    #[pyo3(signature = (
+        flag = None,
+        gray = false,
+        hist = false,
+        crop = None,
+        multiscale = false,
+        encoding = "float",
+        separate_status = false
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn encoded_image(
+        &self,
+        py: Python,
+        flag: Option<u32>,
+        gray: bool,
+        hist: bool,
+        crop: Option<(usize, usize)>,
+        multiscale: bool,
+        encoding: &str,
+        separate_status: bool,
+    ) -> PyResult<PyObject> {
+        let setting = image_setting(
+            gray,
+            flag,
+            hist,
+            crop,
+            multiscale,
+            encoding,
+            separate_status,
+        )?;
+        if setting.encoding.is_float() {
+            Ok(self.image(py, setting)?.to_object(py))
+        } else {
+            Ok(self.image_u8(py, setting)?.to_object(py))
+        }
+    }
+    /// Legality of actions, in the order of '.hjklnbuy>sHJKLNBUY'
+    #[getter]
+    fn action_mask(&self) -> &PyArray1<bool> {
+        let py = unsafe { Python::assume_gil_acquired() };
+        PyArray1::from_slice(py, &self.action_mask)
+    }
+    /// Enemies, items, the stair and the room the player can see, as a dict
+    /// Coordinates are the same as the map, and hp_rate is None until the player damages it
+    #[getter]
+    fn entities(&self) -> PyResult<PyObject> {
+        let py = unsafe { Python::assume_gil_acquired() };
+        let entities = &self.entities;
+        let res = PyDict::new(py);
+        let enemies = PyList::empty(py);
+        for enemy in &entities.enemies {
+            let dict = PyDict::new(py);
+            dict.set_item("x", enemy.cd.x.0)?;
+            dict.set_item("y", enemy.cd.y.0)?;
+            dict.set_item("tile", enemy.tile.to_char().to_string())?;
+            dict.set_item("name", &enemy.name)?;
+            dict.set_item("hp_rate", enemy.hp_rate)?;
+            enemies.append(dict)?;
+        }
+        res.set_item("enemies", enemies)?;
+        let items = PyList::empty(py);
+        for item in &entities.items {
+            let dict = PyDict::new(py);
+            dict.set_item("x", item.cd.x.0)?;
+            dict.set_item("y", item.cd.y.0)?;
+            dict.set_item("tile", item.tile.to_char().to_string())?;
+            dict.set_item("kind", item.kind.category())?;
+            dict.set_item("num", item.num)?;
+            items.append(dict)?;
+        }
+        res.set_item("items", items)?;
+        res.set_item("stair", entities.stair.map(|cd| (cd.x.0, cd.y.0)))?;
+        res.set_item("room", entities.room.map(|r| (r[0], r[1], r[2], r[3])))?;
+        Ok(res.to_object(py))
+    }
+    /// What the player can see, the status and messages of the last step in English,
+    /// where verbosity is 'brief', 'normal' or 'verbose'
+    /// Only available for states of games created with describe=True
+    #[pyo3(signature = (verbosity = "normal"))]
+    fn describe(&self, verbosity: &str) -> PyResult<String> {
+        let verbosity: Verbosity = pyresult(verbosity.parse())?;
+        let scene = self.scene.as_ref().ok_or_else(|| {
+            PyRuntimeError::new_err("Descriptions are disabled, create the game with describe=True")
+        })?;
+        Ok(scene.describe(verbosity))
+    }
+    /// Same as `describe('normal')`
+    #[getter]
+    fn description(&self) -> PyResult<String> {
+        self.describe("normal")
+    }
+    /// Entities as an array of [max_enemies + max_items + 2, 7]
+    /// Rows are enemies, items, the stair and the room in this order,
+    /// and each row is [exists, x, y, tile, hp_rate, width, height], where unknown hp_rate is -1
+    fn entity_tensor(&self, max_enemies: usize, max_items: usize) -> &PyArray2<f32> {
+        let py = unsafe { Python::assume_gil_acquired() };
+        self.entities
+            .to_array(max_enemies, max_items)
+            .into_pyarray(py)
     }
     // dunder methods
     fn __repr__(&self) -> String {
@@ -213,28 +608,93 @@ impl PlayerState {
     fn __str__(&self) -> String {
         self.__repr__()
     }
+    /// Pickles the state as a dict of the map, history, status, message and others
+    fn __reduce__(&self, py: Python) -> PyResult<(PyObject, (PyObject,))> {
+        let restore = py.get_type::<Self>().getattr("_from_state")?;
+        Ok((restore.into(), (to_py(py, &PlayerStateDump::new(self))?,)))
+    }
+    #[staticmethod]
+    fn _from_state(state: &PyAny) -> PyResult<Self> {
+        let dump: PlayerStateDump = from_py(state, "PlayerState")?;
+        pyresult(dump.into_state())
+    }
+    fn __copy__(&self) -> Self {
+        self.clone()
+    }
+    fn __deepcopy__(&self, _memo: &PyAny) -> Self {
+        self.clone()
+    }
 }
 
-#[pyclass]
+#[pyclass(module = "rogue_gym_python._rogue_gym")]
 struct GameState {
     inner: GameStateImpl,
     config: GameConfig,
+    viewport: bool,
+    describe: bool,
+}
+
+/// (height, width) of the viewport if `viewport` is true, otherwise of the whole dungeon
+fn observation_size(config: &GameConfig, viewport: bool) -> (i32, i32) {
+    if viewport {
+        (config.height, config.width)
+    } else {
+        let (w, h) = config.dungeon_size();
+        (h, w)
+    }
 }
 
 #[pymethods]
 impl GameState {
+    /// States have descriptions in English only if `describe` is true, since making them
+    /// takes time every step
+    /// `config_str` is a deprecated name of `config`.
     #[new]
-    fn __new__(max_steps: usize, config_str: Option<String>) -> PyResult<GameState> {
-        let config = if let Some(cfg) = config_str {
-            pyresult_with(GameConfig::from_json(&cfg), "Failed to parse config")?
+    #[pyo3(signature = (
+        max_steps,
+        config = None,
+        viewport = false,
+        describe = false,
+        config_str = None
+    ))]
+    fn __new__(
+        py: Python,
+        max_steps: usize,
+        config: Option<&PyAny>,
+        viewport: bool,
+        describe: bool,
+        config_str: Option<&PyAny>,
+    ) -> PyResult<GameState> {
+        if config_str.is_some() {
+            if config.is_some() {
+                return Err(PyTypeError::new_err(
+                    "config and config_str can't be given at the same time",
+                ));
+            }
+            let category = py.get_type::<PyDeprecationWarning>();
+            PyErr::warn(
+                py,
+                category,
+                "config_str is deprecated, use config instead",
+                1,
+            )?;
+        }
+        let config = if let Some(cfg) = config.or(config_str) {
+            extract_config(cfg)?
         } else {
             GameConfig::default()
         };
-        let inner = pyresult(GameStateImpl::new(config.clone(), max_steps))?;
-        Ok(GameState { inner, config })
+        let inner = GameStateImpl::new(config.clone(), max_steps, viewport, describe);
+        Ok(GameState {
+            inner: pyresult(inner)?,
+            config,
+            viewport,
+            describe,
+        })
     }
+    /// Returns the size of observations as (height, width)
     fn screen_size(&self) -> (i32, i32) {
-        (self.config.height, self.config.width)
+        observation_size(&self.config, self.viewport)
     }
     fn set_seed(&mut self, seed: u64) -> PyResult<()> {
         self.config.seed = Some(seed as u128);
@@ -245,11 +705,27 @@ impl GameState {
         pyresult(self.inner.reset(self.config.clone()))
     }
     /// Returns the latest game state
-    fn prev(&self) -> PlayerState {
-        self.inner.state()
+    fn prev(&self) -> PyResult<PlayerState> {
+        pyresult(self.inner.state())
     }
-    fn react(&mut self, input: u8) -> PyResult<()> {
-        pyresult(self.inner.react(input))
+    /// Reacts to the input and returns what happened, same as `PlayerState.info`
+    fn react(&mut self, py: Python, input: u8) -> PyResult<PyObject> {
+        pyresult(self.inner.react(input))?;
+        Ok(self.inner.state_ref().info_dict(py)?.to_object(py))
+    }
+    /// Reacts to an `Action`, `InputCode` or a sequence of them in order, and returns
+    /// what happened for each input
+    fn react_inputs(&mut self, py: Python, inputs: &PyAny) -> PyResult<PyObject> {
+        let res = PyList::empty(py);
+        for input in extract_inputs(inputs)? {
+            pyresult(self.inner.react_input(input))?;
+            res.append(self.inner.state_ref().info_dict(py)?)?;
+        }
+        Ok(res.to_object(py))
+    }
+    /// Legality of each action in the space, in the current state
+    fn action_mask_of(&self, space: &ActionSpace) -> Vec<bool> {
+        space.mask(&self.inner.runtime)
     }
     /// Returns action history as Json
     fn dump_history(&self) -> PyResult<String> {
@@ -258,12 +734,153 @@ impl GameState {
             "Error when getting history",
         )
     }
+    /// Stable hash of the current state, where granularity is 'known'(the map the player
+    /// has seen), 'map_and_status'(the map and status) or 'full'(the ground truth)
+    #[pyo3(signature = (granularity = "map_and_status"))]
+    fn state_hash(&self, granularity: &str) -> PyResult<u64> {
+        let granularity = pyresult(granularity.parse())?;
+        pyresult(self.inner.runtime.state_hash(granularity))
+    }
+    /// Returns a replay with the config, seed, inputs and state hashes as Json
+    fn dump_replay(&self) -> PyResult<String> {
+        let replay = Replay::new(&self.config, &self.inner.runtime);
+        pyresult_with(replay.and_then(|r| r.to_json()), "Error when making replay")
+    }
     /// Returns config as Json
     fn dump_config(&self) -> PyResult<String> {
         pyresult_with(self.config.to_json(), "Error when getting config")
     }
     fn symbols(&self) -> PyResult<usize> {
-        Ok(self.inner.symbols())
+        Ok(self.inner.symbols().len())
+    }
+    /// Returns tiles in each channel of symbol images, like `[' ', '@', '#', '.', '-|', ...]`
+    fn symbol_table(&self) -> Vec<String> {
+        self.inner.symbols().channels()
+    }
+    /// Starts recording trajectories into `path`, from the current state
+    fn start_recording(&mut self, path: &str) -> PyResult<()> {
+        let writer = pyresult(TrajectoryWriter::create(path))?;
+        pyresult(self.inner.record(Some(Arc::new(Mutex::new(writer)))))
+    }
+    /// Stops recording trajectories and flushes the file
+    fn stop_recording(&mut self) -> PyResult<()> {
+        pyresult(self.inner.record(None))
+    }
+    /// Pickles the game as the config, seed and inputs, and restores it by replaying them.
+    /// Recording is not restored.
+    #[allow(clippy::type_complexity)]
+    fn __reduce__(slf: &PyCell<Self>) -> PyResult<(PyObject, (usize, String, bool, bool), String)> {
+        let game = slf.borrow();
+        let config = pyresult(game.config.to_json())?;
+        let snapshot = pyresult(
+            game.inner
+                .snapshot()
+                .and_then(|s| serde_json::to_string(&s).into_chained(|| "GameState::__reduce__")),
+        )?;
+        let args = (game.inner.max_steps(), config, game.viewport, game.describe);
+        Ok((slf.get_type().into(), args, snapshot))
+    }
+    fn __setstate__(&mut self, snapshot: &str) -> PyResult<()> {
+        let snapshot: GameSnapshot =
+            pyresult(serde_json::from_str(snapshot).into_chained(|| "GameState::__setstate__"))?;
+        self.inner = pyresult(GameStateImpl::replay(
+            &snapshot,
+            self.inner.max_steps(),
+            self.viewport,
+            self.describe,
+        ))?;
+        Ok(())
+    }
+    /// Copies the game by replaying its inputs
+    fn __deepcopy__(&self, _memo: &PyAny) -> PyResult<Self> {
+        let snapshot = pyresult(self.inner.snapshot())?;
+        let max_steps = self.inner.max_steps();
+        let inner = GameStateImpl::replay(&snapshot, max_steps, self.viewport, self.describe);
+        Ok(GameState {
+            inner: pyresult(inner)?,
+            config: self.config.clone(),
+            viewport: self.viewport,
+            describe: self.describe,
+        })
+    }
+}
+
+fn image_setting(
+    gray: bool,
+    flag: Option<u32>,
+    hist: bool,
+    crop: Option<(usize, usize)>,
+    multiscale: bool,
+    encoding: &str,
+    separate_status: bool,
+) -> PyResult<ImageSetting> {
+    Ok(ImageSetting {
+        crop,
+        multiscale,
+        encoding: pyresult(encoding.parse())?,
+        separate_status,
+        ..ImageSetting::new(gray, flag.into(), hist)
+    })
+}
+
+/// Preallocated image buffer, `u8` for integer encodings
+enum BatchImages {
+    Float(Py<PyArray4<f32>>),
+    Int(Py<PyArray4<u8>>),
+}
+
+/// Preallocated buffers to write observations of all workers into
+struct Batch {
+    setting: ImageSetting,
+    images: BatchImages,
+    status: Py<PyArray2<i32>>,
+    masks: Py<PyArray2<bool>>,
+    rewards: Py<PyArray1<f32>>,
+    dones: Py<PyArray1<bool>>,
+}
+
+impl Batch {
+    fn new(
+        py: Python,
+        setting: ImageSetting,
+        n: usize,
+        symbols: &SymbolTable,
+        size: (usize, usize),
+    ) -> Self {
+        let [c, h, w] = setting.shape(symbols, size);
+        let images = match setting.encoding {
+            Encoding::Float => BatchImages::Float(PyArray4::zeros(py, [n, c, h, w], false).into()),
+            _ => BatchImages::Int(PyArray4::zeros(py, [n, c, h, w], false).into()),
+        };
+        Batch {
+            setting,
+            images,
+            status: PyArray2::zeros(py, [n, setting.status.len()], false).into(),
+            masks: PyArray2::zeros(py, [n, KeyMap::AI_KEYS.len()], false).into(),
+            rewards: PyArray1::zeros(py, n, false).into(),
+            dones: PyArray1::zeros(py, n, false).into(),
+        }
+    }
+    /// views of buffers which workers write states into
+    fn view(&self, py: Python) -> BatchView {
+        let images = match self.images {
+            BatchImages::Float(ref a) => BatchImagesView::Float(a.as_ref(py).as_raw_array_mut()),
+            BatchImages::Int(ref a) => BatchImagesView::Int(a.as_ref(py).as_raw_array_mut()),
+        };
+        BatchView {
+            setting: self.setting,
+            images,
+            status: self.status.as_ref(py).as_raw_array_mut(),
+            masks: self.masks.as_ref(py).as_raw_array_mut(),
+        }
+    }
+    fn write_scalars(&self, py: Python, scalars: &[(f32, bool)]) {
+        let mut rewards = unsafe { self.rewards.as_ref(py).as_array_mut() };
+        let mut dones = unsafe { self.dones.as_ref(py).as_array_mut() };
+        for (i, &(reward, done)) in scalars.iter().enumerate() {
+            rewards[i] = reward;
+            dones[i] = done;
+        }
     }
 }
 
@@ -271,42 +888,99 @@ impl GameState {
 struct ParallelGameState {
     conductor: ThreadConductor,
     configs: Vec<GameConfig>,
-    symbols: u8,
+    symbols: SymbolTable,
+    viewport: bool,
+    batch: Batch,
 }
 
 #[pymethods]
 impl ParallelGameState {
+    /// `gray`, `flag`, `hist`, `crop`, `multiscale`, `encoding` and `separate_status` select
+    /// the image written by `step_batched` and `reset_batched`, in the same way as
+    /// `PlayerState.encoded_image`
+    /// Games run in a pool of `threads` threads(as many as CPUs by default)
     #[new]
-    fn __new__(py: Python, max_steps: usize, configs: Vec<String>) -> PyResult<ParallelGameState> {
-        let configs = {
-            let mut res = vec![];
-            for cfg in configs {
-                res.push(pyresult_with(
-                    GameConfig::from_json(&cfg),
-                    "Failed to parse config",
-                )?);
+    #[pyo3(signature = (
+        max_steps,
+        configs,
+        viewport = false,
+        describe = false,
+        gray = false,
+        flag = None,
+        hist = false,
+        crop = None,
+        multiscale = false,
+        encoding = "float",
+        separate_status = false,
+        threads = None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn __new__(
+        py: Python,
+        max_steps: usize,
+        configs: Vec<&PyAny>,
+        viewport: bool,
+        describe: bool,
+        gray: bool,
+        flag: Option<u32>,
+        hist: bool,
+        crop: Option<(usize, usize)>,
+        multiscale: bool,
+        encoding: &str,
+        separate_status: bool,
+        threads: Option<usize>,
+    ) -> PyResult<ParallelGameState> {
+        let configs = configs
+            .into_iter()
+            .map(extract_config)
+            .collect::<PyResult<Vec<_>>>()?;
+        let symbols = pyresult(configs[0].symbol_table())?;
+        for config in &configs[1..] {
+            if pyresult(config.symbol_table())? != symbols {
+                return Err(PyRuntimeError::new_err(
+                    "All configs need the same symbol table",
+                ));
             }
-            res
-        };
-        let symbols = configs[0]
-            .symbol_max()
-            .expect("Failed to get symbol max")
-            .to_byte()
-            + 1;
+        }
+        let setting = image_setting(
+            gray,
+            flag,
+            hist,
+            crop,
+            multiscale,
+            encoding,
+            separate_status,
+        )?;
+        let (h, w) = observation_size(&configs[0], viewport);
+        let batch = Batch::new(
+            py,
+            setting,
+            configs.len(),
+            &symbols,
+            (h as usize, w as usize),
+        );
         let cloned = configs.clone();
-        let conductor = py.allow_threads(move || ThreadConductor::new(cloned, max_steps));
+        let conductor = py.allow_threads(move || {
+            ThreadConductor::new(cloned, max_steps, viewport, describe, threads)
+        });
         let conductor = pyresult(conductor)?;
         Ok(ParallelGameState {
             conductor,
             configs,
             symbols,
+            viewport,
+            batch,
         })
     }
     fn screen_size(&self) -> (i32, i32) {
-        (self.configs[0].height, self.configs[0].width)
+        observation_size(&self.configs[0], self.viewport)
     }
     fn symbols(&self) -> PyResult<usize> {
-        Ok(usize::from(self.symbols))
+        Ok(self.symbols.len())
+    }
+    /// Returns tiles in each channel of symbol images, same as `GameState.symbol_table`
+    fn symbol_table(&self) -> Vec<String> {
+        self.symbols.channels()
     }
     fn seed(&mut self, py: Python, seed: Vec<u128>) -> PyResult<()> {
         let ParallelGameState {
@@ -342,6 +1016,177 @@ impl ParallelGameState {
         } = self;
         pyresult(py.allow_threads(move || conductor.close()))
     }
+    /// Same as `step`, but writes observations into preallocated buffers instead of
+    /// returning PlayerStates
+    fn step_batched(&mut self, py: Python, input: Vec<u8>) -> PyResult<()> {
+        let ParallelGameState {
+            ref mut conductor,
+            ref batch,
+            ..
+        } = self;
+        let view = batch.view(py);
+        let scalars = py.allow_threads(move || conductor.step_batched(input, &view));
+        batch.write_scalars(py, &pyresult(scalars)?);
+        Ok(())
+    }
+    /// Sends inputs to all games and returns without waiting for them
+    fn step_async(&mut self, py: Python, input: Vec<u8>) -> PyResult<()> {
+        let ParallelGameState {
+            ref mut conductor, ..
+        } = self;
+        pyresult(py.allow_threads(move || conductor.step_async(input)))
+    }
+    /// Waits for `step_async` and returns the states
+    fn step_wait(&mut self, py: Python) -> PyResult<Vec<PlayerState>> {
+        let ParallelGameState {
+            ref mut conductor, ..
+        } = self;
+        pyresult(py.allow_threads(move || conductor.step_wait()))
+    }
+    /// Same as `step_wait`, but writes observations into preallocated buffers
+    fn step_wait_batched(&mut self, py: Python) -> PyResult<()> {
+        let ParallelGameState {
+            ref mut conductor,
+            ref batch,
+            ..
+        } = self;
+        let view = batch.view(py);
+        let scalars = py.allow_threads(move || conductor.step_wait_batched(&view));
+        batch.write_scalars(py, &pyresult(scalars)?);
+        Ok(())
+    }
+    /// Same as `reset`, but writes observations into preallocated buffers instead of
+    /// returning PlayerStates
+    fn reset_batched(&mut self, py: Python) -> PyResult<()> {
+        let ParallelGameState {
+            ref mut conductor,
+            ref batch,
+            ..
+        } = self;
+        let view = batch.view(py);
+        let scalars = py.allow_threads(move || conductor.reset_batched(&view));
+        batch.write_scalars(py, &pyresult(scalars)?);
+        Ok(())
+    }
+    /// Starts recording trajectories of all games into `path`
+    fn start_recording(&mut self, py: Python, path: &str) -> PyResult<()> {
+        let writer = pyresult(TrajectoryWriter::create(path))?;
+        let writer = Arc::new(Mutex::new(writer));
+        let ParallelGameState {
+            ref mut conductor, ..
+        } = self;
+        pyresult(py.allow_threads(move || conductor.record(Some(writer))))
+    }
+    /// Stops recording trajectories and flushes the file
+    fn stop_recording(&mut self, py: Python) -> PyResult<()> {
+        let ParallelGameState {
+            ref mut conductor, ..
+        } = self;
+        pyresult(py.allow_threads(move || conductor.record(None)))
+    }
+    /// Images of all workers as `[workers, channel, y, x]`, overwritten by batched methods
+    #[getter]
+    fn images(&self, py: Python) -> PyObject {
+        match self.batch.images {
+            BatchImages::Float(ref images) => images.to_object(py),
+            BatchImages::Int(ref images) => images.to_object(py),
+        }
+    }
+    /// Status vectors of all workers, overwritten by batched methods
+    #[getter]
+    fn status(&self, py: Python) -> Py<PyArray2<i32>> {
+        self.batch.status.clone_ref(py)
+    }
+    /// Action masks of all workers, overwritten by batched methods
+    #[getter]
+    fn action_masks(&self, py: Python) -> Py<PyArray2<bool>> {
+        self.batch.masks.clone_ref(py)
+    }
+    /// Rewards of all workers, overwritten by batched methods
+    #[getter]
+    fn rewards(&self, py: Python) -> Py<PyArray1<f32>> {
+        self.batch.rewards.clone_ref(py)
+    }
+    /// Terminal flags of all workers, overwritten by batched methods
+    #[getter]
+    fn dones(&self, py: Python) -> Py<PyArray1<bool>> {
+        self.batch.dones.clone_ref(py)
+    }
+}
+
+/// Reads trajectories recorded by `start_recording`(or devui's `--record`),
+/// as batches of transitions
+#[pyclass]
+struct TrajectoryReader {
+    inner: trajectory::TrajectoryReader<BufReader<File>>,
+    batch_size: usize,
+}
+
+fn stack_frames<'py>(py: Python<'py>, frames: &[&Frame]) -> PyResult<&'py PyDict> {
+    let maps: Vec<_> = frames.iter().map(|f| f.map.view()).collect();
+    let hists: Vec<_> = frames.iter().map(|f| f.history.view()).collect();
+    let status: Vec<_> = frames
+        .iter()
+        .map(|f| ArrayView1::from(&f.status[..]))
+        .collect();
+    let err = "Frames in a batch have different shapes";
+    let dict = PyDict::new(py);
+    let map = pyresult_with(stack(Axis(0), &maps), err)?;
+    dict.set_item("map", map.into_pyarray(py))?;
+    let history = pyresult_with(stack(Axis(0), &hists), err)?;
+    dict.set_item("history", history.into_pyarray(py))?;
+    let status = pyresult_with(stack(Axis(0), &status), err)?;
+    dict.set_item("status", status.into_pyarray(py))?;
+    Ok(dict)
+}
+
+#[pymethods]
+impl TrajectoryReader {
+    #[new]
+    #[pyo3(signature = (path, batch_size = 32))]
+    fn __new__(path: &str, batch_size: usize) -> PyResult<TrajectoryReader> {
+        let inner = pyresult_with(
+            trajectory::TrajectoryReader::open(path),
+            "Failed to open trajectory",
+        )?;
+        Ok(TrajectoryReader { inner, batch_size })
+    }
+    #[getter]
+    fn version(&self) -> u16 {
+        self.inner.version()
+    }
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+    /// Returns a dict of arrays stacked along the first axis('map', 'history', 'status',
+    /// 'action', 'reward', 'next_map', 'next_history', 'next_status', 'done' and 'episode')
+    /// and 'info', a list of dicts same as `PlayerState.info`
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        let batch = match pyresult(self.inner.read_batch(self.batch_size))? {
+            Some(batch) => batch,
+            None => return Ok(None),
+        };
+        let obs: Vec<_> = batch.iter().map(|t| &t.observation).collect();
+        let res = stack_frames(py, &obs)?;
+        let next_obs: Vec<_> = batch.iter().map(|t| &t.next_observation).collect();
+        for (key, value) in stack_frames(py, &next_obs)? {
+            res.set_item(format!("next_{}", key), value)?;
+        }
+        let actions: Vec<_> = batch.iter().map(|t| t.action).collect();
+        res.set_item("action", PyArray1::from_vec(py, actions))?;
+        let rewards: Vec<_> = batch.iter().map(|t| t.reward.total()).collect();
+        res.set_item("reward", PyArray1::from_vec(py, rewards))?;
+        let dones: Vec<_> = batch.iter().map(|t| t.done).collect();
+        res.set_item("done", PyArray1::from_vec(py, dones))?;
+        let episodes: Vec<_> = batch.iter().map(|t| t.episode).collect();
+        res.set_item("episode", PyArray1::from_vec(py, episodes))?;
+        let infos = PyList::empty(py);
+        for t in &batch {
+            infos.append(info_dict(py, &t.reward, &t.events, None)?)?;
+        }
+        res.set_item("info", infos)?;
+        Ok(Some(res.to_object(py)))
+    }
 }
 
 #[cfg(unix)]
@@ -349,11 +1194,31 @@ impl ParallelGameState {
 fn replay(game: &GameState, py: Python, interval_ms: u64) -> PyResult<()> {
     use rogue_gym_devui::show_replay;
     let inputs = game.inner.runtime.saved_inputs().to_vec();
-    let config = game.config.clone();
+    let mut config = game.config.clone();
+    config.seed = Some(game.inner.runtime.seed());
     let res = py.allow_threads(move || show_replay(config, inputs, interval_ms));
     pyresult(res)
 }
 
+/// Shows a replay file written by `GameState.dump_replay` or devui's `--save`
+#[cfg(unix)]
+#[pyfunction]
+fn replay_file(json: &str, py: Python, interval_ms: u64) -> PyResult<()> {
+    use rogue_gym_core::replay::load_replay;
+    use rogue_gym_devui::show_replay;
+    let (config, inputs) = pyresult(load_replay(json, GameConfig::default()))?;
+    let res = py.allow_threads(move || show_replay(config, inputs, interval_ms));
+    pyresult(res)
+}
+
+/// Re-simulates a replay and returns the first divergence as a string, or None
+#[pyfunction]
+fn verify_replay(json: &str, py: Python) -> PyResult<Option<String>> {
+    let replay = pyresult(Replay::from_json(json))?;
+    let res = pyresult(py.allow_threads(move || replay.verify()))?;
+    Ok(res.map(|div| div.to_string()))
+}
+
 #[cfg(unix)]
 #[pyfunction]
 fn play_cli(game: &GameState) -> PyResult<()> {
@@ -368,9 +1233,24 @@ fn init_mod(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<GameState>()?;
     m.add_class::<PlayerState>()?;
     m.add_class::<ParallelGameState>()?;
+    m.add_class::<TrajectoryReader>()?;
+    m.add_class::<PyAction>()?;
+    m.add_class::<PyDirection>()?;
+    m.add_class::<PyInputCode>()?;
+    m.add_class::<PySystem>()?;
+    m.add_class::<ActionSpace>()?;
+    m.add_class::<PyGameConfig>()?;
+    m.add_class::<PyDungeonStyle>()?;
+    m.add_class::<PyRogueConfig>()?;
+    m.add_class::<PyItemConfig>()?;
+    m.add_class::<PyPlayerConfig>()?;
+    m.add_class::<PyEnemiesConfig>()?;
     #[cfg(unix)]
     m.add_wrapped(pyo3::wrap_pyfunction!(replay))?;
     #[cfg(unix)]
+    m.add_wrapped(pyo3::wrap_pyfunction!(replay_file))?;
+    m.add_wrapped(pyo3::wrap_pyfunction!(verify_replay))?;
+    #[cfg(unix)]
     m.add_wrapped(pyo3::wrap_pyfunction!(play_cli))?;
     Ok(())
 }
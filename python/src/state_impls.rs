@@ -1,79 +1,276 @@
 use rogue_gym_core::{
-    error::GameResult,
-    input::{Key, KeyMap},
+    error::*,
+    events::{EpisodeStats, StepEvents, Termination},
+    hash::HashGranularity,
+    input::{InputCode, Key, KeyMap},
+    reward::{Reward, RewardTracker},
+    symbol::SymbolTable,
+    trajectory::TrajectoryWriter,
     ui::{MordalKind, UiState},
     GameConfig, Reaction, RunTime,
 };
+use std::fs::File;
+use std::io::BufWriter;
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard};
 use PlayerState;
 
+/// a trajectory writer shared by games
+pub(crate) type SharedWriter = Arc<Mutex<TrajectoryWriter<BufWriter<File>>>>;
+
+fn lock(writer: &SharedWriter) -> GameResult<MutexGuard<'_, TrajectoryWriter<BufWriter<File>>>> {
+    writer
+        .lock()
+        .map_err(|_| ErrorId::MaybeBug.into_with(|| "TrajectoryWriter is poisoned"))
+}
+
+/// a game as pickled, which is restored by replaying `inputs`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct GameSnapshot {
+    /// the config the game was built from, which may not have the seed
+    config: GameConfig,
+    /// the seed the game was actually built with
+    seed: u128,
+    /// inputs since the last reset
+    inputs: Vec<InputCode>,
+    /// full hash of the game, to check it's reproduced
+    hash: u64,
+}
+
+struct Recording {
+    writer: SharedWriter,
+    /// id of the running episode
+    episode: Option<u32>,
+}
+
 pub(crate) struct GameStateImpl {
     pub(crate) runtime: RunTime,
+    config: GameConfig,
     state: PlayerState,
+    reward: RewardTracker,
+    episode: EpisodeStats,
     steps: usize,
     max_steps: usize,
+    /// observe only the viewport instead of the whole dungeon
+    viewport: bool,
+    /// make scenes to describe states in English
+    describe: bool,
+    recording: Option<Recording>,
 }
 
 unsafe impl Send for GameStateImpl {}
 
 impl GameStateImpl {
-    pub(crate) fn new(config: GameConfig, max_steps: usize) -> GameResult<Self> {
-        let symbols = config
-            .symbol_max()
-            .expect("Failed to get symbol max")
-            .to_byte()
-            + 1;
-        let mut runtime = config.build()?;
+    pub(crate) fn new(
+        config: GameConfig,
+        max_steps: usize,
+        viewport: bool,
+        describe: bool,
+    ) -> GameResult<Self> {
+        let symbols = config.symbol_table()?;
+        let mut runtime = config.clone().build()?;
         runtime.keymap = KeyMap::ai();
-        let (w, h) = runtime.screen_size();
+        let reward = RewardTracker::new(config.reward.clone(), &runtime);
+        let (w, h) = if viewport {
+            runtime.screen_size()
+        } else {
+            runtime.dungeon_size()
+        };
         let mut state = PlayerState::new(w, h, symbols);
-        state.reset(&mut runtime)?;
+        state.reset(&runtime, viewport)?;
         Ok(GameStateImpl {
             runtime,
+            config,
             state,
+            reward,
+            episode: EpisodeStats::default(),
             steps: 0,
             max_steps,
+            viewport,
+            describe,
+            recording: None,
         })
     }
+    /// restores the game from `snapshot` by replaying its inputs, which doesn't record
+    pub(crate) fn replay(
+        snapshot: &GameSnapshot,
+        max_steps: usize,
+        viewport: bool,
+        describe: bool,
+    ) -> GameResult<Self> {
+        let mut config = snapshot.config.clone();
+        config.seed = Some(snapshot.seed);
+        let mut game = Self::new(config, max_steps, viewport, describe)?;
+        game.config = snapshot.config.clone();
+        for &input in &snapshot.inputs {
+            // ignored inputs are saved too, and fail again here as they did when playing
+            let _ = game.react_input(input);
+        }
+        if game.runtime.state_hash(HashGranularity::Full)? != snapshot.hash {
+            return Err(ErrorId::MaybeBug.into_with(|| {
+                "GameStateImpl::replay: the game can't be reproduced by its inputs"
+            }));
+        }
+        Ok(game)
+    }
+    pub(crate) fn snapshot(&self) -> GameResult<GameSnapshot> {
+        Ok(GameSnapshot {
+            config: self.config.clone(),
+            seed: self.runtime.seed(),
+            inputs: self.runtime.saved_inputs().to_vec(),
+            hash: self.runtime.state_hash(HashGranularity::Full)?,
+        })
+    }
+    pub(crate) fn max_steps(&self) -> usize {
+        self.max_steps
+    }
     pub(crate) fn reset(&mut self, config: GameConfig) -> GameResult<()> {
-        self.runtime = config.build()?;
+        self.end_episode()?;
+        self.runtime = config.clone().build()?;
         self.runtime.keymap = KeyMap::ai();
-        self.state.reset(&mut self.runtime)?;
+        self.reward = RewardTracker::new(config.reward.clone(), &self.runtime);
+        self.config = config;
+        self.state.reset(&self.runtime, self.viewport)?;
+        self.episode = EpisodeStats::default();
         self.steps = 0;
+        self.begin_episode()
+    }
+    /// resets the game, where the state keeps what happened in the last step
+    pub(crate) fn restart(&mut self, config: GameConfig) -> GameResult<()> {
+        let reward = self.state.reward;
+        let events = mem::take(&mut self.state.events);
+        let episode = self.state.episode.take();
+        let is_terminal = self.state.is_terminal;
+        self.reset(config)?;
+        self.state.reward = reward;
+        self.state.events = events;
+        self.state.episode = episode;
+        self.state.is_terminal = is_terminal;
+        Ok(())
+    }
+    /// starts recording trajectories into `writer` from the current state,
+    /// or stops recording if `writer` is None
+    pub(crate) fn record(&mut self, writer: Option<SharedWriter>) -> GameResult<()> {
+        self.end_episode()?;
+        if let Some(recording) = self.recording.take() {
+            lock(&recording.writer)?.flush()?;
+        }
+        self.recording = writer.map(|writer| Recording {
+            writer,
+            episode: None,
+        });
+        self.begin_episode()
+    }
+    fn begin_episode(&mut self) -> GameResult<()> {
+        if let Some(recording) = &mut self.recording {
+            let frame = self.state.frame();
+            let seed = self.runtime.seed();
+            let episode = lock(&recording.writer)?.begin(&self.config, seed, &frame)?;
+            recording.episode = Some(episode);
+        }
         Ok(())
     }
-    pub(crate) fn state(&self) -> PlayerState {
-        self.state.clone()
+    fn end_episode(&mut self) -> GameResult<()> {
+        if let Some(recording) = &mut self.recording {
+            if let Some(episode) = recording.episode.take() {
+                lock(&recording.writer)?.end(episode)?;
+            }
+        }
+        Ok(())
     }
-    pub(crate) fn symbols(&self) -> usize {
-        usize::from(self.state.symbols)
+    fn record_step(&mut self, input: u8) -> GameResult<()> {
+        if let Some(recording) = &self.recording {
+            if let Some(episode) = recording.episode {
+                let state = &self.state;
+                lock(&recording.writer)?.step(
+                    episode,
+                    input,
+                    &state.reward,
+                    &state.events,
+                    &state.frame(),
+                )?;
+            }
+        }
+        if self.state.is_terminal {
+            self.end_episode()?;
+        }
+        Ok(())
     }
-    pub(crate) fn react(&mut self, input: u8) -> GameResult<()> {
+    /// the state with everything computed by `PlayerState::observe`
+    pub(crate) fn state(&self) -> GameResult<PlayerState> {
+        let mut state = self.state.clone();
+        state.observe(&self.runtime, self.viewport, self.describe)?;
+        Ok(state)
+    }
+    pub(crate) fn state_ref(&self) -> &PlayerState {
+        &self.state
+    }
+    pub(crate) fn symbols(&self) -> &SymbolTable {
+        &self.state.symbols
+    }
+    /// reacts to the key and returns what happened
+    pub(crate) fn react(&mut self, input: u8) -> GameResult<&StepEvents> {
+        let key = Key::Char(input as char);
+        let code = self
+            .runtime
+            .keymap
+            .get(key)
+            .ok_or(ErrorId::InvalidInput(key))?;
+        self.react_to_code(code, input)
+    }
+    /// reacts to the input code and returns what happened
+    pub(crate) fn react_input(&mut self, input: InputCode) -> GameResult<&StepEvents> {
+        // trajectories have the key of the input, or 0 if it has no key
+        let key = KeyMap::AI_KEYS
+            .iter()
+            .find(|&&c| self.runtime.keymap.get(Key::Char(c)) == Some(input))
+            .map_or(0, |&c| c as u8);
+        self.react_to_code(input, key)
+    }
+    fn react_to_code(&mut self, input: InputCode, key: u8) -> GameResult<&StepEvents> {
         if self.steps > self.max_steps {
-            return Ok(());
+            self.state.reward = Reward::default();
+            self.state.events = StepEvents::default();
+            return Ok(&self.state.events);
         }
-        let res = self.runtime.react_to_key(Key::Char(input as char))?;
+        let res = self.runtime.react_to_input(input)?;
+        self.state.reward = self.reward.step(&self.runtime, &res);
         self.state.message.reset();
+        let prev_status = self.state.status.clone();
         let mut dead = false;
-        for reaction in res {
+        for reaction in &res {
             match reaction {
                 Reaction::Redraw => {
-                    self.state.draw_map(&self.runtime)?;
+                    self.state.draw_map(&self.runtime, self.viewport)?;
                 }
                 Reaction::StatusUpdated => {
                     self.state.status = self.runtime.player_status();
                 }
-                Reaction::UiTransition(ui) => match ui {
-                    UiState::Mordal(MordalKind::Grave(_)) => dead = true,
-                    _ => bail!(
-                        "[rogue_gym_python::GameStateImpl] Invalid ui transition {:?}",
-                        ui
-                    ),
-                },
-                Reaction::Notify(msg) => self.state.message.append(&msg),
+                Reaction::UiTransition(ui) => {
+                    // other transitions are caused only by system inputs, and don't
+                    // change what the player sees
+                    if let UiState::Mordal(MordalKind::Grave(_)) = ui {
+                        dead = true;
+                    }
+                }
+                Reaction::Notify(msg) => self.state.message.append(msg),
             }
         }
         self.steps += 1;
         self.state.is_terminal = dead || self.steps >= self.max_steps;
-        Ok(())
+        let mut events = StepEvents::new(&res, &prev_status, &self.state.status);
+        if self.state.is_terminal && events.termination.is_none() {
+            events.termination = Some(Termination::MaxSteps);
+        }
+        self.episode
+            .push(&events, &self.state.reward, &self.state.status);
+        self.state.episode = if self.state.is_terminal {
+            Some(self.episode.clone())
+        } else {
+            None
+        };
+        self.state.events = events;
+        self.record_step(key)?;
+        Ok(&self.state.events)
     }
 }
@@ -1,145 +1,386 @@
+use ndarray::{s, Axis, Ix2, Ix4, RawArrayViewMut};
 use rogue_gym_core::{
-    error::{GameResult, ResultExt2},
+    error::{GameResult, ResultExt1, ResultExt2},
+    observation::ImageSetting,
     GameConfig,
 };
-use state_impls::GameStateImpl;
+use state_impls::{GameStateImpl, SharedWriter};
+use std::ops::Range;
 use std::sync::mpsc::{self, Receiver, SyncSender};
 use std::thread;
-use PlayerState;
+use {ai_action_mask, PlayerState};
 
+/// images of `BatchView`
+#[derive(Clone, Copy)]
+pub(crate) enum BatchImagesView {
+    Float(RawArrayViewMut<f32, Ix4>),
+    Int(RawArrayViewMut<u8, Ix4>),
+}
+
+/// raw views of preallocated buffers, where workers write observations of their games
+/// The buffers have to be alive and untouched until the conductor returns.
+#[derive(Clone, Copy)]
+pub(crate) struct BatchView {
+    pub setting: ImageSetting,
+    pub images: BatchImagesView,
+    pub status: RawArrayViewMut<i32, Ix2>,
+    pub masks: RawArrayViewMut<bool, Ix2>,
+}
+
+unsafe impl Send for BatchView {}
+unsafe impl Sync for BatchView {}
+
+impl BatchView {
+    /// rows of `range`
+    fn chunk(&self, range: Range<usize>) -> Self {
+        let images = match self.images {
+            BatchImagesView::Float(a) => {
+                BatchImagesView::Float(a.slice_move(s![range.clone(), .., .., ..]))
+            }
+            BatchImagesView::Int(a) => {
+                BatchImagesView::Int(a.slice_move(s![range.clone(), .., .., ..]))
+            }
+        };
+        BatchView {
+            setting: self.setting,
+            images,
+            status: self.status.slice_move(s![range.clone(), ..]),
+            masks: self.masks.slice_move(s![range, ..]),
+        }
+    }
+    /// writes the state and the action mask of the `i`th game
+    /// safety: no one else accesses the buffers
+    unsafe fn write(&self, i: usize, state: &PlayerState, mask: &[bool]) -> GameResult<()> {
+        match self.images {
+            BatchImagesView::Float(a) => {
+                let mut images = a.deref_into_view_mut();
+                state
+                    .encode(&self.setting, images.index_axis_mut(Axis(0), i))
+                    .chain_err(|| "in BatchView::write")?;
+            }
+            BatchImagesView::Int(a) => {
+                let mut images = a.deref_into_view_mut();
+                state
+                    .encode_u8(&self.setting, images.index_axis_mut(Axis(0), i))
+                    .chain_err(|| "in BatchView::write")?;
+            }
+        }
+        let mut status = self.status.deref_into_view_mut();
+        let values = self.setting.status.to_vector(&state.status);
+        for (p, &v) in status.row_mut(i).iter_mut().zip(&values) {
+            *p = v;
+        }
+        let mut masks = self.masks.deref_into_view_mut();
+        for (p, &m) in masks.row_mut(i).iter_mut().zip(mask) {
+            *p = m;
+        }
+        Ok(())
+    }
+}
+
+/// reply of a worker
+enum Reply {
+    Done,
+    /// errors of games which failed to step, keyed by env index
+    Stepped(Vec<(usize, failure::Error)>),
+    States(Vec<PlayerState>),
+    /// rewards and terminal flags of games
+    Written(Vec<(f32, bool)>),
+}
+
+/// Runs games in a fixed size pool of threads.
+/// Each thread owns a contiguous chunk of games and steps them in a loop, so the results
+/// don't depend on the number of threads.
+/// Workers keep the states of their games, and send them only when asked.
 pub(crate) struct ThreadConductor {
-    receivers: Vec<Receiver<GameResult<PlayerState>>>,
+    receivers: Vec<Receiver<GameResult<Reply>>>,
     senders: Vec<SyncSender<Instruction>>,
+    /// games each thread owns
+    chunks: Vec<Range<usize>>,
+    /// `step_async` is called and `step_wait` is not yet
+    stepping: bool,
 }
 
 impl ThreadConductor {
     const SENDER_BOUND: usize = 4;
-    pub fn new(configs: Vec<GameConfig>, max_steps: usize) -> GameResult<Self> {
+    /// uses as many threads as available CPUs if `threads` is None
+    pub fn new(
+        configs: Vec<GameConfig>,
+        max_steps: usize,
+        viewport: bool,
+        describe: bool,
+        threads: Option<usize>,
+    ) -> GameResult<Self> {
+        let num_games = configs.len();
+        let threads = threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+            .min(num_games)
+            .max(1);
+        let chunks: Vec<_> = (0..threads)
+            .map(|i| i * num_games / threads..(i + 1) * num_games / threads)
+            .collect();
         let mut receivers = vec![];
         let mut senders = vec![];
-        for config in configs {
-            let state = GameStateImpl::new(config.clone(), max_steps)?;
+        let mut configs = configs.into_iter();
+        for chunk in &chunks {
+            let offset = chunk.start;
+            let mut games = vec![];
+            for config in configs.by_ref().take(chunk.len()) {
+                let state = GameStateImpl::new(config.clone(), max_steps, viewport, describe)?;
+                games.push((state, config));
+            }
             let (tx1, rx1) = mpsc::sync_channel(Self::SENDER_BOUND);
             let (tx2, rx2) = mpsc::sync_channel(Self::SENDER_BOUND);
             thread::spawn(move || {
                 let mut worker = ThreadWorker {
-                    game_state: state,
+                    games,
+                    offset,
                     receiver: rx1,
                     sender: tx2,
-                    config,
                 };
                 worker.run();
             });
             receivers.push(rx2);
             senders.push(tx1);
         }
-        Ok(ThreadConductor { receivers, senders })
+        Ok(ThreadConductor {
+            receivers,
+            senders,
+            chunks,
+            stepping: false,
+        })
     }
-    pub fn reset(&mut self) -> GameResult<Vec<PlayerState>> {
-        for sender in &mut self.senders {
-            sender.send(Instruction::Reset).compat()?;
-        }
-        let mut result = vec![];
-        for res in self.receivers.iter_mut().map(|rx| rx.recv().compat()) {
-            result.push(res??);
-        }
-        Ok(result)
+    fn num_games(&self) -> usize {
+        self.chunks.last().map_or(0, |c| c.end)
     }
-    pub fn seed(&mut self, seeds: Vec<u128>) -> GameResult<()> {
-        for (sender, seed) in self.senders.iter_mut().zip(seeds) {
-            sender.send(Instruction::Seed(seed)).compat()?;
+    fn send_all(&mut self, mut inst: impl FnMut(Range<usize>) -> Instruction) -> GameResult<()> {
+        if self.stepping {
+            bail!("ThreadConductor: step_wait has to be called after step_async");
+        }
+        for (sender, chunk) in self.senders.iter_mut().zip(&self.chunks) {
+            sender.send(inst(chunk.clone())).compat()?;
         }
         Ok(())
     }
-    pub fn states(&mut self) -> GameResult<Vec<PlayerState>> {
-        for sender in &mut self.senders {
-            sender.send(Instruction::State).compat()?;
+    /// receives replies from all workers, and returns the first error if any
+    fn recv_all(&mut self) -> GameResult<Vec<Reply>> {
+        // every reply has to be read, otherwise the next instruction gets a stale one
+        let replies: Vec<_> = self
+            .receivers
+            .iter()
+            .map(|rx| rx.recv().compat().and_then(|reply| reply))
+            .collect();
+        replies.into_iter().collect()
+    }
+    fn recv_done(&mut self) -> GameResult<()> {
+        self.recv_all().map(|_| ())
+    }
+    fn recv_states(&mut self) -> GameResult<Vec<PlayerState>> {
+        let mut result = vec![];
+        for reply in self.recv_all()? {
+            match reply {
+                Reply::States(states) => result.extend(states),
+                _ => bail!("ThreadConductor: unexpected reply"),
+            }
         }
+        Ok(result)
+    }
+    /// lets workers write the states of their games into `batch`, and returns rewards and
+    /// terminal flags
+    fn write(&mut self, batch: &BatchView) -> GameResult<Vec<(f32, bool)>> {
+        self.send_all(|chunk| Instruction::Write(batch.chunk(chunk)))?;
         let mut result = vec![];
-        for res in self.receivers.iter_mut().map(|rx| rx.recv().compat()) {
-            result.push(res??);
+        for reply in self.recv_all()? {
+            match reply {
+                Reply::Written(scalars) => result.extend(scalars),
+                _ => bail!("ThreadConductor: unexpected reply"),
+            }
         }
         Ok(result)
     }
-    pub fn step(&mut self, inputs: Vec<u8>) -> GameResult<Vec<PlayerState>> {
-        for (sender, input) in self.senders.iter_mut().zip(inputs) {
-            sender.send(Instruction::Step(input)).compat()?;
+    pub fn reset(&mut self) -> GameResult<Vec<PlayerState>> {
+        self.send_all(|_| Instruction::Reset)?;
+        self.recv_done()?;
+        self.states()
+    }
+    /// same as `reset`, but writes states into `batch`
+    pub fn reset_batched(&mut self, batch: &BatchView) -> GameResult<Vec<(f32, bool)>> {
+        self.send_all(|_| Instruction::Reset)?;
+        self.recv_done()?;
+        self.write(batch)
+    }
+    pub fn seed(&mut self, seeds: Vec<u128>) -> GameResult<()> {
+        self.send_all(|chunk| {
+            let seeds = seeds.iter().skip(chunk.start).take(chunk.len());
+            Instruction::Seed(seeds.cloned().collect())
+        })
+    }
+    pub fn states(&mut self) -> GameResult<Vec<PlayerState>> {
+        self.send_all(|_| Instruction::State)?;
+        self.recv_states()
+    }
+    /// sends inputs to all games, without waiting for the results
+    pub fn step_async(&mut self, inputs: Vec<u8>) -> GameResult<()> {
+        if inputs.len() != self.num_games() {
+            bail!(
+                "ThreadConductor: {} inputs for {} games",
+                inputs.len(),
+                self.num_games()
+            );
         }
-        let mut result = vec![];
-        for res in self.receivers.iter_mut().map(|rx| rx.recv().compat()) {
-            result.push(res??);
+        self.send_all(|chunk| Instruction::Step(inputs[chunk].to_vec()))?;
+        self.stepping = true;
+        Ok(())
+    }
+    /// waits for the results of `step_async`
+    /// terminal games are reset, but their states keep `is_terminal` and the last reward
+    pub fn step_wait(&mut self) -> GameResult<Vec<PlayerState>> {
+        self.wait_stepping()?;
+        self.states()
+    }
+    /// same as `step_wait`, but writes states into `batch`
+    pub fn step_wait_batched(&mut self, batch: &BatchView) -> GameResult<Vec<(f32, bool)>> {
+        self.wait_stepping()?;
+        self.write(batch)
+    }
+    fn wait_stepping(&mut self) -> GameResult<()> {
+        if !self.stepping {
+            bail!("ThreadConductor: step_wait is called without step_async");
         }
-        for (i, res) in result.iter().enumerate() {
-            if res.is_terminal {
-                self.senders[i].send(Instruction::Reset).compat()?;
+        self.stepping = false;
+        let mut errors = vec![];
+        for reply in self.recv_all()? {
+            match reply {
+                Reply::Stepped(e) => errors.extend(e),
+                _ => bail!("ThreadConductor: unexpected reply"),
             }
         }
-        for (i, res) in result.iter_mut().enumerate() {
-            if res.is_terminal {
-                *res = self.receivers[i].recv().compat()??;
-                res.is_terminal = true;
-            }
+        if errors.is_empty() {
+            return Ok(());
         }
-        Ok(result)
+        let msg: Vec<_> = errors
+            .iter()
+            .map(|(i, e)| format!("env {}: {}", i, e))
+            .collect();
+        bail!("ThreadConductor: failed to step {}", msg.join(", "))
+    }
+    pub fn step(&mut self, inputs: Vec<u8>) -> GameResult<Vec<PlayerState>> {
+        self.step_async(inputs)?;
+        self.step_wait()
+    }
+    /// same as `step`, but writes states into `batch`
+    pub fn step_batched(
+        &mut self,
+        inputs: Vec<u8>,
+        batch: &BatchView,
+    ) -> GameResult<Vec<(f32, bool)>> {
+        self.step_async(inputs)?;
+        self.step_wait_batched(batch)
+    }
+    /// starts recording trajectories of all games into `writer`, or stops it
+    pub fn record(&mut self, writer: Option<SharedWriter>) -> GameResult<()> {
+        self.send_all(|_| Instruction::Record(writer.clone()))?;
+        self.recv_done()
     }
     pub fn close(&mut self) -> GameResult<()> {
-        for sender in &mut self.senders {
-            sender.send(Instruction::Stop).compat()?;
+        if self.stepping {
+            self.wait_stepping()?;
         }
-        Ok(())
+        self.send_all(|_| Instruction::Stop)
     }
 }
 
 /// Thread instruction
 /// have no 'stop' or 'close', becase they're not integrated with python's GC well.
-#[derive(Clone, Debug)]
 enum Instruction {
-    Step(u8),
+    Step(Vec<u8>),
     Reset,
-    Seed(u128),
+    Seed(Vec<u128>),
     State,
+    /// writes states into the chunk of the batch
+    Write(BatchView),
+    Record(Option<SharedWriter>),
     Stop,
 }
 
 unsafe impl Send for Instruction {}
 
 struct ThreadWorker {
-    game_state: GameStateImpl,
-    config: GameConfig,
+    games: Vec<(GameStateImpl, GameConfig)>,
+    /// env index of the first game
+    offset: usize,
     receiver: Receiver<Instruction>,
-    sender: SyncSender<GameResult<PlayerState>>,
+    sender: SyncSender<GameResult<Reply>>,
 }
 
 impl ThreadWorker {
     fn run(&mut self) {
         for inst in self.receiver.iter() {
-            match inst {
-                Instruction::Step(code) => {
-                    let res = self.game_state.react(code).map(|_| self.game_state.state());
-                    self.sender.send(res)
-                }
-                Instruction::Reset => {
-                    let res = self
-                        .game_state
-                        .reset(self.config.clone())
-                        .map(|_| self.game_state.state());
-                    self.sender.send(res)
-                }
-                Instruction::Seed(seed) => {
-                    self.config.seed = Some(seed);
+            let res = match inst {
+                Instruction::Step(inputs) => Ok(Self::step(&mut self.games, self.offset, inputs)),
+                Instruction::Reset => self
+                    .games
+                    .iter_mut()
+                    .try_for_each(|(game, config)| game.reset(config.clone()))
+                    .map(|_| Reply::Done),
+                Instruction::Seed(seeds) => {
+                    for ((_, config), seed) in self.games.iter_mut().zip(seeds) {
+                        config.seed = Some(seed);
+                    }
                     continue;
                 }
-                Instruction::State => self.sender.send(Ok(self.game_state.state())),
+                Instruction::State => self
+                    .games
+                    .iter()
+                    .map(|(game, _)| game.state())
+                    .collect::<GameResult<_>>()
+                    .map(Reply::States),
+                Instruction::Write(batch) => Self::write(&self.games, &batch),
+                Instruction::Record(writer) => self
+                    .games
+                    .iter_mut()
+                    .try_for_each(|(game, _)| game.record(writer.clone()))
+                    .map(|_| Reply::Done),
                 Instruction::Stop => break,
+            };
+            self.sender.send(res).expect("ThreadWorker: disconnected")
+        }
+    }
+    /// steps all games, even if some of them fail
+    fn step(games: &mut [(GameStateImpl, GameConfig)], offset: usize, inputs: Vec<u8>) -> Reply {
+        let mut errors = vec![];
+        for (i, ((game, config), input)) in games.iter_mut().zip(inputs).enumerate() {
+            let res = game.react(input).map(|_| ()).and_then(|_| {
+                if game.state_ref().is_terminal {
+                    // reset the game, keeping what happened in the terminal step
+                    game.restart(config.clone())
+                } else {
+                    Ok(())
+                }
+            });
+            if let Err(e) = res {
+                errors.push((offset + i, e));
             }
-            .expect("ThreadWorker: disconnected")
         }
+        Reply::Stepped(errors)
+    }
+    fn write(games: &[(GameStateImpl, GameConfig)], batch: &BatchView) -> GameResult<Reply> {
+        let mut result = vec![];
+        for (i, (game, _)) in games.iter().enumerate() {
+            let state = game.state_ref();
+            // safe because chunks of workers don't overlap and the conductor waits for us
+            let mask = ai_action_mask(&game.runtime);
+            unsafe { batch.write(i, state, &mask)? };
+            result.push((state.reward.total(), state.is_terminal));
+        }
+        Ok(Reply::Written(result))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use rogue_gym_core::input::KeyMap;
     const CONFIG: &str = r#"
 {
     "width": 32,
@@ -160,8 +401,14 @@ mod test {
     fn test_threads() {
         use std::iter::repeat_with;
         let config = GameConfig::from_json(CONFIG).unwrap();
-        let mut threads =
-            ThreadConductor::new(repeat_with(|| config.clone()).take(8).collect(), 100).unwrap();
+        let mut threads = ThreadConductor::new(
+            repeat_with(|| config.clone()).take(8).collect(),
+            100,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
         let states = threads.states().unwrap();
         for state in &states {
             assert_eq!(*state, states[0]);
@@ -174,4 +421,116 @@ mod test {
         }
         assert!(!same);
     }
+    #[test]
+    fn deterministic_in_pools() {
+        let configs: Vec<_> = (0..7)
+            .map(|seed| {
+                let mut config = GameConfig::from_json(CONFIG).unwrap();
+                config.seed = Some(seed);
+                config.enemies.enemies.clear();
+                config
+            })
+            .collect();
+        let run = |threads| {
+            let mut pool =
+                ThreadConductor::new(configs.clone(), 20, false, false, Some(threads)).unwrap();
+            let mut states = vec![];
+            for c in "hjklyubnhhjjkkll>.hjklyubn".bytes() {
+                pool.step_async(vec![c; 7]).unwrap();
+                states.push(pool.step_wait().unwrap());
+            }
+            pool.close().unwrap();
+            states
+        };
+        let expected = run(1);
+        assert!(expected.iter().any(|s| s.iter().any(|s| s.is_terminal)));
+        for &threads in &[2, 3, 7, 16] {
+            assert_eq!(run(threads), expected);
+        }
+    }
+    #[test]
+    fn write_batches() {
+        use ndarray::{Array2, Array3, Array4};
+        use rogue_gym_core::observation::{Encoding, StatusFlag};
+        let configs: Vec<_> = (0..5)
+            .map(|seed| {
+                let mut config = GameConfig::from_json(CONFIG).unwrap();
+                config.seed = Some(seed);
+                config.enemies.enemies.clear();
+                config
+            })
+            .collect();
+        let symbols = configs[0].symbol_table().unwrap();
+        let mut pool = ThreadConductor::new(configs, 100, false, false, Some(2)).unwrap();
+        for &encoding in &[Encoding::Float, Encoding::Index] {
+            let setting = ImageSetting {
+                encoding,
+                separate_status: true,
+                ..ImageSetting::new(false, StatusFlag(StatusFlag::HP_CURRENT), true)
+            };
+            let [c, h, w] = setting.shape(&symbols, (16, 32));
+            let mut floats = Array4::<f32>::zeros([5, c, h, w]);
+            let mut ints = Array4::<u8>::zeros([5, c, h, w]);
+            let mut status = Array2::<i32>::zeros([5, 1]);
+            let mut masks = Array2::<bool>::from_elem([5, KeyMap::AI_KEYS.len()], false);
+            let batch = BatchView {
+                setting,
+                images: match encoding {
+                    Encoding::Float => BatchImagesView::Float(floats.raw_view_mut()),
+                    _ => BatchImagesView::Int(ints.raw_view_mut()),
+                },
+                status: status.raw_view_mut(),
+                masks: masks.raw_view_mut(),
+            };
+            let scalars = pool.step_batched(b"hjklh".to_vec(), &batch).unwrap();
+            for (i, state) in pool.states().unwrap().iter().enumerate() {
+                assert_eq!(scalars[i], (state.reward.total(), state.is_terminal));
+                assert_eq!(status[[i, 0]], state.status.hp.current.0 as i32);
+                assert_eq!(masks.row(i).to_vec(), state.action_mask);
+                match encoding {
+                    Encoding::Float => {
+                        let mut expected = Array3::zeros([c, h, w]);
+                        state.encode(&setting, expected.view_mut()).unwrap();
+                        assert_eq!(floats.index_axis(Axis(0), i), expected);
+                    }
+                    _ => {
+                        let mut expected = Array3::zeros([c, h, w]);
+                        state.encode_u8(&setting, expected.view_mut()).unwrap();
+                        assert_eq!(ints.index_axis(Axis(0), i), expected);
+                    }
+                }
+            }
+        }
+    }
+    #[test]
+    fn step_after_failure() {
+        let configs: Vec<_> = (0..4)
+            .map(|seed| {
+                let mut config = GameConfig::from_json(CONFIG).unwrap();
+                config.seed = Some(seed);
+                config.enemies.enemies.clear();
+                config
+            })
+            .collect();
+        let mut pool = ThreadConductor::new(configs.clone(), 100, false, false, Some(2)).unwrap();
+        // game 0 gets an invalid input
+        let err = pool.step(vec![0, b'h', b'h', b'h']).unwrap_err();
+        assert!(err.to_string().contains("env 0"), "{}", err);
+        let states = pool.step(vec![b'j'; 4]).unwrap();
+        let mut expected = ThreadConductor::new(configs, 100, false, false, Some(1)).unwrap();
+        expected.step(vec![b'h'; 4]).unwrap();
+        let expected = expected.step(vec![b'j'; 4]).unwrap();
+        assert_eq!(states[1..], expected[1..]);
+        assert_eq!(pool.states().unwrap(), states);
+    }
+    #[test]
+    fn step_wait_without_async() {
+        let config = GameConfig::from_json(CONFIG).unwrap();
+        let mut pool = ThreadConductor::new(vec![config; 2], 100, false, false, Some(1)).unwrap();
+        assert!(pool.step_wait().is_err());
+        assert!(pool.step(vec![b'h']).is_err());
+        pool.step_async(vec![b'h'; 2]).unwrap();
+        assert!(pool.states().is_err());
+        assert_eq!(pool.step_wait().unwrap().len(), 2);
+    }
 }
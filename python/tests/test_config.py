@@ -0,0 +1,92 @@
+"""test for typed configs"""
+import json
+import os
+import warnings
+from rogue_gym.envs import DungeonStyle, EnemiesConfig, GameConfig, RogueConfig, RogueEnv
+from rogue_gym_python._rogue_gym import GameState
+
+
+def raises(f, exc_type) -> str:
+    try:
+        f()
+    except exc_type as e:
+        return str(e)
+    raise AssertionError('{} was not raised'.format(exc_type))
+
+
+def test_fields():
+    config = GameConfig(seed=1, width=80, dungeon={'room_num_x': 4})
+    assert config.seed == 1
+    assert config.dungeon.style == 'rogue'
+    assert config.dungeon.rogue.room_num_x == 4
+    # nested configs are shared, not copied
+    config.dungeon.rogue.room_num_y = 2
+    config.enemies.enemies = []
+    assert config.to_dict()['dungeon']['room_num_y'] == 2
+    assert config.to_dict()['enemies']['enemies'] == []
+    config.enemies = EnemiesConfig(appear_rate_gold=50)
+    assert config.enemies.enemies != []
+    config.dungeon = DungeonStyle(room_num_x=2)
+    assert config.dungeon.rogue == RogueConfig(room_num_x=2)
+
+
+def test_round_trip():
+    path = os.path.join(os.path.dirname(__file__), '../../data/config-default.json')
+    with open(path) as f:
+        d = json.load(f)
+    config = GameConfig.from_dict(d)
+    assert GameConfig.from_dict(config.to_dict()) == config
+    assert config != GameConfig(seed=2)
+
+
+def test_errors():
+    msg = raises(lambda: GameConfig(dungeon={'room_num': 3}), ValueError)
+    assert msg.startswith('Unknown field dungeon.room_num, expected one of room_num_x')
+    msg = raises(lambda: GameConfig.from_dict({'dungeon': {'treasure_room': {'rate': 1}}}),
+                 ValueError)
+    assert msg == 'Unknown field dungeon.treasure_room.rate'
+    msg = raises(lambda: GameConfig(player={'init_hp': 'many'}), ValueError)
+    assert msg.startswith('player.init_hp: invalid type')
+    msg = raises(lambda: GameConfig(dungeon={'style': 'nethack'}), ValueError)
+    assert msg.startswith('dungeon.style: only rogue is implemented')
+    config = GameConfig()
+    msg = raises(lambda: setattr(config, 'width', 'wide'), ValueError)
+    assert msg.startswith('width: invalid type')
+    raises(lambda: RogueEnv(seeed=1), ValueError)
+
+
+def test_validate():
+    assert GameConfig().validate() == []
+    config = GameConfig(dungeon={'room_num_x': 0}, enemies={'enemies': [0, 30]})
+    assert config.validate() == [
+        'dungeon.room_num_x: must be positive',
+        'enemies.enemies[1]: no builtin enemy 30, there are 26',
+    ]
+    msg = raises(lambda: RogueEnv(config_dict=config), RuntimeError)
+    assert 'dungeon.room_num_x: must be positive' in msg
+
+
+def test_env():
+    config = GameConfig(seed=1, enemies={'enemies': []})
+    env1, env2 = RogueEnv(config_dict=config), RogueEnv(seed=1, enemies={'enemies': []})
+    assert env1.get_dungeon() == env2.get_dungeon()
+    assert env1.get_config() == env2.get_config()
+
+
+def test_symbols():
+    config = GameConfig(enemies={'enemies': []})
+    assert GameState(100, json.dumps(config.to_dict())).symbol_table()[:5] == [' ', '@', '#', '.', '-|']
+    config.symbols = {'enemies': 'one', 'items': 'one'}
+    table = GameState(100, json.dumps(config.to_dict())).symbol_table()
+    assert len(table) == 10 and table[-1] == 'ABCDEFGHIJKLMNOPQRSTUVWXYZ'
+    config.symbols = {'channels': ['@', '#.', '-#']}
+    assert config.validate() == ["symbols.channels[2]: '#' is already in channel 1"]
+
+
+def test_config_str():
+    config = json.dumps({'width': 40, 'height': 20})
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter('always')
+        assert GameState(100, config_str=config).screen_size() == (20, 40)
+    assert caught[0].category is DeprecationWarning
+    raises(lambda: GameState(100, config, config_str=config), TypeError)
@@ -1,5 +1,7 @@
 """test for ParallelRogueEnv"""
-from rogue_gym.envs import StairRewardParallel, ParallelRogueEnv
+import numpy as np
+from rogue_gym.envs import Encoding, ImageSetting, StairRewardParallel, StatusFlag, \
+    ParallelRogueEnv
 from data import CMD_STR, CMD_STR3, CMD_STR4, \
     CMD_STR5, SEED1_DUNGEON, SEED1_DUNGEON2, SEED1_DUNGEON3
 
@@ -76,3 +78,46 @@ def test_stair_reward() -> None:
         _, rewards, *_ = env.step([0] * NUM_WOKRERS)
         for r in rewards:
             assert r >= 0.0
+
+
+def test_step_batched() -> None:
+    setting = ImageSetting(
+        status=StatusFlag.DUNGEON_LEVEL | StatusFlag.HP_CURRENT,
+        includes_hist=True,
+    )
+    env = ParallelRogueEnv(config_dicts=[CONFIG_NOENEM] * NUM_WOKRERS, image_setting=setting)
+    images = env.reset_batched()
+    assert images.shape == (NUM_WOKRERS, *env.observation_space.shape)
+    for image, state in zip(images, env.game.states()):
+        assert np.array_equal(image, setting.expand(state))
+    images, rewards, dones, info = env.step_batched('h' * NUM_WOKRERS)
+    for image, state in zip(images, env.game.states()):
+        assert np.array_equal(image, setting.expand(state))
+    assert rewards.shape == (NUM_WOKRERS,)
+    assert not dones.any()
+    assert info['status'].shape == (NUM_WOKRERS, 2)
+    assert info['action_masks'].shape == (NUM_WOKRERS, env.ACTION_LEN)
+
+
+def test_step_batched_packed() -> None:
+    setting = ImageSetting(status=StatusFlag.HP_CURRENT, encoding=Encoding.PACKED)
+    env = ParallelRogueEnv(config_dicts=[CONFIG_NOENEM] * NUM_WOKRERS, image_setting=setting)
+    images = env.reset_batched()
+    assert images.dtype == np.uint8
+    assert images.shape == (NUM_WOKRERS, *env.observation_space.shape)
+    images, *_, info = env.step_batched('h' * NUM_WOKRERS)
+    for image, state in zip(images, env.game.states()):
+        assert np.array_equal(image, setting.expand(state))
+    assert info['status'].shape == (NUM_WOKRERS, 1)
+
+
+def test_step_async() -> None:
+    env = ParallelRogueEnv(config_dicts=[CONFIG_NOENEM] * NUM_WOKRERS, threads=3)
+    expected = ParallelRogueEnv(config_dicts=[CONFIG_NOENEM] * NUM_WOKRERS, threads=1)
+    for c in CMD_STR:
+        env.step_async(c * NUM_WOKRERS)
+        states, rewards, dones, _ = env.step_wait()
+        states2, rewards2, dones2, _ = expected.step(c * NUM_WOKRERS)
+        assert [s.dungeon for s in states] == [s.dungeon for s in states2]
+        assert rewards == rewards2
+        assert dones == dones2
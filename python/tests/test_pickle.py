@@ -0,0 +1,54 @@
+"""test for pickling and copying states"""
+import copy
+import json
+import pickle
+from rogue_gym.envs import RogueEnv
+from rogue_gym_python._rogue_gym import GameState
+
+CONFIG = {'seed': 3, 'enemies': {'enemies': []}}
+KEYS = 'hjklllljjkkhh.s>'
+
+
+def play(game: GameState, keys: str) -> None:
+    for key in keys:
+        try:
+            game.react(ord(key))
+        except RuntimeError:
+            pass
+
+
+def test_player_state():
+    game = GameState(100, json.dumps(CONFIG))
+    play(game, KEYS)
+    state = game.prev()
+    for restored in [pickle.loads(pickle.dumps(state)), copy.deepcopy(state)]:
+        assert restored.dungeon == state.dungeon
+        assert restored.status == state.status
+        assert restored.info == state.info
+        assert restored.state_hash() == state.state_hash()
+        assert restored.state_hash('known') == state.state_hash('known')
+        assert (restored.gray_image() == state.gray_image()).all()
+
+
+def test_game_state():
+    game = GameState(100, json.dumps(CONFIG))
+    play(game, KEYS)
+    for restored in [pickle.loads(pickle.dumps(game)), copy.deepcopy(game)]:
+        assert restored.state_hash('full') == game.state_hash('full')
+        assert restored.dump_config() == game.dump_config()
+        play(restored, 'jjl')
+        assert restored.state_hash('full') != game.state_hash('full')
+    play(game, 'jjl')
+    assert restored.state_hash('full') == game.state_hash('full')
+
+
+def test_env():
+    env = RogueEnv(config_dict=CONFIG, max_steps=20)
+    for key in KEYS:
+        env.step(key)
+    restored = pickle.loads(pickle.dumps(env))
+    assert restored.get_dungeon() == env.get_dungeon()
+    _, _, done1, _ = env.step('j')
+    _, _, done2, _ = restored.step('j')
+    assert done1 == done2
+    assert restored.get_dungeon() == env.get_dungeon()
@@ -1,9 +1,11 @@
 """test for RogueEnv """
 from data import CMD_STR, SEED1_DUNGEON, SEED1_DUNGEON2
 import gym
+import json
 from gym import spaces
 import numpy as np
-from rogue_gym.envs import StatusFlag, RogueEnv
+from rogue_gym.envs import Action, ActionSpace, Direction, DungeonType, Encoding, \
+    ImageSetting, InputCode, StatusFlag, RogueEnv, System, TrajectoryReader, verify_replay
 
 CONFIG_NOENEM = {
     "seed": 1,
@@ -46,6 +48,206 @@ def test_kwargs_setting():
     assert env.screen_size() == (24, 48)
 
 
+def test_reward_terms():
+    reward = {'gold': 1.0, 'explore': 0.1, 'time': -0.01}
+    env = RogueEnv(config_dict=dict(CONFIG_NOENEM, reward=reward))
+    _, r, _, info = env.step(CMD_STR)
+    terms = info['reward_terms']
+    assert abs(r - sum(terms.values())) < 1e-4
+    assert abs(terms['time'] + 0.01 * len(CMD_STR)) < 1e-4
+    assert terms['explore'] > 0.0
+
+
+def test_step_info():
+    env = RogueEnv(config_dict=CONFIG_NOENEM, max_steps=5)
+    _, _, done, info = env.step('h')
+    assert not done
+    assert info['termination'] is None
+    assert 'episode' not in info
+    assert all('kind' in msg for msg in info['messages'])
+    _, _, done, info = env.step('hhhh')
+    assert done
+    assert info['termination'] == 'max_steps'
+    episode = info['episode']
+    assert episode['steps'] == 5
+    assert episode['max_level'] == 1
+    assert episode['termination'] == 'max_steps'
+
+
+def test_trajectory(tmp_path):
+    fname = str(tmp_path / 'trajectory.bin')
+    env = RogueEnv(config_dict=CONFIG_NOENEM, max_steps=5)
+    env.start_recording(fname)
+    env.step('hhhhh')
+    env.reset()
+    env.step('ll')
+    env.stop_recording()
+    batches = list(TrajectoryReader(fname, batch_size=4))
+    assert [len(b['action']) for b in batches] == [4, 3]
+    first = batches[0]
+    assert first['map'].shape == (4, 24, 80)
+    assert first['history'].shape == (4, 24, 80)
+    assert first['status'].shape[0] == 4
+    assert list(first['action']) == [ord('h')] * 4
+    assert (first['map'][1:] == first['next_map'][:-1]).all()
+    assert list(batches[1]['done']) == [True, False, False]
+    assert list(batches[1]['episode']) == [0, 1, 1]
+    assert batches[1]['info'][0]['termination'] == 'max_steps'
+
+
+def test_replay(tmp_path):
+    fname = str(tmp_path / 'replay.json')
+    env = RogueEnv(config_dict=CONFIG_NOENEM)
+    env.step('hjkl')
+    env.save_replay(fname)
+    replay = json.loads(open(fname).read())
+    assert replay['config'] == env.get_config()
+    assert len(replay['inputs']) == 4
+    assert len(replay['hashes']) == 5
+    assert verify_replay(fname) is None
+    replay['hashes'][3] ^= 1
+    with open(fname, 'w') as f:
+        json.dump(replay, f)
+    assert verify_replay(fname).startswith('diverged at step 3')
+
+
+def test_state_hash():
+    env = RogueEnv(config_dict=CONFIG_NOENEM)
+    for act in 'hjkl':
+        state, *_ = env.step(act)
+        for granularity in ['known', 'map_and_status']:
+            assert state.state_hash(granularity) == env.game.state_hash(granularity)
+    env2 = RogueEnv(config_dict=CONFIG_NOENEM)
+    env2.step('hjkl')
+    assert env2.game.state_hash('full') == env.game.state_hash('full')
+    env.reset()
+    assert env.result.state_hash() == RogueEnv(config_dict=CONFIG_NOENEM).result.state_hash()
+
+
+def test_entities():
+    env = RogueEnv(seed=1, hide_dungeon=False)
+    entities = env.result.entities
+    assert entities['stair'] is not None
+    assert all('kind' in item for item in entities['items'])
+    assert all(enemy['hp_rate'] is None for enemy in entities['enemies'])
+    tensor = env.result.entity_tensor(8, 8)
+    assert tensor.shape == (18, 7)
+    assert tensor[16][0] == 1.0
+
+
+def test_description():
+    try:
+        RogueEnv(config_dict=CONFIG_NOENEM).result.description
+        assert False, 'description is made without describe=True'
+    except RuntimeError:
+        pass
+    env = RogueEnv(config_dict=CONFIG_NOENEM, describe=True)
+    state = env.result
+    assert state.description.startswith('You are on level 1, in a ')
+    assert state.description == state.describe('normal')
+    brief, verbose = state.describe('brief'), state.describe('verbose')
+    assert len(brief) < len(state.description) < len(verbose)
+    assert 'HP 12/12' in brief
+    try:
+        state.describe('loud')
+        assert False, 'invalid verbosity is accepted'
+    except RuntimeError:
+        pass
+
+
+def test_egocentric_images():
+    env = RogueEnv(config_dict=CONFIG_NOENEM)
+    state = env.result
+    setting = ImageSetting(status=StatusFlag.EMPTY, crop=(5, 3))
+    img = setting.expand(state)
+    assert img.shape == setting.detect_space(24, 80, env.game.symbols()).shape
+    assert img.shape == (env.game.symbols(), 7, 11)
+    assert img[1][3][5] == 1.0
+    setting = ImageSetting(
+        dungeon=DungeonType.GRAY,
+        status=StatusFlag.DUNGEON_LEVEL,
+        includes_hist=True,
+        crop=(5, 3),
+        multiscale=True,
+    )
+    img = setting.expand(state)
+    assert img.shape == (5, 7, 11)
+    assert img.shape == setting.detect_space(24, 80, env.game.symbols()).shape
+
+
+def test_compact_encodings():
+    env = RogueEnv(config_dict=CONFIG_NOENEM)
+    state = env.result
+    symbols = env.game.symbols()
+    onehot = ImageSetting(status=StatusFlag.EMPTY, includes_hist=True).expand(state)
+    setting = ImageSetting(includes_hist=True, encoding=Encoding.INDEX)
+    img = setting.expand(state)
+    assert img.dtype == np.uint8
+    assert img.shape == setting.detect_space(24, 80, symbols).shape == (2, 24, 80)
+    assert np.array_equal(img[0], onehot[:symbols].argmax(axis=0) + 1)
+    assert np.array_equal(img[1], onehot[symbols])
+    setting = ImageSetting(includes_hist=True, encoding=Encoding.PACKED)
+    img = setting.expand(state)
+    assert img.shape == setting.detect_space(24, 80, symbols).shape == (symbols + 1, 24, 10)
+    assert np.array_equal(np.unpackbits(img, axis=-1)[..., :80], onehot)
+    setting = ImageSetting(status=StatusFlag.HP_CURRENT, separate_status=True)
+    assert setting.expand(state).shape == (symbols, 24, 80)
+    assert setting.status_vec(state) == [12]
+
+
+def test_action_mask():
+    env = RogueEnv(config_dict=CONFIG_NOENEM)
+    mask = env.action_mask()
+    assert mask.shape == (RogueEnv.ACTION_LEN,)
+    assert not mask[RogueEnv.ACTIONS.index('.')]
+    assert mask[RogueEnv.ACTIONS.index('s')]
+    for i, act in enumerate(RogueEnv.ACTIONS):
+        if act not in 'hjklnbuy':
+            continue
+        before = env.result.dungeon
+        env.step(act)
+        assert mask[i] == (env.result.dungeon != before)
+        mask = env.action_mask()
+
+
+def test_typed_actions():
+    assert len(Action.all()) == 36
+    assert InputCode.from_key('h') == InputCode.act(Action.move(Direction.Left))
+    assert InputCode.from_key('s').action.kind == 'search'
+    env1, env2 = RogueEnv(config_dict=CONFIG_NOENEM), RogueEnv(config_dict=CONFIG_NOENEM)
+    for key, act in [('h', Action.move(Direction.Left)), ('>', Action.down_stair())]:
+        state1, *_ = env1.step(key)
+        state2, *_ = env2.step(act)
+        assert state1.dungeon == state2.dungeon
+    # opening and closing the inventory takes no turn
+    inventory = [InputCode.sys(System.Inventory), InputCode.sys(System.Cancel)]
+    before = env2.result.dungeon
+    env2.step(inventory)
+    assert env2.result.dungeon == before
+    env = RogueEnv(
+        config_dict=CONFIG_NOENEM,
+        actions=ActionSpace([Action.search(), Action.move(Direction.Left), inventory]),
+    )
+    assert env.action_space == gym.spaces.discrete.Discrete(3)
+    assert env.actions.index(inventory) == 2
+    # system inputs are never legal actions
+    assert list(env.action_mask()) == [True, env1.action_mask()[1], False]
+    env.step(2)
+    assert ActionSpace.ai()[1] == [InputCode.from_key('h')]
+
+
+def test_viewport():
+    env = RogueEnv(seed=1, dungeon_width=160, dungeon_height=48)
+    assert env.screen_size() == (48, 160)
+    env = RogueEnv(seed=1, dungeon_width=160, dungeon_height=48, viewport=True)
+    assert env.screen_size() == (24, 80)
+    state, *_ = env.step('.')
+    assert len(state.dungeon) == 24
+    assert sum(row.count('@') for row in state.dungeon) == 1
+    for item in state.entities['items']:
+        assert state.dungeon[item['y']][item['x']] == item['tile']
+
+
 def test_images():
     env = RogueEnv(config_dict=CONFIG_NOENEM)
     state, *_ = env.step('H')
@@ -0,0 +1,107 @@
+//! a blocking client of the server
+use crate::protocol::{ActionArg, ObservationJson, Request, Response, SessionId};
+use crate::Address;
+use rogue_gym_core::env::StepInfo;
+use rogue_gym_core::error::*;
+use rogue_gym_core::GameConfig;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+pub struct Client {
+    reader: BufReader<Box<dyn Read + Send>>,
+    writer: Box<dyn Write + Send>,
+}
+
+fn missing(field: &'static str) -> failure::Error {
+    ErrorId::MaybeBug.into_with(move || format!("Client: no {} in the response", field))
+}
+
+impl Client {
+    pub fn connect(addr: &Address) -> GameResult<Self> {
+        let (reader, writer): (Box<dyn Read + Send>, Box<dyn Write + Send>) = match addr {
+            Address::Tcp(addr) => {
+                let stream = TcpStream::connect(addr).into_chained(|| "Client::connect")?;
+                (
+                    Box::new(stream.try_clone().into_chained(|| "Client::connect")?),
+                    Box::new(stream),
+                )
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                let stream = UnixStream::connect(path).into_chained(|| "Client::connect")?;
+                (
+                    Box::new(stream.try_clone().into_chained(|| "Client::connect")?),
+                    Box::new(stream),
+                )
+            }
+        };
+        Ok(Client {
+            reader: BufReader::new(reader),
+            writer,
+        })
+    }
+    /// sends a request and returns the response, which can be an error response
+    pub fn request(&mut self, req: &Request) -> GameResult<Response> {
+        let mut line = serde_json::to_string(req).into_chained(|| "Client::request")?;
+        line.push('\n');
+        self.writer
+            .write_all(line.as_bytes())
+            .and_then(|_| self.writer.flush())
+            .into_chained(|| "Client::request")?;
+        let mut line = String::new();
+        if self
+            .reader
+            .read_line(&mut line)
+            .into_chained(|| "Client::request")?
+            == 0
+        {
+            return Err(ErrorId::InvalidSetting.into_with(|| "Client: the server closed"));
+        }
+        serde_json::from_str(&line).into_chained(|| "Client::request")
+    }
+    fn call(&mut self, req: &Request) -> GameResult<Response> {
+        self.request(req)?.into_result()
+    }
+    pub fn create(
+        &mut self,
+        config: Option<GameConfig>,
+        seed: Option<u128>,
+        max_steps: Option<usize>,
+    ) -> GameResult<(SessionId, ObservationJson)> {
+        let res = self.call(&Request::Create {
+            config: config.map(Box::new),
+            seed,
+            max_steps,
+        })?;
+        let session = res.session.ok_or_else(|| missing("session"))?;
+        let obs = res.observation.ok_or_else(|| missing("observation"))?;
+        Ok((session, obs))
+    }
+    pub fn reset(&mut self, session: SessionId, seed: Option<u128>) -> GameResult<ObservationJson> {
+        let res = self.call(&Request::Reset { session, seed })?;
+        res.observation.ok_or_else(|| missing("observation"))
+    }
+    /// returns (observation, reward, done, info)
+    pub fn step(
+        &mut self,
+        session: SessionId,
+        action: ActionArg,
+    ) -> GameResult<(ObservationJson, f32, bool, StepInfo)> {
+        let res = self.call(&Request::Step { session, action })?;
+        Ok((
+            res.observation.ok_or_else(|| missing("observation"))?,
+            res.reward.ok_or_else(|| missing("reward"))?,
+            res.done.ok_or_else(|| missing("done"))?,
+            res.info.ok_or_else(|| missing("info"))?,
+        ))
+    }
+    pub fn observe(&mut self, session: SessionId) -> GameResult<ObservationJson> {
+        let res = self.call(&Request::Observe { session })?;
+        res.observation.ok_or_else(|| missing("observation"))
+    }
+    pub fn close(&mut self, session: SessionId) -> GameResult<()> {
+        self.call(&Request::Close { session }).map(|_| ())
+    }
+}
@@ -0,0 +1,350 @@
+//! a server hosting game sessions for agents in other processes
+//!
+//! Clients connect via a Unix socket or localhost TCP, and talk the line-delimited
+//! json protocol in `protocol`. Sessions belong to the connection which created
+//! them, and are closed when it's closed.
+pub mod client;
+pub mod protocol;
+
+pub use crate::client::Client;
+pub use crate::protocol::{ActionArg, ObservationJson, Request, Response, SessionId};
+
+use rogue_gym_core::env::{Env, RogueEnv};
+use rogue_gym_core::error::*;
+use rogue_gym_core::GameConfig;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// where the server listens, written as `HOST:PORT` or `unix:PATH`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Address {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for Address {
+    type Err = failure::Error;
+    fn from_str(s: &str) -> GameResult<Self> {
+        #[cfg(unix)]
+        {
+            if let Some(path) = s.strip_prefix("unix:") {
+                return Ok(Address::Unix(PathBuf::from(path)));
+            }
+        }
+        let addr = s
+            .to_socket_addrs()
+            .into_chained(|| format!("Invalid address {}", s))?
+            .next()
+            .ok_or_else(|| ErrorId::InvalidSetting.into_with(|| "Address: no address"))?;
+        Ok(Address::Tcp(addr))
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            Address::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// connections served at the same time
+    pub max_connections: usize,
+    /// sessions alive at the same time, over all connections
+    pub max_sessions: usize,
+    /// steps per episode, used when `create` doesn't specify it
+    pub max_steps: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_connections: 16,
+            max_sessions: 64,
+            max_steps: 1000,
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+/// decrements the counter when dropped
+struct CountGuard(Arc<AtomicUsize>);
+
+impl CountGuard {
+    /// increments `counter` if it's less than `max`
+    fn acquire(counter: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        counter
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                if n < max {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| CountGuard(Arc::clone(counter)))
+    }
+}
+
+impl Drop for CountGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub struct Server {
+    listener: Listener,
+    limits: Limits,
+    connections: Arc<AtomicUsize>,
+    sessions: Arc<AtomicUsize>,
+}
+
+impl Server {
+    /// listens on `addr`, which has to be a loopback address for TCP
+    pub fn bind(addr: &Address, limits: Limits) -> GameResult<Self> {
+        let listener = match addr {
+            Address::Tcp(addr) => {
+                if !addr.ip().is_loopback() {
+                    return Err(ErrorId::InvalidSetting
+                        .into_with(|| "Server::bind: only localhost is allowed for TCP"));
+                }
+                Listener::Tcp(TcpListener::bind(addr).into_chained(|| "Server::bind")?)
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                use std::os::unix::fs::FileTypeExt;
+                // remove the socket left by the last run
+                if let Ok(meta) = std::fs::metadata(path) {
+                    if meta.file_type().is_socket() {
+                        std::fs::remove_file(path).into_chained(|| "Server::bind")?;
+                    }
+                }
+                Listener::Unix(UnixListener::bind(path).into_chained(|| "Server::bind")?)
+            }
+        };
+        Ok(Server {
+            listener,
+            limits,
+            connections: Arc::new(AtomicUsize::new(0)),
+            sessions: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+    /// the actual address, e.g. with the port assigned for `127.0.0.1:0`
+    pub fn local_addr(&self) -> GameResult<Address> {
+        match &self.listener {
+            Listener::Tcp(l) => Ok(Address::Tcp(
+                l.local_addr().into_chained(|| "Server::local_addr")?,
+            )),
+            #[cfg(unix)]
+            Listener::Unix(l) => {
+                let addr = l.local_addr().into_chained(|| "Server::local_addr")?;
+                let path = addr.as_pathname().ok_or_else(|| {
+                    ErrorId::MaybeBug.into_with(|| "Server::local_addr: unnamed socket")
+                })?;
+                Ok(Address::Unix(path.to_owned()))
+            }
+        }
+    }
+    /// accepts connections forever, serving each one in a thread
+    pub fn run(&self) -> GameResult<()> {
+        loop {
+            match &self.listener {
+                Listener::Tcp(l) => {
+                    let (stream, _) = l.accept().into_chained(|| "Server::run")?;
+                    let reader = stream.try_clone().into_chained(|| "Server::run")?;
+                    self.spawn(reader, stream);
+                }
+                #[cfg(unix)]
+                Listener::Unix(l) => {
+                    let (stream, _) = l.accept().into_chained(|| "Server::run")?;
+                    let reader = stream.try_clone().into_chained(|| "Server::run")?;
+                    self.spawn(reader, stream);
+                }
+            }
+        }
+    }
+    fn spawn<R, W>(&self, reader: R, mut writer: W)
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let guard = match CountGuard::acquire(&self.connections, self.limits.max_connections) {
+            Some(guard) => guard,
+            None => {
+                let err = ErrorId::InvalidSetting.into_with(|| "Too many connections");
+                let _ = write_response(&mut writer, &Response::error(&err));
+                return;
+            }
+        };
+        let (limits, sessions) = (self.limits.clone(), Arc::clone(&self.sessions));
+        // RunTime isn't Send, so sessions are created in the thread
+        thread::spawn(move || {
+            let _guard = guard;
+            let mut conn = Connection::new(limits, sessions);
+            let _ = conn.serve(BufReader::new(reader), writer);
+        });
+    }
+}
+
+fn write_response(writer: &mut impl Write, res: &Response) -> GameResult<()> {
+    let mut line = serde_json::to_string(res).into_chained(|| "write_response")?;
+    line.push('\n');
+    writer
+        .write_all(line.as_bytes())
+        .and_then(|_| writer.flush())
+        .into_chained(|| "write_response")
+}
+
+struct Session {
+    env: RogueEnv,
+    _guard: CountGuard,
+}
+
+/// sessions of a connection
+struct Connection {
+    sessions: HashMap<SessionId, Session>,
+    next_id: SessionId,
+    limits: Limits,
+    live_sessions: Arc<AtomicUsize>,
+}
+
+impl Connection {
+    fn new(limits: Limits, live_sessions: Arc<AtomicUsize>) -> Self {
+        Connection {
+            sessions: HashMap::new(),
+            next_id: 0,
+            limits,
+            live_sessions,
+        }
+    }
+    fn serve(&mut self, reader: impl BufRead, mut writer: impl Write) -> GameResult<()> {
+        for line in reader.lines() {
+            let line = line.into_chained(|| "Connection::serve")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let res = serde_json::from_str(&line)
+                .into_chained(|| "Invalid request")
+                .and_then(|req| self.handle(req))
+                .unwrap_or_else(|e| Response::error(&e));
+            write_response(&mut writer, &res)?;
+        }
+        Ok(())
+    }
+    fn session(&mut self, id: SessionId) -> GameResult<&mut RogueEnv> {
+        self.sessions
+            .get_mut(&id)
+            .map(|s| &mut s.env)
+            .ok_or_else(|| ErrorId::InvalidSetting.into_with(move || format!("No session {}", id)))
+    }
+    fn handle(&mut self, req: Request) -> GameResult<Response> {
+        match req {
+            Request::Create {
+                config,
+                seed,
+                max_steps,
+            } => {
+                let guard = CountGuard::acquire(&self.live_sessions, self.limits.max_sessions)
+                    .ok_or_else(|| ErrorId::InvalidSetting.into_with(|| "Too many sessions"))?;
+                let mut config = config.map_or_else(GameConfig::default, |c| *c);
+                if seed.is_some() {
+                    config.seed = seed;
+                }
+                let max_steps = max_steps.unwrap_or(self.limits.max_steps);
+                let env = RogueEnv::new(config, max_steps, false)?;
+                let obs = env.observe()?;
+                let id = self.next_id;
+                self.next_id += 1;
+                self.sessions.insert(id, Session { env, _guard: guard });
+                let mut res = Response::ok().with_observation(&obs);
+                res.session = Some(id);
+                Ok(res)
+            }
+            Request::Reset { session, seed } => {
+                let obs = self.session(session)?.reset(seed)?;
+                Ok(Response::ok().with_observation(&obs))
+            }
+            Request::Step { session, action } => {
+                let action = action.to_action()?;
+                let (obs, reward, done, info) = self.session(session)?.step(action)?;
+                let mut res = Response::ok().with_observation(&obs);
+                res.reward = Some(reward);
+                res.done = Some(done);
+                res.info = Some(info);
+                Ok(res)
+            }
+            Request::Observe { session } => {
+                let obs = self.session(session)?.observe()?;
+                Ok(Response::ok().with_observation(&obs))
+            }
+            Request::Close { session } => {
+                self.sessions.remove(&session).ok_or_else(|| {
+                    ErrorId::InvalidSetting.into_with(move || format!("No session {}", session))
+                })?;
+                Ok(Response::ok())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn address() {
+        let addr: Address = "127.0.0.1:8000".parse().unwrap();
+        assert_eq!(addr, Address::Tcp(([127, 0, 0, 1], 8000).into()));
+        assert_eq!(addr.to_string(), "127.0.0.1:8000");
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:/tmp/rogue.sock".parse::<Address>().unwrap(),
+            Address::Unix(PathBuf::from("/tmp/rogue.sock"))
+        );
+        assert!("rogue".parse::<Address>().is_err());
+        let public = Address::Tcp(([8, 8, 8, 8], 8000).into());
+        assert!(Server::bind(&public, Limits::default()).is_err());
+    }
+    #[test]
+    fn session_limit() {
+        let limits = Limits {
+            max_sessions: 1,
+            ..Limits::default()
+        };
+        let live = Arc::new(AtomicUsize::new(0));
+        let mut conn = Connection::new(limits, Arc::clone(&live));
+        let create = || Request::Create {
+            config: None,
+            seed: Some(1),
+            max_steps: None,
+        };
+        let res = conn.handle(create()).unwrap();
+        assert_eq!(res.session, Some(0));
+        assert!(conn.handle(create()).is_err());
+        assert_eq!(live.load(Ordering::SeqCst), 1);
+        conn.handle(Request::Close { session: 0 }).unwrap();
+        assert_eq!(live.load(Ordering::SeqCst), 0);
+        assert_eq!(conn.handle(create()).unwrap().session, Some(1));
+        drop(conn);
+        assert_eq!(live.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,68 @@
+use clap::{self, ArgMatches};
+use rogue_gym_core::error::*;
+use rogue_gym_server::{Address, Limits, Server};
+
+fn parse_args<'a>() -> ArgMatches<'a> {
+    clap::App::new("rogue-gym-server")
+        .version("0.1.0")
+        .author("Yuji Kanagawa <yuji.kngw.80s.revive@gmail.com>")
+        .about("host rogue-gym sessions for agents in other processes")
+        .arg(
+            clap::Arg::with_name("address")
+                .short("a")
+                .long("address")
+                .value_name("ADDRESS")
+                .help("HOST:PORT on localhost, or unix:PATH for a Unix socket")
+                .default_value("127.0.0.1:8765")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("max_connections")
+                .long("max-connections")
+                .value_name("N")
+                .help("Number of clients served at the same time")
+                .default_value("16")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("max_sessions")
+                .long("max-sessions")
+                .value_name("N")
+                .help("Number of sessions alive at the same time")
+                .default_value("64")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("max_steps")
+                .long("max-steps")
+                .value_name("N")
+                .help("Default max steps of episodes")
+                .default_value("1000")
+                .takes_value(true),
+        )
+        .get_matches()
+}
+
+fn get_arg<T>(args: &ArgMatches, value: &'static str) -> GameResult<T>
+where
+    T: ::std::str::FromStr,
+    T::Err: ::std::error::Error + Send + Sync + 'static,
+{
+    args.value_of(value)
+        .unwrap()
+        .parse()
+        .into_chained(move || format!("Failed to parse '{}' arg!", value))
+}
+
+fn main() -> GameResult<()> {
+    let args = parse_args();
+    let addr: Address = args.value_of("address").unwrap().parse()?;
+    let limits = Limits {
+        max_connections: get_arg(&args, "max_connections")?,
+        max_sessions: get_arg(&args, "max_sessions")?,
+        max_steps: get_arg(&args, "max_steps")?,
+    };
+    let server = Server::bind(&addr, limits)?;
+    println!("rogue-gym-server listening on {}", server.local_addr()?);
+    server.run()
+}
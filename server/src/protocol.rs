@@ -0,0 +1,235 @@
+//! line-delimited json protocol
+//!
+//! Each line sent by a client is a `Request`, tagged by `"cmd"`, and the server
+//! answers each request by a line of `Response`. e.g.
+//! ```text
+//! > {"cmd": "create", "config": {"seed": 1}, "max_steps": 100}
+//! < {"ok":true,"session":0,"observation":{...}}
+//! > {"cmd": "step", "session": 0, "action": "j"}
+//! < {"ok":true,"observation":{...},"reward":0.0,"done":false,"info":{...}}
+//! ```
+use rogue_gym_core::character::Action;
+use rogue_gym_core::env::{Observation, StepInfo, AI_ACTIONS};
+use rogue_gym_core::error::*;
+use rogue_gym_core::input::KeyMap;
+use rogue_gym_core::GameConfig;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+pub type SessionId = u64;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case", try_from = "RawRequest")]
+pub enum Request {
+    /// starts a session, with the default config if `config` is None
+    Create {
+        #[serde(default)]
+        config: Option<Box<GameConfig>>,
+        /// overrides the seed in the config
+        #[serde(default)]
+        seed: Option<u128>,
+        #[serde(default)]
+        max_steps: Option<usize>,
+    },
+    /// starts a new episode with `seed`, or the seed of the session if None
+    Reset {
+        session: SessionId,
+        #[serde(default)]
+        seed: Option<u128>,
+    },
+    Step {
+        session: SessionId,
+        action: ActionArg,
+    },
+    Observe {
+        session: SessionId,
+    },
+    Close {
+        session: SessionId,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Command {
+    Create,
+    Reset,
+    Step,
+    Observe,
+    Close,
+}
+
+/// `Request` with all fields, since internally tagged enums can't have `u128`s
+#[derive(Deserialize)]
+struct RawRequest {
+    cmd: Command,
+    session: Option<SessionId>,
+    config: Option<GameConfig>,
+    seed: Option<u128>,
+    max_steps: Option<usize>,
+    action: Option<ActionArg>,
+}
+
+impl TryFrom<RawRequest> for Request {
+    type Error = String;
+    fn try_from(raw: RawRequest) -> Result<Self, String> {
+        let cmd = raw.cmd;
+        let missing = |field| format!("missing field `{}` for {:?}", field, cmd);
+        let session = raw.session.ok_or_else(|| missing("session"));
+        Ok(match cmd {
+            Command::Create => Request::Create {
+                config: raw.config.map(Box::new),
+                seed: raw.seed,
+                max_steps: raw.max_steps,
+            },
+            Command::Reset => Request::Reset {
+                session: session?,
+                seed: raw.seed,
+            },
+            Command::Step => Request::Step {
+                session: session?,
+                action: raw.action.ok_or_else(|| missing("action"))?,
+            },
+            Command::Observe => Request::Observe { session: session? },
+            Command::Close => Request::Close { session: session? },
+        })
+    }
+}
+
+/// an action as a key of `KeyMap::AI_KEYS`, or an index of it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ActionArg {
+    Index(usize),
+    Key(char),
+}
+
+impl ActionArg {
+    pub fn to_action(self) -> GameResult<Action> {
+        let idx = match self {
+            ActionArg::Index(i) => Some(i),
+            ActionArg::Key(c) => KeyMap::AI_KEYS.iter().position(|&k| k == c),
+        };
+        idx.and_then(|i| AI_ACTIONS.get(i).cloned()).ok_or_else(|| {
+            ErrorId::InvalidSetting.into_with(move || format!("Invalid action {:?}", self))
+        })
+    }
+}
+
+/// `Observation` as json
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObservationJson {
+    /// rows of the map
+    pub map: Vec<String>,
+    pub status: BTreeMap<String, u32>,
+    /// legality of `KeyMap::AI_KEYS`
+    pub action_mask: Vec<bool>,
+}
+
+impl<'a> From<&'a Observation> for ObservationJson {
+    fn from(obs: &'a Observation) -> Self {
+        let map = obs
+            .map
+            .outer_iter()
+            .map(|row| row.iter().map(|&b| char::from(b)).collect())
+            .collect();
+        let status = obs
+            .status
+            .to_dict_vec()
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v))
+            .collect();
+        ObservationJson {
+            map,
+            status,
+            action_mask: obs.action_mask.clone(),
+        }
+    }
+}
+
+/// a response to a request, where fields not related to the request are omitted
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub observation: Option<ObservationJson>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reward: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<StepInfo>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Response {
+            ok: true,
+            ..Default::default()
+        }
+    }
+    pub fn error(e: &failure::Error) -> Self {
+        let msgs: Vec<_> = e.iter_chain().map(|e| e.to_string()).collect();
+        Response {
+            ok: false,
+            error: Some(msgs.join(": ")),
+            ..Default::default()
+        }
+    }
+    pub fn with_observation(mut self, obs: &Observation) -> Self {
+        self.observation = Some(obs.into());
+        self
+    }
+    /// converts an error response into `Err`
+    pub fn into_result(self) -> GameResult<Self> {
+        if self.ok {
+            Ok(self)
+        } else {
+            let msg = self.error.unwrap_or_default();
+            Err(ErrorId::InvalidSetting.into_with(move || format!("Server error: {}", msg)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn parse_request() {
+        let req: Request =
+            serde_json::from_str(r#"{"cmd": "step", "session": 2, "action": "j"}"#).unwrap();
+        assert_eq!(
+            req,
+            Request::Step {
+                session: 2,
+                action: ActionArg::Key('j'),
+            }
+        );
+        let req: Request = serde_json::from_str(r#"{"cmd": "create", "seed": 5}"#).unwrap();
+        assert_eq!(
+            req,
+            Request::Create {
+                config: None,
+                seed: Some(5),
+                max_steps: None,
+            }
+        );
+        let req: Request =
+            serde_json::from_str(r#"{"cmd": "create", "config": {"seed": 3}}"#).unwrap();
+        match req {
+            Request::Create { config, .. } => assert_eq!(config.unwrap().seed, Some(3)),
+            _ => unreachable!(),
+        }
+        assert!(serde_json::from_str::<Request>(r#"{"cmd": "jump"}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"cmd": "step", "session": 0}"#).is_err());
+        assert_eq!(ActionArg::Index(1).to_action().unwrap(), AI_ACTIONS[1]);
+        assert_eq!(ActionArg::Key('s').to_action().unwrap(), Action::Search);
+        assert!(ActionArg::Key('q').to_action().is_err());
+        assert!(ActionArg::Index(100).to_action().is_err());
+    }
+}
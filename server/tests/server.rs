@@ -0,0 +1,105 @@
+use rogue_gym_core::GameConfig;
+use rogue_gym_server::{ActionArg, Address, Client, Limits, Response, Server};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread;
+
+fn spawn_server(addr: &str, limits: Limits) -> Address {
+    let server = Server::bind(&addr.parse().unwrap(), limits).unwrap();
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+    addr
+}
+
+fn config() -> GameConfig {
+    let mut config = GameConfig::default();
+    config.enemies.enemies.clear();
+    config
+}
+
+fn play(addr: &Address) {
+    let mut client = Client::connect(addr).unwrap();
+    let (session, first) = client.create(Some(config()), Some(1), Some(3)).unwrap();
+    assert_eq!(first.map.len(), 24);
+    assert!(first.map.iter().any(|row| row.contains('@')));
+    assert_eq!(first.status["dungeon_level"], 1);
+    for i in 0..3 {
+        let (_, reward, done, info) = client.step(session, ActionArg::Key('s')).unwrap();
+        assert_eq!(reward, info.reward.total());
+        assert_eq!(done, i == 2);
+    }
+    assert!(client.step(session, ActionArg::Index(0)).is_err());
+    assert_eq!(client.reset(session, None).unwrap(), first);
+    assert_eq!(client.observe(session).unwrap(), first);
+    // sessions with the same seed are the same game
+    let (other, obs) = client.create(Some(config()), Some(1), None).unwrap();
+    assert_ne!(other, session);
+    assert_eq!(obs, first);
+    client.close(session).unwrap();
+    assert!(client.observe(session).is_err());
+    assert!(client.observe(other).is_ok());
+}
+
+#[test]
+fn tcp() {
+    let addr = spawn_server("127.0.0.1:0", Limits::default());
+    play(&addr);
+    // raw json lines
+    let Address::Tcp(sock) = addr else {
+        unreachable!()
+    };
+    let mut stream = TcpStream::connect(sock).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    stream
+        .write_all(b"{\"cmd\": \"create\", \"config\": {\"seed\": 3}}\n{\"cmd\": \"fly\"}\n")
+        .unwrap();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.starts_with("{\"ok\":true,\"session\":0,"));
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert!(line.starts_with("{\"ok\":false,\"error\":\"Invalid request"));
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket() {
+    let path = std::env::temp_dir().join(format!("rogue-gym-test-{}.sock", std::process::id()));
+    let addr = spawn_server(&format!("unix:{}", path.display()), Limits::default());
+    play(&addr);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn limits() {
+    let limits = Limits {
+        max_connections: 1,
+        max_sessions: 1,
+        ..Limits::default()
+    };
+    let addr = spawn_server("127.0.0.1:0", limits);
+    let mut client = Client::connect(&addr).unwrap();
+    let (session, _) = client.create(None, None, None).unwrap();
+    assert!(client.create(None, None, None).is_err());
+    assert!(client.observe(session).is_ok());
+    // the second client is refused
+    let Address::Tcp(sock) = addr else {
+        unreachable!()
+    };
+    let mut line = String::new();
+    BufReader::new(TcpStream::connect(sock).unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let res: Response = serde_json::from_str(&line).unwrap();
+    assert!(res.error.unwrap().starts_with("Too many connections"));
+    // sessions are closed with the connection
+    drop(client);
+    loop {
+        if let Ok(mut client) = Client::connect(&addr) {
+            if client.create(None, None, None).is_ok() {
+                break;
+            }
+        }
+        thread::yield_now();
+    }
+}
@@ -51,7 +51,7 @@ pub trait Screen {
     }
     fn dungeon(&mut self, runtime: &mut RunTime) -> GameResult<()> {
         let mut player_pos = None;
-        runtime.draw_screen(|Positioned(cd, tile)| {
+        runtime.draw_viewport(|Positioned(cd, tile)| {
             if tile.to_byte() == b'@' {
                 player_pos = Some(cd);
             }
@@ -123,8 +123,8 @@ pub fn process_reaction<S: Screen>(
                 screen.pend_message(format!("You got {} {:?}", num, kind))
             }
             GameMsg::SecretDoor => screen.pend_message(format!("You found a secret door")),
-            GameMsg::HitTo(s) => screen.pend_message(format!("You swings and hit {}", s)),
-            GameMsg::HitFrom(s) => screen.pend_message(format!("{} swings and hits you", s)),
+            GameMsg::HitTo(s, _) => screen.pend_message(format!("You swings and hit {}", s)),
+            GameMsg::HitFrom(s, _) => screen.pend_message(format!("{} swings and hits you", s)),
             GameMsg::MissTo(s) => screen.pend_message(format!("You swing and miss {}", s)),
             GameMsg::MissFrom(s) => screen.pend_message(format!("{} swings and misses you", s)),
             GameMsg::Killed(s) => screen.pend_message(format!("You defeated the {}", s)),